@@ -7,6 +7,7 @@ const XCODE_CONFIGURATION_ENV: &'static str = "CONFIGURATION";
 fn main() {
     compile_shader(&PathBuf::from("src/surfaces/sphere_shader.metal"));
     compile_shader(&PathBuf::from("src/lines/line_shader.metal"));
+    compile_shader(&PathBuf::from("src/labels/label_shader.metal"));
     generate_swift_bridge(vec!["src/lines/mod.rs"], "swift-generated")
 }
 
@@ -1,5 +1,3 @@
-use std::f32::consts::PI;
-
 use nalgebra::{point, Point2, Point3, vector, Vector3};
 
 pub struct Plane {
@@ -24,15 +22,10 @@ impl Plane {
         self.o + (self.u * p.x) + (self.v * p.y)
     }
 
-    pub fn circle_points(&self, segments: usize, radius: f32) -> Vec<Point3<f32>> {
-        let segment_theta = (2.0 * PI) / (segments as f32);
-
-        (0..segments)
-            .map(|i| {
-                let angle = segment_theta * (i as f32);
+    // to projects p onto the plane, it's the inverse of from for points on the plane
+    pub fn to(&self, p: Point3<f32>) -> Point2<f32> {
+        let d = p - self.o;
 
-                self.from(point![angle.cos(), angle.sin()] * radius)
-            })
-            .collect()
+        point![d.dot(&self.u), d.dot(&self.v)]
     }
 }
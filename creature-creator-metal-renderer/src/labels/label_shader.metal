@@ -0,0 +1,54 @@
+#include <metal_stdlib>
+
+using namespace metal;
+
+struct Instance {
+    float3 origin   [[attribute(0)]];
+    float3 right    [[attribute(1)]];
+    float3 up       [[attribute(2)]];
+    float2 uv_min   [[attribute(3)]];
+    float2 uv_max   [[attribute(4)]];
+    float3 color    [[attribute(5)]];
+};
+
+struct VertexOut {
+    float4 position [[position]];
+    float4 color;
+    float2 uv;
+};
+
+struct Uniform {
+    float4x4 camera;
+    float3 camera_position;
+};
+
+vertex VertexOut
+vertex_main(Instance inst [[stage_in]],
+            uint vid [[vertex_id]],
+            const device float2 *geometry [[buffer(0)]],
+            constant Uniform &uniform [[buffer(2)]])
+{
+    // The quad goes from 0 to 1, the atlas is upside down compared to it
+    float2 corner = geometry[vid];
+    float3 pos = inst.origin + (inst.right * corner.x) + (inst.up * corner.y);
+
+    VertexOut out;
+    out.position = uniform.camera * float4(pos, 1.0);
+    out.color = float4(inst.color, 1.0);
+    out.uv = mix(inst.uv_min, inst.uv_max, float2(corner.x, 1.0 - corner.y));
+    return out;
+}
+
+fragment float4
+fragment_main(VertexOut inst [[stage_in]],
+              texture2d<float> atlas [[texture(0)]])
+{
+    // Nearest keeps the glyph's pixels sharp
+    constexpr sampler atlas_sampler(filter::nearest);
+
+    if (atlas.sample(atlas_sampler, inst.uv).r < 0.5) {
+        discard_fragment();
+    }
+
+    return inst.color;
+}
@@ -0,0 +1,3 @@
+pub use pipeline::LabelPipeline;
+
+mod pipeline;
@@ -0,0 +1,207 @@
+use std::ffi::c_void;
+use std::mem::size_of;
+
+use metal::{
+    DeviceRef, MTLPixelFormat, MTLPrimitiveType, MTLRegion, MTLTextureUsage, MTLVertexFormat,
+    MTLVertexStepFunction, NSUInteger, RenderCommandEncoderRef, RenderPipelineDescriptor,
+    RenderPipelineState, Texture, TextureDescriptor, VertexAttributeDescriptor,
+    VertexBufferLayoutDescriptor, VertexDescriptor,
+};
+
+use creature_creator_renderer::labels::{FontAtlas, GlyphQuad};
+
+use crate::shared::{Shared, SharedVec};
+
+// Just a quad
+const VERTEX_COUNT: usize = 4;
+// The glyph buffer starts with room for this many characters, it grows when there are more
+const INITIAL_GLYPH_CAPACITY: usize = 256;
+const LABEL_SHADER_LIBRARY: &[u8] = include_bytes!("label_shader.metallib");
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+struct Vertex {
+    position: [f32; 2],
+}
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+struct Glyph {
+    origin: [f32; 3],
+    right: [f32; 3],
+    up: [f32; 3],
+    uv_min: [f32; 2],
+    uv_max: [f32; 2],
+    color: [f32; 3],
+}
+
+impl From<&GlyphQuad> for Glyph {
+    fn from(quad: &GlyphQuad) -> Self {
+        Glyph {
+            origin: quad.origin.coords.data.0[0],
+            right: quad.right.data.0[0],
+            up: quad.up.data.0[0],
+            uv_min: quad.uv_min.coords.data.0[0],
+            uv_max: quad.uv_max.coords.data.0[0],
+            color: quad.color.data.0[0],
+        }
+    }
+}
+
+pub struct LabelPipeline {
+    pipeline: RenderPipelineState,
+    atlas: Texture,
+
+    vertices: Shared<[Vertex; VERTEX_COUNT]>,
+
+    glyph_count: usize,
+    glyphs: SharedVec<Glyph>,
+}
+
+// Initialization
+impl LabelPipeline {
+    fn new_pipeline(device: &DeviceRef) -> RenderPipelineState {
+        let library = device
+            .new_library_with_data(LABEL_SHADER_LIBRARY)
+            .expect("label shader should load without error");
+        let vertex_function = library
+            .get_function("vertex_main", None)
+            .expect("function `vertex_main` to exist");
+        let frag_function = library
+            .get_function("fragment_main", None)
+            .expect("function `fragment_main` to exist");
+
+        let pipeline_descriptor = RenderPipelineDescriptor::new();
+        pipeline_descriptor.set_vertex_function(Some(&vertex_function));
+        pipeline_descriptor.set_fragment_function(Some(&frag_function));
+        pipeline_descriptor.set_depth_attachment_pixel_format(MTLPixelFormat::Depth32Float);
+
+        let attachment = pipeline_descriptor
+            .color_attachments()
+            .object_at(0)
+            .unwrap();
+        attachment.set_pixel_format(MTLPixelFormat::RGBA8Unorm);
+
+        // Instance attributes, in the order they're laid out in Glyph
+        let vertex_descriptor = VertexDescriptor::new();
+        let attributes = [
+            MTLVertexFormat::Float3,
+            MTLVertexFormat::Float3,
+            MTLVertexFormat::Float3,
+            MTLVertexFormat::Float2,
+            MTLVertexFormat::Float2,
+            MTLVertexFormat::Float3,
+        ];
+        let mut offset = 0;
+        for (i, format) in attributes.into_iter().enumerate() {
+            let attribute = VertexAttributeDescriptor::new();
+            attribute.set_format(format);
+            attribute.set_buffer_index(1);
+            attribute.set_offset(offset as NSUInteger);
+            vertex_descriptor
+                .attributes()
+                .set_object_at(i as NSUInteger, Some(&attribute));
+
+            offset += match format {
+                MTLVertexFormat::Float2 => size_of::<[f32; 2]>(),
+                _ => size_of::<[f32; 3]>(),
+            };
+        }
+        assert_eq!(offset, size_of::<Glyph>());
+
+        // Buffer layouts
+        let instance_buffer = VertexBufferLayoutDescriptor::new();
+        instance_buffer.set_stride(size_of::<Glyph>() as NSUInteger);
+        instance_buffer.set_step_function(MTLVertexStepFunction::PerInstance);
+        instance_buffer.set_step_rate(1);
+        vertex_descriptor
+            .layouts()
+            .set_object_at(1, Some(&instance_buffer));
+
+        pipeline_descriptor.set_vertex_descriptor(Some(vertex_descriptor));
+
+        device
+            .new_render_pipeline_state(pipeline_descriptor.as_ref())
+            .unwrap()
+    }
+
+    fn new_atlas_texture(device: &DeviceRef) -> Texture {
+        let atlas = FontAtlas::new();
+
+        let texture_descriptor = TextureDescriptor::new();
+        texture_descriptor.set_width(atlas.width as u64);
+        texture_descriptor.set_height(atlas.height as u64);
+        texture_descriptor.set_pixel_format(MTLPixelFormat::R8Unorm);
+        texture_descriptor.set_usage(MTLTextureUsage::ShaderRead);
+
+        let texture = device.new_texture(&texture_descriptor);
+        texture.replace_region(
+            MTLRegion::new_2d(0, 0, atlas.width as u64, atlas.height as u64),
+            0,
+            atlas.pixels.as_ptr() as *const c_void,
+            atlas.width as u64,
+        );
+
+        texture
+    }
+
+    fn new_vertex_buffer(device: &DeviceRef) -> Shared<[Vertex; VERTEX_COUNT]> {
+        let vertices = [
+            Vertex {
+                position: [0.0, 0.0],
+            },
+            Vertex {
+                position: [0.0, 1.0],
+            },
+            Vertex {
+                position: [1.0, 0.0],
+            },
+            Vertex {
+                position: [1.0, 1.0],
+            },
+        ];
+
+        Shared::new(device, vertices)
+    }
+
+    fn new_glyph_buffer(device: &DeviceRef) -> SharedVec<Glyph> {
+        SharedVec::with_capacity(device, INITIAL_GLYPH_CAPACITY)
+    }
+
+    pub fn new(device: &DeviceRef) -> Self {
+        Self {
+            pipeline: Self::new_pipeline(device),
+            atlas: Self::new_atlas_texture(device),
+            vertices: Self::new_vertex_buffer(device),
+            glyph_count: 0,
+            glyphs: Self::new_glyph_buffer(device),
+        }
+    }
+}
+
+// Drawing
+impl LabelPipeline {
+    fn encode(&self, encoder: &RenderCommandEncoderRef) {
+        encoder.set_render_pipeline_state(&self.pipeline);
+        encoder.set_vertex_buffer(0, Some(self.vertices.buffer()), 0);
+        encoder.set_vertex_buffer(1, Some(self.glyphs.buffer()), 0);
+        encoder.set_fragment_texture(0, Some(&self.atlas));
+
+        encoder.draw_primitives_instanced(
+            MTLPrimitiveType::TriangleStrip,
+            0,
+            VERTEX_COUNT as NSUInteger,
+            self.glyph_count as NSUInteger,
+        );
+    }
+
+    pub fn draw(&mut self, encoder: &RenderCommandEncoderRef, quads: &[GlyphQuad]) {
+        self.glyphs.reserve(encoder.device(), quads.len());
+        for (glyph, quad) in self.glyphs.iter_mut().zip(quads) {
+            *glyph = Glyph::from(quad);
+        }
+        self.glyph_count = quads.len();
+
+        self.encode(encoder);
+    }
+}
@@ -1,9 +1,11 @@
 pub use renderer::MetalRenderer;
+pub use surfaces::{NormalReport, SamplingStats};
 
 mod shared;
 mod uniforms;
 
 mod geometry;
+mod labels;
 mod lines;
 mod renderer;
 mod surfaces;
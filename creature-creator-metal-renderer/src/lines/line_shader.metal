@@ -8,16 +8,14 @@ struct Instance {
     float3 end          [[attribute(1)]];
     float3 color        [[attribute(2)]];
     float thickness     [[attribute(3)]];
-    float segment_size  [[attribute(4)]];
-    uint style          [[attribute(5)]];
-    float t_offset      [[attribute(6)]];
+    uint style          [[attribute(4)]];
 };
 
 struct VertexOut {
     float4 position [[position]];
     float4 color;
-    float t; // Position along the line
-    float segment_size;
+    float2 corner; // Where in the quad, from -1 to 1
+    uint style [[flat]];
 };
 
 struct Uniform {
@@ -33,12 +31,8 @@ vertex_main(Instance inst [[stage_in]],
 {
     float3 origin = (inst.start + inst.end) / 2.0;
     float size = length(inst.start - inst.end);
-    float2 vert = geometry[vid + (4 * inst.style)] * (float2(size, inst.thickness) / 2.0);
-    
-    float t = inst.t_offset;
-    if (vert.x > 0) {
-        t += size;
-    }
+    float2 corner = geometry[vid + (4 * inst.style)];
+    float2 vert = corner * (float2(size, inst.thickness) / 2.0);
     
     // Construct a plane facing the camera
     float3 to_camera = uniform.camera_position - origin;
@@ -49,8 +43,8 @@ vertex_main(Instance inst [[stage_in]],
     VertexOut out;
     out.position = uniform.camera * float4(origin + pos, 1.0);
     out.color = float4(inst.color, 1.0);
-    out.t = t;
-    out.segment_size = inst.segment_size;
+    out.corner = corner;
+    out.style = inst.style;
     return
     out;
 }
@@ -58,17 +52,10 @@ vertex_main(Instance inst [[stage_in]],
 fragment float4
 fragment_main(VertexOut inst [[stage_in]])
 {
-    if (inst.segment_size == 0.0) {
-        return inst.color;
-    }
-    
-    float y = (sin((inst.t * M_PI_F) / inst.segment_size) / 2.0) + 0.5;
-    
-    if (y > 0.5) {
-        return
-        float4(inst.color.xyz, 1.0);
-    } else {
-        return
-        float4(inst.color.xyz, 0.0);
+    // Dots are round
+    if (inst.style == 2 && length(inst.corner) > 1.0) {
+        discard_fragment();
     }
+
+    return inst.color;
 }
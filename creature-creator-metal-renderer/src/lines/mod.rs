@@ -1,10 +1,8 @@
 use metal::foreign_types::ForeignTypeRef;
 use metal::{DeviceRef, MTLDevice, MTLRenderCommandEncoder, RenderCommandEncoderRef};
 
-pub use lines::line_segments;
 pub use pipeline::{LinePipeline, LineSegment};
 
-mod lines;
 mod pipeline;
 
 // #[rustfmt::skip]
@@ -15,7 +13,7 @@ mod ffi {
         type LineSegment;
 
         #[swift_bridge(init)]
-        fn new_ugly(
+        fn from_components(
             a_x: f32,
             a_y: f32,
             a_z: f32,
@@ -26,9 +24,7 @@ mod ffi {
             color_g: f32,
             color_b: f32,
             thickness: f32,
-            segment_size: f32,
             style: u32,
-            t_offset: f32,
         ) -> LineSegment;
     }
 
@@ -7,12 +7,15 @@ use metal::{
 };
 use nalgebra::{Point3, Vector3};
 
-use crate::shared::Shared;
+use creature_creator_renderer::lines::{Segment, SegmentStyle};
+
+use crate::shared::{Shared, SharedVec};
 
 const VERTEX_COUNT: usize = 4;
 // Just a quad
-const STYLE_COUNT: usize = 2;
-const MAX_LINE_SEGMENTS: usize = 1000;
+const STYLE_COUNT: usize = 3;
+// The segment buffer starts with room for this many segments, it grows when there are more
+const INITIAL_SEGMENT_CAPACITY: usize = 1024;
 const LINE_SHADER_LIBRARY: &[u8] = include_bytes!("line_shader.metallib");
 
 #[derive(Copy, Clone)]
@@ -28,10 +31,8 @@ pub struct LineSegment {
     end: [f32; 3],
     color: [f32; 3],
     thickness: f32,
-    segment_size: f32,
-    // 0 means no segments
+    // 0 is a line, 1 an arrow head and 2 a dot
     style: u32,
-    t_offset: f32,
 }
 
 impl LineSegment {
@@ -40,22 +41,19 @@ impl LineSegment {
         b: Point3<f32>,
         color: Vector3<f32>,
         thickness: f32,
-        segment_size: f32,
         style: u32,
-        t_offset: f32,
     ) -> Self {
         Self {
             start: a.coords.data.0[0],
             end: b.coords.data.0[0],
             color: color.data.0[0],
             thickness,
-            segment_size,
             style,
-            t_offset,
         }
     }
 
-    pub fn new_ugly(
+    // from_components is new for Swift, which can only hand over plain numbers
+    pub fn from_components(
         a_x: f32,
         a_y: f32,
         a_z: f32,
@@ -66,19 +64,34 @@ impl LineSegment {
         color_g: f32,
         color_b: f32,
         thickness: f32,
-        segment_size: f32,
         style: u32,
-        t_offset: f32,
     ) -> Self {
-        Self {
-            start: [a_x, a_y, a_z],
-            end: [b_x, b_y, b_z],
-            color: [color_r, color_g, color_b],
+        Self::new(
+            Point3::new(a_x, a_y, a_z),
+            Point3::new(b_x, b_y, b_z),
+            Vector3::new(color_r, color_g, color_b),
             thickness,
-            segment_size,
             style,
-            t_offset,
-        }
+        )
+    }
+}
+
+impl From<&Segment> for LineSegment {
+    fn from(segment: &Segment) -> Self {
+        let style = match segment.style {
+            SegmentStyle::Line => 0,
+            SegmentStyle::ArrowHead => 1,
+            SegmentStyle::Dot => 2,
+        };
+
+        // Dashes are already cut out, so the shader draws every segment solid
+        LineSegment::new(
+            segment.start,
+            segment.end,
+            segment.color,
+            segment.thickness,
+            style,
+        )
     }
 }
 
@@ -88,7 +101,7 @@ pub struct LinePipeline {
     vertices: Shared<[Vertex; VERTEX_COUNT * STYLE_COUNT]>,
 
     segment_count: usize,
-    segments: Shared<[LineSegment; MAX_LINE_SEGMENTS]>,
+    segments: SharedVec<LineSegment>,
 }
 
 // Initialization
@@ -158,29 +171,13 @@ impl LinePipeline {
             .attributes()
             .set_object_at(3, Some(&thickness_attribute));
 
-        let segment_size_attribute = VertexAttributeDescriptor::new();
-        segment_size_attribute.set_format(MTLVertexFormat::Float);
-        segment_size_attribute.set_buffer_index(1);
-        segment_size_attribute.set_offset((size_of::<[f32; 10]>()) as NSUInteger);
-        vertex_descriptor
-            .attributes()
-            .set_object_at(4, Some(&segment_size_attribute));
-
         let style_attribute = VertexAttributeDescriptor::new();
         style_attribute.set_format(MTLVertexFormat::UInt);
         style_attribute.set_buffer_index(1);
-        style_attribute.set_offset((size_of::<[f32; 11]>()) as NSUInteger);
+        style_attribute.set_offset((size_of::<[f32; 10]>()) as NSUInteger);
         vertex_descriptor
             .attributes()
-            .set_object_at(5, Some(&style_attribute));
-
-        let t_offset_attribute = VertexAttributeDescriptor::new();
-        t_offset_attribute.set_format(MTLVertexFormat::Float);
-        t_offset_attribute.set_buffer_index(1);
-        t_offset_attribute.set_offset((size_of::<[f32; 11]>() + size_of::<u32>()) as NSUInteger);
-        vertex_descriptor
-            .attributes()
-            .set_object_at(6, Some(&t_offset_attribute));
+            .set_object_at(4, Some(&style_attribute));
 
         // Buffer layouts
         let instance_buffer = VertexBufferLayoutDescriptor::new();
@@ -226,13 +223,26 @@ impl LinePipeline {
             Vertex {
                 position: [1.0, 1.0],
             },
+            // Dot style, the corners are cut off in the fragment shader
+            Vertex {
+                position: [-1.0, -1.0],
+            },
+            Vertex {
+                position: [-1.0, 1.0],
+            },
+            Vertex {
+                position: [1.0, -1.0],
+            },
+            Vertex {
+                position: [1.0, 1.0],
+            },
         ];
 
         Shared::new(device, vertices)
     }
 
-    fn new_segment_buffer(device: &DeviceRef) -> Shared<[LineSegment; MAX_LINE_SEGMENTS]> {
-        Shared::new_zeroed(device)
+    fn new_segment_buffer(device: &DeviceRef) -> SharedVec<LineSegment> {
+        SharedVec::with_capacity(device, INITIAL_SEGMENT_CAPACITY)
     }
 
     pub fn new(device: &DeviceRef) -> Self {
@@ -262,9 +272,22 @@ impl LinePipeline {
 
     pub fn draw(&mut self, encoder: &RenderCommandEncoderRef, segments: &[LineSegment]) {
         let segment_count = segments.len();
-        self.segments[..segment_count].copy_from_slice(&segments);
+        self.segments.reserve(encoder.device(), segment_count);
+        self.segments[..segment_count].copy_from_slice(segments);
         self.segment_count = segment_count;
 
         self.encode(encoder);
     }
+
+    // draw_segments converts the segments straight into the segment buffer, so nothing is
+    // allocated from frame to frame
+    pub fn draw_segments(&mut self, encoder: &RenderCommandEncoderRef, segments: &[Segment]) {
+        self.segments.reserve(encoder.device(), segments.len());
+        for (line_segment, segment) in self.segments.iter_mut().zip(segments) {
+            *line_segment = LineSegment::from(segment);
+        }
+        self.segment_count = segments.len();
+
+        self.encode(encoder);
+    }
 }
@@ -1,3 +1,6 @@
+use std::io;
+use std::io::Write;
+
 use cocoa::appkit::NSView;
 use cocoa::base::id;
 use core_graphics_types::geometry::CGSize;
@@ -7,13 +10,21 @@ use metal::{
     MTLCompareFunction, MTLLoadAction, MTLPixelFormat, MTLStorageMode, MTLStoreAction,
     MTLTextureUsage, MetalLayer, Texture, TextureDescriptor,
 };
+use nalgebra::Point2;
 use raw_window_handle::{RawWindowHandle, WindowHandle};
 
-use creature_creator_renderer::{Camera, Kind, RenderGraph, Renderer};
+use creature_creator_renderer::export::points::PointFormat;
+use creature_creator_renderer::export::svg;
+use creature_creator_renderer::labels::{label_quads, labels_from_graph};
+use creature_creator_renderer::lines::{dashed_segments_from_graph, LineBatch};
+use creature_creator_renderer::mesh::Mesh;
+use creature_creator_renderer::surface::Surface;
+use creature_creator_renderer::{Camera, Kind, NodeId, RenderGraph, Renderer, Viewport};
 
-use crate::lines::{line_segments, LinePipeline};
+use crate::labels::LabelPipeline;
+use crate::lines::LinePipeline;
 use crate::shared::Shared;
-use crate::surfaces::{Surface, SurfacePipeline};
+use crate::surfaces::{NormalReport, RadiusBounds, SamplingStats, SurfacePipeline};
 use crate::uniforms::Uniforms;
 
 fn create_metal_layer(device: &DeviceRef, window_handle: &WindowHandle) -> MetalLayer {
@@ -63,14 +74,20 @@ pub struct MetalRenderer {
     depth_target: Texture,
 
     camera: Camera,
+    // The drawable's size in pixels
+    size: (u32, u32),
     uniforms: Shared<Uniforms>,
+    // The last frame's lines, kept so the allocation is reused
+    lines: LineBatch,
 
     sphere_pipeline: SurfacePipeline,
     line_pipeline: LinePipeline,
+    label_pipeline: LabelPipeline,
 }
 
 impl MetalRenderer {
-    pub fn new(window: &WindowHandle, mut camera: Camera) -> Self {
+    // new draws the surface with at most `max_particles` particles
+    pub fn new(window: &WindowHandle, mut camera: Camera, max_particles: usize) -> Self {
         let device = Device::system_default().expect("no device found");
         let command_queue = device.new_command_queue();
 
@@ -82,9 +99,10 @@ impl MetalRenderer {
         camera.aspect_ratio_updated(1.0);
         let uniforms = Shared::new(&device, Uniforms::new(&camera));
 
-        let sphere_pipeline = SurfacePipeline::new(&device);
+        let sphere_pipeline = SurfacePipeline::new(&device, max_particles);
 
         let widget_pipeline = LinePipeline::new(&device);
+        let label_pipeline = LabelPipeline::new(&device);
 
         MetalRenderer {
             device,
@@ -93,11 +111,54 @@ impl MetalRenderer {
             depth_state,
             depth_target,
             camera,
+            size: (10, 10),
             uniforms,
+            lines: LineBatch::new(),
             sphere_pipeline,
             line_pipeline: widget_pipeline,
+            label_pipeline,
         }
     }
+
+    // export_points writes the surface particles as they were last drawn
+    pub fn export_points<W: Write>(&self, format: PointFormat, writer: &mut W) -> io::Result<()> {
+        self.sphere_pipeline.export_points(format, writer)
+    }
+
+    // export_diagram writes the graph's lines and labels as an SVG the size of the window, hidden
+    // behind the surface particles as they were last drawn
+    pub fn export_diagram<W: Write>(&self, graph: &RenderGraph, writer: &mut W) -> io::Result<()> {
+        let viewport = Viewport::new(&self.camera, self.size);
+
+        svg::write(
+            &dashed_segments_from_graph(graph, &viewport),
+            &labels_from_graph(graph),
+            &self.sphere_pipeline.particles(),
+            &viewport,
+            writer,
+        )
+    }
+
+    // pick is the shape under a pixel of the window, mirrored copies resolve to their source node
+    pub fn pick(&self, graph: &RenderGraph, pixel: Point2<f32>) -> Option<NodeId> {
+        let viewport = Viewport::new(&self.camera, self.size);
+        graph.pick(viewport.eye(), viewport.ray(pixel))
+    }
+
+    // particle_mesh triangulates the surface particles, matching what was last drawn
+    pub fn particle_mesh(&self) -> Mesh {
+        self.sphere_pipeline.particle_mesh()
+    }
+
+    // sampling_stats describes how the surface particles changed in the last draw
+    pub fn sampling_stats(&self) -> &SamplingStats {
+        self.sphere_pipeline.sampling_stats()
+    }
+
+    // particle_normal_report checks the surface particles' normals, in debug builds only
+    pub fn particle_normal_report(&self) -> NormalReport {
+        self.sphere_pipeline.normal_report()
+    }
 }
 
 impl Renderer for MetalRenderer {
@@ -106,6 +167,7 @@ impl Renderer for MetalRenderer {
             .set_drawable_size(CGSize::new(new_size.0 as f64, new_size.1 as f64));
 
         self.depth_target = prepare_depth_target(&self.device, new_size);
+        self.size = new_size;
 
         self.camera
             .aspect_ratio_updated(new_size.0 as f32 / new_size.1 as f32);
@@ -118,14 +180,24 @@ impl Renderer for MetalRenderer {
 
     fn draw(&mut self, graph: &RenderGraph) {
         let mut surface = Surface::new();
-        let mut segments = vec![];
+        let mut glyphs = vec![];
+        let viewport = Viewport::new(&self.camera, self.size);
+        let lines = &mut self.lines;
+        lines.clear();
 
         graph.walk(|transform, kind| match kind {
-            Kind::Line(l) => line_segments(l, &mut segments, &transform),
-            Kind::Shape(s) => surface.push(
+            Kind::Line(l) => lines.push(l, &transform, &viewport),
+            Kind::Label(l) => {
+                label_quads(l, &mut glyphs, &transform, &viewport);
+                lines.push_leader(l, &transform, &viewport);
+            }
+            Kind::Shape(s, material, displacements) => surface.push(
                 transform.try_inverse().expect("transform can be inverted"),
                 *s,
+                *material,
+                displacements,
             ),
+            Kind::Symmetry(_) => {}
         });
 
         let drawable = match self.layer.next_drawable() {
@@ -152,11 +224,13 @@ impl Renderer for MetalRenderer {
         if !surface.empty() {
             encoder.set_depth_stencil_state(&self.depth_state);
             encoder.set_vertex_buffer(2, Some(self.uniforms.buffer()), 0);
-            self.sphere_pipeline.draw(encoder, &surface, 0.4);
+            self.sphere_pipeline
+                .draw(encoder, &surface, RadiusBounds::new(0.15, 0.4));
         }
         encoder.set_depth_stencil_state(&self.depth_state);
         encoder.set_vertex_buffer(2, Some(self.uniforms.buffer()), 0);
-        self.line_pipeline.draw(encoder, &segments);
+        self.line_pipeline.draw_segments(encoder, self.lines.segments());
+        self.label_pipeline.draw(encoder, &glyphs);
 
         encoder.end_encoding();
         command_buffer.present_drawable(drawable);
@@ -1,9 +1,11 @@
 use std::alloc::{alloc_zeroed, Layout};
+use std::marker::PhantomData;
 use std::mem;
 use std::mem::size_of;
 use std::ops::{Deref, DerefMut};
 use std::pin::Pin;
 use std::ptr::addr_of;
+use std::slice;
 
 use metal::{Buffer, DeviceRef, MTLResourceOptions};
 
@@ -61,3 +63,60 @@ impl<T: Sized + Unpin> DerefMut for Shared<T> {
         self.value.deref_mut()
     }
 }
+
+// SharedVec is a gpu buffer of Ts that can be grown, for when the count isn't known up front
+// T needs to be plain data, a new buffer's contents are whatever was in memory
+pub struct SharedVec<T: Copy> {
+    buffer: Buffer,
+    capacity: usize,
+    _t: PhantomData<T>,
+}
+
+impl<T: Copy> SharedVec<T> {
+    pub fn with_capacity(device: &DeviceRef, capacity: usize) -> Self {
+        // Metal doesn't allow empty buffers
+        let capacity = capacity.max(1);
+        let buffer = device.new_buffer(
+            (capacity * size_of::<T>()) as u64,
+            MTLResourceOptions::StorageModeShared,
+        );
+
+        SharedVec {
+            buffer,
+            capacity,
+            _t: PhantomData,
+        }
+    }
+
+    // reserve makes room for at least `len` Ts, doubling the capacity until there's enough
+    // Growing replaces the buffer without copying, so it needs filling and binding again
+    pub fn reserve(&mut self, device: &DeviceRef, len: usize) {
+        if len > self.capacity {
+            let capacity = len.next_power_of_two();
+            *self = Self::with_capacity(device, capacity);
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+}
+
+impl<T: Copy> Deref for SharedVec<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        // This is safe because the buffer is shared memory holding `capacity` Ts
+        unsafe { slice::from_raw_parts(self.buffer.contents() as *const T, self.capacity) }
+    }
+}
+
+impl<T: Copy> DerefMut for SharedVec<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { slice::from_raw_parts_mut(self.buffer.contents() as *mut T, self.capacity) }
+    }
+}
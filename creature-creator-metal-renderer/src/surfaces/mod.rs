@@ -1,5 +1,5 @@
 pub use pipeline::SurfacePipeline;
-pub use sampling::Surface;
+pub use sampling::{NormalReport, RadiusBounds, SamplingStats};
 
 mod pipeline;
 mod sampling;
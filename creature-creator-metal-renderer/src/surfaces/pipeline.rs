@@ -1,6 +1,7 @@
 use std::f32::consts::PI;
+use std::io;
+use std::io::Write;
 use std::mem::size_of;
-use std::time::Instant;
 
 use metal::{
     DeviceRef, MTLPixelFormat, MTLPrimitiveType, MTLVertexFormat, MTLVertexStepFunction,
@@ -8,13 +9,19 @@ use metal::{
     VertexAttributeDescriptor, VertexBufferLayoutDescriptor, VertexDescriptor,
 };
 
-use crate::shared::Shared;
-use crate::surfaces::sampling::{SamplingSystem, Surface, MAX_PARTICLE_COUNT};
+use creature_creator_renderer::export::points;
+use creature_creator_renderer::export::points::{Point, PointFormat};
+use creature_creator_renderer::mesh::Mesh;
+use creature_creator_renderer::surface::Surface;
+
+use crate::shared::{Shared, SharedVec};
+use crate::surfaces::sampling::{NormalReport, RadiusBounds, SamplingStats, SamplingSystem};
 
 const SPHERE_SLICES: f32 = 16.0 / 2.0;
 const SPHERE_RINGS: f32 = 16.0 / 2.0;
 const SPHERE_VERTEX_COUNT: usize = (SPHERE_RINGS as usize + 2) * SPHERE_SLICES as usize * 6;
-const MAX_INSTANCE_COUNT: usize = MAX_PARTICLE_COUNT;
+// The instance buffer starts with room for this many particles, it grows when there are more
+const INITIAL_INSTANCE_CAPACITY: usize = 4096;
 const SPHERE_SHADER_LIBRARY: &[u8] = include_bytes!("sphere_shader.metallib");
 
 #[derive(Copy, Clone)]
@@ -29,15 +36,19 @@ pub struct Sphere {
     pub center: [f32; 3],
     pub radius: f32,
     pub normal: [f32; 3],
+    pub color: [f32; 3],
+    pub roughness: f32,
 }
 
 pub struct SurfacePipeline {
     pipeline: RenderPipelineState,
 
     sampling_system: SamplingSystem,
+    sampling_stats: SamplingStats,
+    normal_report: NormalReport,
 
     instance_count: usize,
-    instances: Shared<[Sphere; MAX_INSTANCE_COUNT]>,
+    instances: SharedVec<Sphere>,
     vertices: Shared<[Vertex; SPHERE_VERTEX_COUNT]>,
 }
 
@@ -137,13 +148,29 @@ impl SurfacePipeline {
             .attributes()
             .set_object_at(2, Some(&radius_attribute));
 
+        let normal_attribute = VertexAttributeDescriptor::new();
+        normal_attribute.set_format(MTLVertexFormat::Float3);
+        normal_attribute.set_buffer_index(1);
+        normal_attribute.set_offset((size_of::<f32>() * 4) as NSUInteger);
+        vertex_descriptor
+            .attributes()
+            .set_object_at(3, Some(&normal_attribute));
+
         let color_attribute = VertexAttributeDescriptor::new();
         color_attribute.set_format(MTLVertexFormat::Float3);
         color_attribute.set_buffer_index(1);
-        color_attribute.set_offset((size_of::<f32>() * 4) as NSUInteger);
+        color_attribute.set_offset((size_of::<f32>() * 7) as NSUInteger);
+        vertex_descriptor
+            .attributes()
+            .set_object_at(4, Some(&color_attribute));
+
+        let roughness_attribute = VertexAttributeDescriptor::new();
+        roughness_attribute.set_format(MTLVertexFormat::Float);
+        roughness_attribute.set_buffer_index(1);
+        roughness_attribute.set_offset((size_of::<f32>() * 10) as NSUInteger);
         vertex_descriptor
             .attributes()
-            .set_object_at(3, Some(&color_attribute));
+            .set_object_at(5, Some(&roughness_attribute));
 
         // Buffer layouts
         let vertex_buffer = VertexBufferLayoutDescriptor::new();
@@ -155,7 +182,7 @@ impl SurfacePipeline {
             .set_object_at(0, Some(&vertex_buffer));
 
         let instance_buffer = VertexBufferLayoutDescriptor::new();
-        instance_buffer.set_stride((size_of::<f32>() * 7) as NSUInteger);
+        instance_buffer.set_stride(size_of::<Sphere>() as NSUInteger);
         instance_buffer.set_step_function(MTLVertexStepFunction::PerInstance);
         instance_buffer.set_step_rate(1);
         vertex_descriptor
@@ -169,18 +196,21 @@ impl SurfacePipeline {
             .unwrap()
     }
 
-    fn new_instance_buffer(device: &DeviceRef) -> Shared<[Sphere; MAX_INSTANCE_COUNT]> {
-        Shared::new_zeroed(device)
+    fn new_instance_buffer(device: &DeviceRef) -> SharedVec<Sphere> {
+        SharedVec::with_capacity(device, INITIAL_INSTANCE_CAPACITY)
     }
 
     fn new_vertices_buffer(device: &DeviceRef) -> Shared<[Vertex; SPHERE_VERTEX_COUNT]> {
         Shared::new(device, sphere_vertices(SPHERE_RINGS, SPHERE_SLICES))
     }
 
-    pub fn new(device: &DeviceRef) -> Self {
+    // new caps the surface at `max_particles`, past it the particles get bigger instead
+    pub fn new(device: &DeviceRef, max_particles: usize) -> Self {
         Self {
             pipeline: Self::new_pipeline(device),
-            sampling_system: SamplingSystem::new(),
+            sampling_system: SamplingSystem::new().max_particles(max_particles),
+            sampling_stats: SamplingStats::default(),
+            normal_report: NormalReport::default(),
             instance_count: 0,
             instances: Self::new_instance_buffer(device),
             vertices: Self::new_vertices_buffer(device),
@@ -190,25 +220,31 @@ impl SurfacePipeline {
 
 // Drawing
 impl SurfacePipeline {
-    fn sample_surface(&mut self, surface: &Surface, sample_radius: f32) {
-        let start = Instant::now();
+    fn sample_surface(
+        &mut self,
+        device: &DeviceRef,
+        surface: &Surface,
+        sample_radius: RadiusBounds,
+    ) {
+        self.sampling_stats = self.sampling_system.update(sample_radius, surface);
+        // Checking every normal is slow, so release builds skip it
+        if cfg!(debug_assertions) {
+            self.normal_report = self.sampling_system.validate_normals(surface);
+        }
 
-        self.sampling_system.update(sample_radius, surface);
+        let particles = self.sampling_system.positions();
+        self.instances.reserve(device, particles.len());
+        self.instance_count = particles.len();
 
-        let mut max_i = 0;
-        for (i, (position, normal, radius)) in self.sampling_system.positions().enumerate() {
+        for (i, (position, normal, radius, material)) in particles.enumerate() {
             self.instances[i] = Sphere {
                 center: position.coords.data.0[0],
                 radius,
                 normal: normal.data.0[0],
+                color: material.color.data.0[0],
+                roughness: material.roughness,
             };
-
-            max_i = i
         }
-
-        self.instance_count = max_i + 1;
-        let sampling_duration = start.elapsed();
-        dbg!(sampling_duration);
     }
 
     fn encode(&self, encoder: &RenderCommandEncoderRef) {
@@ -228,9 +264,36 @@ impl SurfacePipeline {
         &mut self,
         encoder: &RenderCommandEncoderRef,
         surface: &Surface,
-        sample_radius: f32,
+        sample_radius: RadiusBounds,
     ) {
-        self.sample_surface(surface, sample_radius);
+        self.sample_surface(encoder.device(), surface, sample_radius);
         self.encode(encoder);
     }
 }
+
+impl SurfacePipeline {
+    // export_points writes the particles from the last draw
+    pub fn export_points<W: Write>(&self, format: PointFormat, writer: &mut W) -> io::Result<()> {
+        points::write(&self.sampling_system.points(), format, writer)
+    }
+
+    // particles are the particles from the last draw
+    pub fn particles(&self) -> Vec<Point> {
+        self.sampling_system.points()
+    }
+
+    // particle_mesh triangulates the particles from the last draw
+    pub fn particle_mesh(&self) -> Mesh {
+        self.sampling_system.mesh()
+    }
+
+    // sampling_stats describes the sampling update from the last draw
+    pub fn sampling_stats(&self) -> &SamplingStats {
+        &self.sampling_stats
+    }
+
+    // normal_report checks the particle normals from the last draw, it's empty in release builds
+    pub fn normal_report(&self) -> NormalReport {
+        self.normal_report
+    }
+}
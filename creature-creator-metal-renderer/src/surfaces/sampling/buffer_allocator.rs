@@ -1,84 +1,223 @@
 // A BufferAllocator is responsible for allocating indices in a large static buffer
-pub trait BufferAllocator<const SIZE: usize> {
-    // get the next free index
-    fn insert(&mut self) -> usize;
+pub trait BufferAllocator {
+    // get the next free index, or None once every index up to the capacity is used
+    fn insert(&mut self) -> Option<usize>;
 
     // return an index to the allocator
     fn remove(&mut self, index: usize);
+
+    // move every used index below the number of used indices, so the used part of the buffer
+    // is contiguous. Returns the moves as (from, to), the buffer's contents need moving to match
+    fn compact(&mut self) -> Vec<(usize, usize)>;
+
+    // the highest index given out plus one, the buffer needs to be at least this long
+    fn head(&self) -> usize;
 }
 
 // uses a simple stack based method for tracking free indices
-pub struct StackBufferAllocator<const SIZE: usize> {
+pub struct StackBufferAllocator {
+    capacity: usize,
+    // The highest index given out, plus one
     buffer_head: usize,
-    // The highest index given out
+    // Free indices below the head, sorted from highest to lowest so the lowest is reused first
     returned_indices: Vec<usize>,
 }
 
-impl<const SIZE: usize> StackBufferAllocator<SIZE> {
-    pub fn new() -> Self {
+impl StackBufferAllocator {
+    pub fn new(capacity: usize) -> Self {
         StackBufferAllocator {
+            capacity,
             buffer_head: 0,
             returned_indices: vec![],
         }
     }
 
-    fn compact(&mut self) {
-        self.reduce_head();
-
-        // We need to identify the contiguous occupied ranges in the stack
-        // let mut contiguous_ranges = vec![];
-        // for i in self.returned_indices.split_off() {
-        //
-        // }
+    // fragmentation is the fraction of the buffer below the head that isn't in use
+    pub fn fragmentation(&self) -> f32 {
+        self.returned_indices.len() as f32 / self.buffer_head.max(1) as f32
     }
 
     fn reduce_head(&mut self) {
         // If possible, reduce the size of the stack by reducing the head
-
-        // TODO: this doesn't have to look at the whole stack
-        let mut partition_point = None;
-        for i in (0..self.returned_indices.len()).rev() {
-            if self.returned_indices[i] == (self.buffer_head - 1) {
-                partition_point = Some(i);
-                self.buffer_head -= 1;
-            }
+        // The highest free indices are at the front, so only the run touching the head is looked at
+        let mut reduced = 0;
+        while reduced < self.returned_indices.len()
+            && self.returned_indices[reduced] == self.buffer_head - 1
+        {
+            self.buffer_head -= 1;
+            reduced += 1;
         }
 
-        if let Some(i) = partition_point {
-            // The head has already been reduced, we just need to cut the old indices off the edge
-            assert_eq!(self.buffer_head, self.returned_indices[i]);
-            self.returned_indices.drain(0..(i + 1));
-        }
+        self.returned_indices.drain(0..reduced);
     }
 }
 
-impl<const SIZE: usize> BufferAllocator<SIZE> for StackBufferAllocator<SIZE> {
-    fn insert(&mut self) -> usize {
-        // dbg!(self.buffer_head, &self.returned_indices);
-
+impl BufferAllocator for StackBufferAllocator {
+    fn insert(&mut self) -> Option<usize> {
         match self.returned_indices.pop() {
-            Some(i) => i,
-            None => {
+            Some(i) => Some(i),
+            None if self.buffer_head < self.capacity => {
                 let i = self.buffer_head;
                 self.buffer_head += 1;
 
-                assert!(self.buffer_head < SIZE);
-
-                i
+                Some(i)
             }
+            None => None,
         }
     }
 
     fn remove(&mut self, index: usize) {
+        assert!(index < self.buffer_head);
+
         // Use partition point to make sure returned_indices stays sorted
         let idx = self.returned_indices.partition_point(|&x| x > index);
+        debug_assert_ne!(self.returned_indices.get(idx), Some(&index));
 
-        if idx == (self.buffer_head - 1) {
+        if index == self.buffer_head - 1 {
             self.buffer_head -= 1;
+            self.reduce_head();
         } else {
             self.returned_indices.insert(idx, index);
         }
+    }
+
+    fn compact(&mut self) -> Vec<(usize, usize)> {
+        let used = self.buffer_head - self.returned_indices.len();
+
+        // Holes below `used` get filled by the used indices above it, lowest hole from the highest
+        // used index. There are exactly as many of each
+        let holes = self
+            .returned_indices
+            .iter()
+            .rev()
+            .take_while(|&&i| i < used);
+        let mut free_above = self
+            .returned_indices
+            .iter()
+            .filter(|&&i| i >= used)
+            .peekable();
+        let mut moves = vec![];
+        let mut from = self.buffer_head;
+        for &to in holes {
+            loop {
+                from -= 1;
+                if free_above.peek() == Some(&&from) {
+                    free_above.next();
+                } else {
+                    break;
+                }
+            }
+            moves.push((from, to));
+        }
+
+        self.buffer_head = used;
+        self.returned_indices.clear();
+
+        moves
+    }
+
+    fn head(&self) -> usize {
+        self.buffer_head
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use crate::surfaces::sampling::buffer_allocator::{BufferAllocator, StackBufferAllocator};
+
+    const SIZE: usize = 64;
+
+    // Random inserts, removes and compactions are checked against a set of the used indices
+    fn check_against_model(seed: u64) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut allocator = StackBufferAllocator::new(SIZE);
+        let mut used = BTreeSet::new();
+
+        for _ in 0..2000 {
+            match rng.gen_range(0..20) {
+                0 => {
+                    let moves = allocator.compact();
+
+                    for (from, _) in &moves {
+                        assert!(used.remove(from), "moved unused index {from}");
+                    }
+                    for (_, to) in &moves {
+                        assert!(used.insert(*to), "moved onto used index {to}");
+                    }
+                    assert_eq!(used, (0..used.len()).collect());
+                    assert_eq!(allocator.fragmentation(), 0.0);
+                }
+                1..=9 if !used.is_empty() => {
+                    let index = *used.iter().nth(rng.gen_range(0..used.len())).unwrap();
+                    used.remove(&index);
+                    allocator.remove(index);
+                }
+                _ => match allocator.insert() {
+                    Some(index) => {
+                        assert!(index < SIZE);
+                        assert!(used.insert(index), "gave out used index {index}");
+                    }
+                    None => assert_eq!(used.len(), SIZE),
+                },
+            }
+
+            // Nothing is left above the highest used index
+            let head = used.last().map_or(0, |i| i + 1);
+            assert_eq!(allocator.head(), head);
+            assert_eq!(allocator.returned_indices.len(), head - used.len());
+        }
+    }
+
+    #[test]
+    fn matches_model() {
+        for seed in 0..50 {
+            check_against_model(seed);
+        }
+    }
+
+    #[test]
+    fn compact_fills_holes_from_the_top() {
+        let mut allocator = StackBufferAllocator::new(SIZE);
+        for _ in 0..6 {
+            allocator.insert();
+        }
+        allocator.remove(1);
+        allocator.remove(3);
+        allocator.remove(4);
+
+        // 0, 2 and 5 are used
+        assert_eq!(allocator.compact(), vec![(5, 1)]);
+        assert_eq!(allocator.insert(), Some(3));
+    }
+
+    #[test]
+    fn reuses_lowest_index() {
+        let mut allocator = StackBufferAllocator::new(SIZE);
+        for _ in 0..4 {
+            allocator.insert();
+        }
+        allocator.remove(2);
+        allocator.remove(0);
+
+        assert_eq!(allocator.insert(), Some(0));
+        assert_eq!(allocator.insert(), Some(2));
+        assert_eq!(allocator.insert(), Some(4));
+    }
+
+    #[test]
+    fn stops_at_capacity() {
+        let mut allocator = StackBufferAllocator::new(2);
+
+        assert_eq!(allocator.insert(), Some(0));
+        assert_eq!(allocator.insert(), Some(1));
+        assert_eq!(allocator.insert(), None);
 
-        self.compact();
+        allocator.remove(0);
+        assert_eq!(allocator.insert(), Some(0));
     }
 }
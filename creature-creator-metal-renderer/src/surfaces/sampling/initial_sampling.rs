@@ -2,10 +2,10 @@ use std::f64::consts::PI;
 
 use nalgebra::{point, Point3};
 
+use creature_creator_renderer::surface::{gradient, on_surface, seed, Surface};
+
 use crate::geometry::Plane;
 use crate::surfaces::sampling::spatial_indexer::kd_indexer::KdContainer;
-use crate::surfaces::sampling::surface::{gradient, on_surface, seed};
-use crate::surfaces::Surface;
 
 // Use a technique similar to Delauany triangles to get a fast initial sampling of the entire surface
 // Citation:
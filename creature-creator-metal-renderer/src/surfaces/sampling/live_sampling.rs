@@ -1,15 +1,24 @@
+use std::collections::HashMap;
 use std::mem;
 use std::ops::Neg;
+use std::time::Instant;
 
-use nalgebra::{Point3, vector, Vector3};
+use nalgebra::{Matrix4, Point3, vector, Vector3};
+
+use creature_creator_renderer::export::points::Point;
+use creature_creator_renderer::mesh::Mesh;
+use creature_creator_renderer::reconstruction::reconstruct;
+use creature_creator_renderer::shapes::Material;
+use creature_creator_renderer::surface::{curvature, normal, Surface};
 
-use crate::shared::new_zeroed_box;
 use crate::surfaces::sampling::buffer_allocator::{BufferAllocator, StackBufferAllocator};
 use crate::surfaces::sampling::initial_sampling::sample;
+use crate::surfaces::sampling::normals::{average_normal, check, NormalCheck, NormalReport};
 use crate::surfaces::sampling::spatial_indexer::{Positioned, SpatialIndexer};
 use crate::surfaces::sampling::spatial_indexer::kd_indexer::KdIndexer;
-use crate::surfaces::sampling::surface::gradient;
-use crate::surfaces::Surface;
+use crate::surfaces::sampling::stats::{
+    mean_variance, PhaseTimings, RadiusDistribution, SamplingStats,
+};
 
 // Using Particles to Sample and Control Implicit Surfaces.
 // Andrew P. Witkin, Paul S. Heckbert
@@ -25,7 +34,52 @@ const FISSION_COEFFICIENT: f32 = 0.2;
 const DEATH_COEFFICIENT: f32 = 0.7;
 const MAX_RADIUS_COEFFICIENT: f32 = 1.2;
 const DESIRED_REPULSION_ENERGY: f32 = REPULSION_AMPLITUDE * 0.8;
-pub const MAX_PARTICLE_COUNT: usize = 100000;
+// Desired radii are picked so every particle covers about this angle of the surface, in radians
+const RADIUS_CURVATURE_ANGLE: f32 = 0.35;
+const DEFAULT_MAX_PARTICLE_COUNT: usize = 100000;
+// Particle storage grows this many particles at a time
+const PARTICLE_CHUNK: usize = 4096;
+// Once the particle cap is hit, radii are enlarged by this much every update until there's room
+const RADIUS_SCALE_STEP: f32 = 1.1;
+// Enlarged radii shrink back once fewer than this fraction of the particle cap are used
+const RADIUS_SCALE_RELAX: f32 = 0.8;
+// Particles are moved to the front of their buffers once this much of the used part is empty
+const COMPACT_FRAGMENTATION: f32 = 0.25;
+// Curvature is measured again once a particle has moved this far, in multiples of its radius
+const CURVATURE_REFRESH_DISTANCE: f32 = 0.5;
+// Otherwise it's measured every this many updates, to catch blends changing under the particle
+const CURVATURE_REFRESH_UPDATES: usize = 8;
+
+// RadiusBounds limits the desired radius of particles, flat areas get `max` and tight curves `min`
+#[derive(Copy, Clone, Debug)]
+pub struct RadiusBounds {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl RadiusBounds {
+    pub fn new(min: f32, max: f32) -> Self {
+        assert!(
+            min > 0.0 && min <= max,
+            "radius bounds must be positive and ordered"
+        );
+
+        Self { min, max }
+    }
+
+    // fixed gives every particle the same desired radius, whatever the curvature
+    pub fn fixed(radius: f32) -> Self {
+        Self::new(radius, radius)
+    }
+
+    fn desired_radius(&self, curvature: f32) -> f32 {
+        if curvature <= 0.0 {
+            return self.max;
+        }
+
+        (RADIUS_CURVATURE_ANGLE / curvature).clamp(self.min, self.max)
+    }
+}
 
 fn random_velocity() -> Vector3<f32> {
     Vector3::new(rand::random(), rand::random(), rand::random()).normalize()
@@ -45,6 +99,11 @@ fn constrain_to_surface(
     normal: Vector3<f32>,
     velocity: Vector3<f32>,
 ) -> Vector3<f32> {
+    // Without a normal there's no telling which way the surface is, until one is repaired
+    if normal.magnitude_squared() == 0.0 {
+        return velocity;
+    }
+
     velocity
         - normal.scale(
             (normal.dot(&velocity) + (FEEDBACK * surface.sample(position))) / (normal.dot(&normal)),
@@ -67,12 +126,23 @@ fn should_fission_energy(radius: f32, energy: f32, desired_radius: f32) -> bool
     energy > fission_energy && radius > desired_radius
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Default)]
 struct Particle {
     position: Point3<f32>,
     velocity: Vector3<f32>,
     normal: Vector3<f32>,
+    // surface_normal is the surface's own normal at position, kept so checking normal is cheap
+    // It's None where the gradient vanishes, normal came from the neighbours there
+    surface_normal: Option<Vector3<f32>>,
     radius: f32,
+    // desired_radius follows the curvature under the particle, it's what fission and death aim for
+    desired_radius: f32,
+    // curvature was last measured under the particle when it was at curvature_position
+    curvature: f32,
+    curvature_position: Point3<f32>,
+    material: Material,
+    // shape is the index of the shape the particle sits on, it's carried along when the shape moves
+    shape: usize,
 }
 
 impl Positioned for Particle {
@@ -84,11 +154,21 @@ impl Positioned for Particle {
 pub struct SamplingSystem {
     living_particles: Vec<usize>,
     position_index: KdIndexer,
-    index_allocator: StackBufferAllocator<MAX_PARTICLE_COUNT>,
+    index_allocator: StackBufferAllocator,
+    max_particles: usize,
+
+    // Both are grown in chunks as the allocator hands out higher indices
+    particles_a: Vec<Particle>,
+    particles_b: Vec<Particle>,
+
+    // Desired radii are multiplied by this, it's enlarged when there are too many particles
+    radius_scale: f32,
 
-    // These are boxed so we don't blow out the stack
-    particles_a: Box<[Particle; MAX_PARTICLE_COUNT]>,
-    particles_b: Box<[Particle; MAX_PARTICLE_COUNT]>,
+    // Shape transforms from the last update, to find how far each shape has moved since
+    previous_shapes: Vec<Matrix4<f32>>,
+
+    // Counts calls to update, so periodic work can be spread over the particles
+    updates: usize,
 
     pub t: f32,
 }
@@ -98,59 +178,150 @@ impl SamplingSystem {
         SamplingSystem {
             living_particles: vec![],
             position_index: KdIndexer::new(),
-            index_allocator: StackBufferAllocator::new(),
+            index_allocator: StackBufferAllocator::new(DEFAULT_MAX_PARTICLE_COUNT),
+            max_particles: DEFAULT_MAX_PARTICLE_COUNT,
+
+            particles_a: vec![],
+            particles_b: vec![],
 
-            particles_a: new_zeroed_box(),
-            particles_b: new_zeroed_box(),
+            radius_scale: 1.0,
+
+            previous_shapes: vec![],
+
+            updates: 0,
 
             t: 0.0,
         }
     }
 
-    fn initial_sampling(&mut self, desired_radius: f32, surface: &Surface) {
+    // max_particles caps how many particles there can be, past it radii are enlarged instead
+    pub fn max_particles(mut self, max_particles: usize) -> Self {
+        assert!(self.living_particles.is_empty());
+
+        self.index_allocator = StackBufferAllocator::new(max_particles);
+        self.max_particles = max_particles;
+        self
+    }
+
+    // insert_particle allocates a particle, growing the buffers if needed
+    fn insert_particle(&mut self) -> Option<usize> {
+        let i = self.index_allocator.insert()?;
+
+        if i >= self.particles_a.len() {
+            let len = (i + 1)
+                .next_multiple_of(PARTICLE_CHUNK)
+                .min(self.max_particles);
+            self.particles_a.resize(len, Particle::default());
+            self.particles_b.resize(len, Particle::default());
+        }
+
+        Some(i)
+    }
+
+    fn initial_sampling(&mut self, radius_bounds: RadiusBounds, surface: &Surface) {
         // The initial sampling isn't taken until the particle system is stepped for the first time
         // This way we don't need to know what surface we're sampling when the particle system is allocated
-        println!("Initial sampling...");
-        let positions = sample(surface, desired_radius * 2.0);
-        if positions.len() > MAX_PARTICLE_COUNT {
-            panic!("TOO DANG BIG!!")
+        // Sampling starts sparse, fission fills in the curved areas
+        let mut initial_radius = radius_bounds.max * self.radius_scale;
+        let mut positions = sample(surface, initial_radius * 2.0);
+        while positions.len() > self.max_particles {
+            // Particles cover the surface, so there are fewer by the square of how much they grow
+            self.radius_scale *=
+                (positions.len() as f32 / self.max_particles as f32).sqrt() * RADIUS_SCALE_STEP;
+            initial_radius = radius_bounds.max * self.radius_scale;
+            positions = sample(surface, initial_radius * 2.0);
         }
 
         for p in positions {
-            let normal = gradient(surface, p).normalize();
-
-            let i = self.index_allocator.insert();
+            // Normals missing here are filled in from the neighbours once everything is sampled
+            let surface_normal = normal(surface, p);
+            let normal = surface_normal.unwrap_or_else(Vector3::zeros);
+            let curvature = curvature(surface, p);
+            let desired_radius = radius_bounds.desired_radius(curvature) * self.radius_scale;
+
+            let i = self
+                .insert_particle()
+                .expect("there's room for the initial sampling");
             self.living_particles.push(i);
 
             self.particles_a[i].position = p;
             self.particles_a[i].normal = normal;
-            self.particles_a[i].radius = desired_radius;
+            self.particles_a[i].surface_normal = surface_normal;
+            self.particles_a[i].radius = initial_radius;
+            self.particles_a[i].desired_radius = desired_radius;
+            self.particles_a[i].curvature = curvature;
+            self.particles_a[i].curvature_position = p;
             self.particles_b[i].position = p;
             self.particles_b[i].normal = normal;
-            self.particles_b[i].radius = desired_radius;
+            self.particles_b[i].surface_normal = surface_normal;
+            self.particles_b[i].radius = initial_radius;
+            self.particles_b[i].desired_radius = desired_radius;
+            self.particles_b[i].curvature = curvature;
+            self.particles_b[i].curvature_position = p;
         }
 
-        let mut position_index = KdIndexer::new();
-        position_index.reindex(self.particles_a.as_slice(), self.living_particles.clone());
-
-        println!("Done!")
+        self.position_index
+            .reindex(self.particles_a.as_slice(), self.living_particles.clone());
+        self.repair_normals();
     }
 
     pub fn positions(
         &self,
-    ) -> impl Iterator<Item = (Point3<f32>, Vector3<f32>, f32)> + ExactSizeIterator + '_ {
+    ) -> impl Iterator<Item = (Point3<f32>, Vector3<f32>, f32, Material)> + ExactSizeIterator + '_
+    {
         self.living_particles.iter().map(|i| {
             let particle = self.particles_a[*i];
-            (particle.position, particle.normal, particle.radius)
+            (
+                particle.position,
+                particle.normal,
+                particle.radius,
+                particle.material,
+            )
+        })
+    }
+
+    // points is a snapshot of every living particle, for exporting as a point cloud
+    pub fn points(&self) -> Vec<Point> {
+        self.positions()
+            .map(|(position, normal, radius, material)| Point {
+                position,
+                normal,
+                radius,
+                color: material.color,
+            })
+            .collect()
+    }
+
+    // mesh triangulates the particles, so it matches what's drawn without meshing the surface again
+    pub fn mesh(&self) -> Mesh {
+        let points = self.points();
+        let mut index = KdIndexer::new();
+        index.reindex(&points, (0..points.len()).collect());
+
+        reconstruct(&points, |origin, radius| {
+            index.get_indices_within(&points, origin, radius)
         })
     }
 
-    pub fn update(&mut self, desired_radius: f32, surface: &Surface) {
+    pub fn update(&mut self, radius_bounds: RadiusBounds, surface: &Surface) -> SamplingStats {
+        let mut timing = PhaseTimings::default();
+
+        let start = Instant::now();
         if self.t == 0.0 && self.living_particles.is_empty() {
-            self.initial_sampling(desired_radius, surface)
+            self.initial_sampling(radius_bounds, surface);
+            timing.initial_sampling = start.elapsed();
+        } else {
+            self.carry(surface);
+            timing.carry = start.elapsed();
         }
+        self.previous_shapes = surface.inverse_transforms();
+
+        let (mut born, mut died) = (0, 0);
+        let mut full = false;
+        let mut energies = vec![];
 
-        for _ in 0..UPDATE_ITERATIONS {
+        let start = Instant::now();
+        for iteration in 0..UPDATE_ITERATIONS {
             for j in (0..self.living_particles.len()).rev() {
                 let i = self.living_particles[j];
                 let particle = self.particles_a[i];
@@ -165,8 +336,6 @@ impl SamplingSystem {
                     .iter()
                     .filter(|j| **j != i)
                     .map(|j| {
-                        assert!(*j < MAX_PARTICLE_COUNT);
-
                         let pj = self.particles_a[*j];
 
                         (
@@ -177,17 +346,31 @@ impl SamplingSystem {
                     })
                     .collect();
                 let energy = self.repulsion_energy(&neighbours);
+                if iteration == UPDATE_ITERATIONS - 1 {
+                    energies.push(energy);
+                }
 
+                let desired_radius = particle.desired_radius;
                 if particle.velocity.magnitude() < (EQUILIBRIUM_SPEED * particle.radius) {
                     if should_die(particle.radius, desired_radius) {
                         self.living_particles.remove(j);
                         self.index_allocator.remove(i);
+                        died += 1;
                         continue;
                     }
 
-                    if should_fission_energy(particle.radius, energy, desired_radius)
-                        || should_fission_radius(particle.radius, desired_radius)
-                    {
+                    let fission = should_fission_energy(particle.radius, energy, desired_radius)
+                        || should_fission_radius(particle.radius, desired_radius);
+
+                    // Out of room, the particle carries on as it is until radii have been enlarged
+                    let sibling_i = if fission {
+                        self.insert_particle()
+                    } else {
+                        None
+                    };
+                    full |= fission && sibling_i.is_none();
+
+                    if let Some(sibling_i) = sibling_i {
                         let position = particle.position();
                         let radius = particle.radius;
 
@@ -195,23 +378,45 @@ impl SamplingSystem {
                         let new_velocity = random_velocity().scale(radius);
 
                         let new_position = Point3::from(position + new_velocity);
+                        let surface_normal = normal(surface, new_position);
                         self.particles_b[i] = Particle {
                             position: new_position,
                             velocity: vector![0.0, 0.0, 0.0],
-                            normal: gradient(surface, new_position).normalize(),
+                            normal: self.particle_normal(
+                                surface_normal,
+                                particle.normal,
+                                &neighbours,
+                            ),
+                            surface_normal,
                             radius: new_radius,
+                            desired_radius,
+                            curvature: particle.curvature,
+                            curvature_position: particle.curvature_position,
+                            material: particle.material,
+                            shape: particle.shape,
                         };
 
                         let sibling_position = Point3::from(position - new_velocity);
+                        let surface_normal = normal(surface, sibling_position);
                         let sibling = Particle {
                             position: sibling_position,
                             velocity: vector![0.0, 0.0, 0.0],
-                            normal: gradient(surface, sibling_position).normalize(),
+                            normal: self.particle_normal(
+                                surface_normal,
+                                particle.normal,
+                                &neighbours,
+                            ),
+                            surface_normal,
                             radius: new_radius,
+                            desired_radius,
+                            curvature: particle.curvature,
+                            curvature_position: particle.curvature_position,
+                            material: particle.material,
+                            shape: particle.shape,
                         };
-                        let sibling_i = self.index_allocator.insert();
                         self.particles_b[sibling_i] = sibling;
                         self.living_particles.push(sibling_i);
+                        born += 1;
                         continue;
                     }
                 }
@@ -225,7 +430,8 @@ impl SamplingSystem {
 
                 let position = particle.position + velocity.scale(ITERATION_T_STEP);
 
-                let normal = gradient(surface, position).normalize();
+                let surface_normal = normal(surface, position);
+                let normal = self.particle_normal(surface_normal, particle.normal, &neighbours);
 
                 let radius = self.particle_radius(position, particle.radius, energy, &neighbours);
 
@@ -233,7 +439,13 @@ impl SamplingSystem {
                     position,
                     velocity,
                     normal,
+                    surface_normal,
                     radius,
+                    desired_radius,
+                    curvature: particle.curvature,
+                    curvature_position: particle.curvature_position,
+                    material: particle.material,
+                    shape: particle.shape,
                 }
             }
 
@@ -243,7 +455,206 @@ impl SamplingSystem {
                 .reindex(self.particles_a.as_slice(), self.living_particles.clone());
         }
 
-        self.t += ITERATION_T_STEP
+        timing.relaxation = start.elapsed();
+
+        let living = self.living_particles.len();
+        if full {
+            self.radius_scale *= RADIUS_SCALE_STEP;
+        } else if (living as f32) < self.max_particles as f32 * RADIUS_SCALE_RELAX {
+            self.radius_scale = (self.radius_scale / RADIUS_SCALE_STEP).max(1.0);
+        }
+
+        // Materials only matter for drawing, so they're sampled once particles have settled
+        // Curvature changes slowly as particles move, so desired radii are only updated here too,
+        // along with the shape each particle is carried by
+        // Measuring curvature takes a lot of samples, so it's only done again for particles that
+        // have moved, or whose turn it is. Turns are staggered so they're spread over updates
+        let start = Instant::now();
+        let repaired_normals = self.repair_normals();
+        let mut surface_distance = 0.0;
+        let mut radii = Vec::with_capacity(self.living_particles.len());
+        for i in &self.living_particles {
+            let particle = &mut self.particles_a[*i];
+
+            let (value, material) = surface.sample_material(particle.position);
+            particle.material = material;
+            particle.shape = surface.nearest_shape(particle.position).unwrap_or(0);
+
+            let moved = (particle.position - particle.curvature_position).magnitude();
+            if moved > CURVATURE_REFRESH_DISTANCE * particle.radius
+                || (self.updates + *i).is_multiple_of(CURVATURE_REFRESH_UPDATES)
+            {
+                particle.curvature = curvature(surface, particle.position);
+                particle.curvature_position = particle.position;
+            }
+            particle.desired_radius =
+                radius_bounds.desired_radius(particle.curvature) * self.radius_scale;
+
+            surface_distance += value.abs();
+            radii.push(particle.radius);
+        }
+        timing.attributes = start.elapsed();
+
+        if self.index_allocator.fragmentation() > COMPACT_FRAGMENTATION {
+            self.compact();
+        }
+
+        self.updates += 1;
+        self.t += ITERATION_T_STEP;
+
+        let (mean_energy, energy_variance) = mean_variance(&energies);
+        SamplingStats {
+            living,
+            born,
+            died,
+            mean_energy,
+            energy_variance,
+            desired_energy: DESIRED_REPULSION_ENERGY,
+            mean_surface_distance: surface_distance / living.max(1) as f32,
+            radius: RadiusDistribution::from_radii(&radii),
+            radius_scale: self.radius_scale,
+            repaired_normals,
+            timing,
+        }
+    }
+
+    // compact moves the living particles to the front of the buffers, in index order,
+    // so iterating over them walks memory in order
+    fn compact(&mut self) {
+        let moves = self.index_allocator.compact();
+
+        let mut remap = HashMap::with_capacity(moves.len());
+        for (from, to) in moves {
+            self.particles_a[to] = self.particles_a[from];
+            remap.insert(from, to);
+        }
+
+        for i in &mut self.living_particles {
+            if let Some(to) = remap.get(i) {
+                *i = *to;
+            }
+        }
+        self.living_particles.sort_unstable();
+
+        self.position_index
+            .reindex(self.particles_a.as_slice(), self.living_particles.clone());
+    }
+
+    // carry moves every particle along with its shape, so relaxing only has to fix up the blends
+    // Without this fast moving shapes leave their particles behind
+    fn carry(&mut self, surface: &Surface) {
+        let Some(motion) = surface.motion(&self.previous_shapes) else {
+            return;
+        };
+
+        for i in &self.living_particles {
+            let particle = &mut self.particles_a[*i];
+            let motion = motion[particle.shape];
+
+            particle.position = motion.transform_point(&particle.position);
+            particle.curvature_position = motion.transform_point(&particle.curvature_position);
+            particle.velocity = motion.transform_vector(&particle.velocity);
+            particle.surface_normal = normal(surface, particle.position);
+            particle.normal = particle
+                .surface_normal
+                .unwrap_or_else(|| motion.transform_vector(&particle.normal));
+        }
+
+        self.position_index
+            .reindex(self.particles_a.as_slice(), self.living_particles.clone());
+    }
+
+    // particle_normal is the surface normal at a particle, falling back to the average of its
+    // neighbours' normals where the gradient vanishes, and then to the normal it had before
+    fn particle_normal(
+        &self,
+        surface_normal: Option<Vector3<f32>>,
+        previous: Vector3<f32>,
+        neighbours: &[(usize, f32, f32)],
+    ) -> Vector3<f32> {
+        surface_normal
+            .or_else(|| {
+                average_normal(neighbours.iter().map(|(j, ..)| self.particles_a[*j].normal))
+            })
+            .unwrap_or(previous)
+    }
+
+    // neighbour_normal is the average normal of the particles around particle i
+    fn neighbour_normal(&self, i: usize) -> Option<Vector3<f32>> {
+        let particle = self.particles_a[i];
+
+        let neighbours = self.position_index.get_indices_within(
+            self.particles_a.as_slice(),
+            particle.position,
+            NEIGHBOUR_RADIUS * particle.radius,
+        );
+
+        average_normal(
+            neighbours
+                .into_iter()
+                .filter(|j| *j != i)
+                .map(|j| self.particles_a[j].normal),
+        )
+    }
+
+    // check_normal compares particle i's normal with the surface normal, or its neighbours where
+    // the surface doesn't have a normal. Whichever it was compared with is returned for repairs
+    fn check_normal(
+        &self,
+        i: usize,
+        surface_normal: Option<Vector3<f32>>,
+    ) -> (NormalCheck, Option<Vector3<f32>>) {
+        let particle = self.particles_a[i];
+
+        let neighbour_normal = match surface_normal {
+            Some(_) => None,
+            None => self.neighbour_normal(i),
+        };
+
+        (
+            check(particle.normal, surface_normal, neighbour_normal),
+            surface_normal.or(neighbour_normal),
+        )
+    }
+
+    // validate_normals reports particles with normals that are broken or face the wrong way
+    pub fn validate_normals(&self, surface: &Surface) -> NormalReport {
+        let mut report = NormalReport::default();
+        for i in &self.living_particles {
+            let surface_normal = normal(surface, self.particles_a[*i].position);
+            report.count(self.check_normal(*i, surface_normal).0);
+        }
+
+        report
+    }
+
+    // repair_normals replaces broken normals and flips ones that face the wrong way,
+    // returning how many were changed
+    // The surface normals were found when the particles last moved, so they aren't found again
+    fn repair_normals(&mut self) -> usize {
+        let mut repairs = vec![];
+        for i in &self.living_particles {
+            let Particle {
+                normal,
+                surface_normal,
+                ..
+            } = self.particles_a[*i];
+
+            let repaired = match self.check_normal(*i, surface_normal) {
+                (NormalCheck::Valid, _) => continue,
+                (_, Some(replacement)) => replacement,
+                // Nothing around to go by, it's left for the next update
+                (NormalCheck::Invalid, None) => continue,
+                (NormalCheck::Inward | NormalCheck::Inconsistent, None) => -normal,
+            };
+            repairs.push((*i, repaired));
+        }
+
+        for (i, normal) in &repairs {
+            self.particles_a[*i].normal = *normal;
+        }
+
+        repairs.len()
     }
 
     fn repulsion_energy(&self, neighbours: &[(usize, f32, f32)]) -> f32 {
@@ -302,3 +713,284 @@ impl SamplingSystem {
             .scale(radius.powf(2.0))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{Matrix4, point, Point3, Translation3, vector, Vector3};
+
+    use creature_creator_renderer::shapes::{Material, Shape};
+    use creature_creator_renderer::surface::{normal, Surface};
+
+    use crate::surfaces::sampling::live_sampling::{
+        RadiusBounds, SamplingSystem, COMPACT_FRAGMENTATION, CURVATURE_REFRESH_UPDATES,
+    };
+
+    // A small sphere blended onto the side of a large one
+    fn bump() -> Surface {
+        let mut surface = Surface::new();
+        surface.push(
+            Matrix4::identity(),
+            Shape::Sphere(3.0),
+            Material::default(),
+            &[],
+        );
+        // Surfaces take the inverse transform
+        surface.push(
+            Translation3::new(-3.0, 0.0, 0.0).to_homogeneous(),
+            Shape::Sphere(0.75),
+            Material::default(),
+            &[],
+        );
+
+        surface
+    }
+
+    fn settle(surface: &Surface, radius_bounds: RadiusBounds) -> SamplingSystem {
+        let mut system = SamplingSystem::new();
+        for _ in 0..30 {
+            system.update(radius_bounds, surface);
+        }
+
+        system
+    }
+
+    // The patch of surface within `distance` of a point on a sphere has the same area
+    // whatever the sphere's radius, so counts around different points can be compared directly
+    fn count_near(system: &SamplingSystem, at: Point3<f32>, distance: f32) -> usize {
+        system
+            .positions()
+            .filter(|(p, ..)| (p - at).magnitude() <= distance)
+            .count()
+    }
+
+    #[test]
+    fn curved_areas_are_denser() {
+        let surface = bump();
+        let system = settle(&surface, RadiusBounds::new(0.1, 0.6));
+
+        let small = count_near(&system, point![3.75, 0.0, 0.0], 1.2);
+        let large = count_near(&system, point![-3.0, 0.0, 0.0], 1.2);
+
+        assert!(
+            small > large * 2,
+            "{small} near the small sphere, {large} near the large one"
+        );
+    }
+
+    #[test]
+    fn radii_stay_near_bounds() {
+        let surface = bump();
+        let system = settle(&surface, RadiusBounds::new(0.1, 0.6));
+
+        for (_, _, radius, _) in system.positions() {
+            assert!(radius > 0.1 * 0.5 && radius < 0.6 * 1.5, "radius {radius}");
+        }
+    }
+
+    #[test]
+    fn curvature_is_measured_in_turns() {
+        let surface = bump();
+        let mut system = settle(&surface, RadiusBounds::fixed(0.3));
+
+        let stale = |system: &SamplingSystem| {
+            system
+                .living_particles
+                .iter()
+                .filter(|i| system.particles_a[**i].curvature < 0.0)
+                .count()
+        };
+
+        for i in &system.living_particles {
+            system.particles_a[*i].curvature = -1.0;
+        }
+
+        // Settled particles barely move, so most wait for their turn
+        system.update(RadiusBounds::fixed(0.3), &surface);
+        assert!(stale(&system) > system.living_particles.len() / 2);
+
+        for _ in 0..CURVATURE_REFRESH_UPDATES * 2 {
+            system.update(RadiusBounds::fixed(0.3), &surface);
+        }
+        assert_eq!(stale(&system), 0);
+    }
+
+    #[test]
+    fn particles_follow_moving_shapes() {
+        // The sphere moves further every frame than relaxing alone could keep up with
+        let sphere_at = |x: f32| {
+            let mut surface = Surface::new();
+            surface.push(
+                Translation3::new(-x, 0.0, 0.0).to_homogeneous(),
+                Shape::Sphere(2.0),
+                Material::default(),
+                &[],
+            );
+            surface
+        };
+
+        let mut system = settle(&sphere_at(0.0), RadiusBounds::fixed(0.3));
+
+        for frame in 1..=10 {
+            let surface = sphere_at(frame as f32 * 0.5);
+            let stats = system.update(RadiusBounds::fixed(0.3), &surface);
+
+            for (position, ..) in system.positions() {
+                assert!(surface.sample(position).abs() < 0.05);
+            }
+            assert!(stats.churn() < 0.1, "churn {}", stats.churn());
+        }
+    }
+
+    #[test]
+    fn fixed_radius_is_uniform() {
+        let surface = bump();
+        let system = settle(&surface, RadiusBounds::fixed(0.3));
+
+        let small = count_near(&system, point![3.75, 0.0, 0.0], 1.2);
+        let large = count_near(&system, point![-3.0, 0.0, 0.0], 1.2);
+
+        assert!(
+            small < large * 2,
+            "{small} near the small sphere, {large} near the large one"
+        );
+    }
+
+    #[test]
+    fn stats_count_particles() {
+        let surface = bump();
+        let mut system = SamplingSystem::new();
+
+        let mut living = 0;
+        for _ in 0..10 {
+            let stats = system.update(RadiusBounds::new(0.1, 0.6), &surface);
+
+            assert_eq!(stats.living, system.positions().len());
+            // The first update's births include the initial sampling
+            if living > 0 {
+                assert_eq!(stats.living, living + stats.born - stats.died);
+            }
+            living = stats.living;
+        }
+    }
+
+    #[test]
+    fn fixed_radius_converges() {
+        let surface = bump();
+        let mut system = settle(&surface, RadiusBounds::fixed(0.3));
+
+        let stats = system.update(RadiusBounds::fixed(0.3), &surface);
+        assert!(stats.converged(), "{stats:?}");
+        assert!(stats.radius.max <= 0.3 * 1.2, "{stats:?}");
+    }
+
+    #[test]
+    fn particles_stay_compact() {
+        let surface = bump();
+        let mut system = settle(&surface, RadiusBounds::new(0.1, 0.6));
+        let stats = system.update(RadiusBounds::new(0.1, 0.6), &surface);
+
+        assert!(system.index_allocator.fragmentation() <= COMPACT_FRAGMENTATION);
+        // Nothing is left far above the living particles
+        let highest = system.living_particles.iter().max().unwrap();
+        assert!((*highest as f32) < stats.living as f32 / (1.0 - COMPACT_FRAGMENTATION));
+        assert_eq!(system.positions().len(), stats.living);
+    }
+
+    #[test]
+    fn full_sampling_enlarges_radius() {
+        let surface = bump();
+        let mut system = SamplingSystem::new().max_particles(100);
+
+        let mut stats = system.update(RadiusBounds::fixed(0.05), &surface);
+        for _ in 0..20 {
+            stats = system.update(RadiusBounds::fixed(0.05), &surface);
+            assert!(stats.living <= 100);
+        }
+
+        assert!(stats.radius_scale > 1.0, "{stats:?}");
+        assert!(stats.radius.mean > 0.05, "{stats:?}");
+    }
+
+    // Two spheres blended into a peanut, with a concave neck around the middle
+    fn peanut() -> Surface {
+        let mut surface = Surface::new();
+        for x in [-1.0, 1.0] {
+            surface.push(
+                Translation3::new(x, 0.0, 0.0).to_homogeneous(),
+                Shape::Sphere(1.2),
+                Material::default(),
+                &[],
+            );
+        }
+
+        surface
+    }
+
+    #[test]
+    fn concave_blend_normals_are_valid() {
+        let surface = peanut();
+        let system = settle(&surface, RadiusBounds::fixed(0.2));
+
+        assert_eq!(
+            system.validate_normals(&surface).checked,
+            system.positions().len()
+        );
+        assert!(system.validate_normals(&surface).ok());
+        for (position, normal, ..) in system.positions() {
+            assert!(position.iter().all(|v| v.is_finite()));
+            // The neck's normals face away from the axis, not into the other sphere
+            if position.x.abs() < 0.1 {
+                assert!(normal.x.abs() < 0.5, "{normal} at {position}");
+            }
+        }
+    }
+
+    #[test]
+    fn broken_normals_are_repaired() {
+        let surface = peanut();
+        let mut system = settle(&surface, RadiusBounds::fixed(0.2));
+
+        let (broken, flipped) = (system.living_particles[0], system.living_particles[1]);
+        system.particles_a[broken].normal = vector![f32::NAN, 0.0, 0.0];
+        // Facing inwards and off to the side, so flipping it wouldn't be enough
+        let n = system.particles_a[flipped].normal;
+        system.particles_a[flipped].normal = -(n + n.cross(&Vector3::x()).normalize()).normalize();
+
+        let report = system.validate_normals(&surface);
+        assert_eq!((report.invalid, report.inward), (1, 1));
+
+        assert_eq!(system.repair_normals(), 2);
+        assert!(system.validate_normals(&surface).ok());
+        // They're repaired to the surface normal, within a couple of degrees
+        for i in [broken, flipped] {
+            let particle = system.particles_a[i];
+            let surface_normal = normal(&surface, particle.position).unwrap();
+            let cos = particle.normal.dot(&surface_normal);
+            assert!(cos > 2.0_f32.to_radians().cos(), "{}", cos.acos().to_degrees());
+        }
+    }
+
+    #[test]
+    fn vanishing_gradient_uses_neighbour_normals() {
+        let surface = peanut();
+        let system = settle(&surface, RadiusBounds::fixed(0.2));
+
+        // The middle of the peanut has no gradient, the particles on top of the neck say which way is out
+        let center = point![0.0, 0.0, 0.0];
+        assert_eq!(normal(&surface, center), None);
+
+        let neighbours: Vec<(usize, f32, f32)> = system
+            .living_particles
+            .iter()
+            .filter(|i| {
+                let p = system.particles_a[**i].position;
+                p.x.abs() < 0.5 && p.y > 0.5
+            })
+            .map(|i| (*i, 0.0, 0.0))
+            .collect();
+        assert!(!neighbours.is_empty());
+
+        let n = system.particle_normal(normal(&surface, center), Vector3::zeros(), &neighbours);
+        assert!(n.y > 0.9, "{n}");
+    }
+}
@@ -1,10 +1,10 @@
-pub(super) use live_sampling::MAX_PARTICLE_COUNT;
-pub use live_sampling::SamplingSystem;
-pub use surface::Surface;
+pub use live_sampling::{RadiusBounds, SamplingSystem};
+pub use normals::NormalReport;
+pub use stats::SamplingStats;
 
 mod buffer_allocator;
 mod initial_sampling;
 mod live_sampling;
-mod primitives;
+mod normals;
 mod spatial_indexer;
-mod surface;
+mod stats;
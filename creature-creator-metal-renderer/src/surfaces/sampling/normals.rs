@@ -0,0 +1,71 @@
+use nalgebra::Vector3;
+
+// How far from unit length a normal can be before it's reported
+const UNIT_TOLERANCE: f32 = 0.001;
+// Sums of neighbour normals shorter than this cancel out, so don't say which way is out
+const MIN_NEIGHBOUR_AGREEMENT: f32 = 0.001;
+
+// NormalReport counts particles with normals that can't be trusted
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct NormalReport {
+    pub checked: usize,
+    // NaN, infinite or not unit length
+    pub invalid: usize,
+    // Facing into the surface, where the surface has a normal to compare against
+    pub inward: usize,
+    // Facing away from the particle's neighbours, where the surface doesn't have a normal
+    pub inconsistent: usize,
+}
+
+impl NormalReport {
+    pub fn ok(&self) -> bool {
+        self.invalid == 0 && self.inward == 0 && self.inconsistent == 0
+    }
+
+    pub(super) fn count(&mut self, check: NormalCheck) {
+        self.checked += 1;
+        match check {
+            NormalCheck::Valid => (),
+            NormalCheck::Invalid => self.invalid += 1,
+            NormalCheck::Inward => self.inward += 1,
+            NormalCheck::Inconsistent => self.inconsistent += 1,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(super) enum NormalCheck {
+    Valid,
+    Invalid,
+    Inward,
+    Inconsistent,
+}
+
+pub(super) fn is_unit(normal: Vector3<f32>) -> bool {
+    normal.iter().all(|v| v.is_finite()) && (normal.magnitude() - 1.0).abs() <= UNIT_TOLERANCE
+}
+
+// average_normal is the direction most of the normals agree on, if they agree at all
+pub(super) fn average_normal(normals: impl Iterator<Item = Vector3<f32>>) -> Option<Vector3<f32>> {
+    normals
+        .filter(|n| is_unit(*n))
+        .sum::<Vector3<f32>>()
+        .try_normalize(MIN_NEIGHBOUR_AGREEMENT)
+}
+
+// check compares a particle's normal with the surface normal, or its neighbours' when there isn't one
+pub(super) fn check(
+    normal: Vector3<f32>,
+    surface_normal: Option<Vector3<f32>>,
+    neighbour_normal: Option<Vector3<f32>>,
+) -> NormalCheck {
+    if !is_unit(normal) {
+        return NormalCheck::Invalid;
+    }
+
+    match (surface_normal, neighbour_normal) {
+        (Some(outward), _) if normal.dot(&outward) < 0.0 => NormalCheck::Inward,
+        (None, Some(around)) if normal.dot(&around) < 0.0 => NormalCheck::Inconsistent,
+        _ => NormalCheck::Valid,
+    }
+}
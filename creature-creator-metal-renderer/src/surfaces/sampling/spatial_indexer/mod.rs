@@ -1,5 +1,7 @@
 use nalgebra::Point3;
 
+use creature_creator_renderer::export::points::Point;
+
 pub mod kd_indexer;
 
 pub trait Positioned {
@@ -12,6 +14,12 @@ impl Positioned for Point3<f32> {
     }
 }
 
+impl Positioned for Point {
+    fn position(&self) -> Point3<f32> {
+        self.position
+    }
+}
+
 // SpatialIndexer is used to accelerate nearest neighbour searches. It doesn't own any data, just indices
 pub trait SpatialIndexer<P: Positioned> {
     // reindex will rebuild the internal index with all items
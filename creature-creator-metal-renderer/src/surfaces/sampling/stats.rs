@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+// A settled sampling barely changes between updates, these are how little counts as barely
+const CONVERGED_CHURN: f32 = 0.01;
+// In multiples of the desired repulsion energy
+const CONVERGED_ENERGY_ERROR: f32 = 0.25;
+// In multiples of the mean particle radius
+const CONVERGED_SURFACE_DISTANCE: f32 = 0.05;
+
+// SamplingStats describes what the last call to SamplingSystem::update did
+#[derive(Clone, Debug, Default)]
+pub struct SamplingStats {
+    pub living: usize,
+    pub born: usize,
+    pub died: usize,
+
+    // Repulsion energy of every particle in the last iteration, it settles around desired_energy
+    pub mean_energy: f32,
+    pub energy_variance: f32,
+    pub desired_energy: f32,
+
+    // Mean |surface value| of the particles, how far they've drifted off the surface
+    pub mean_surface_distance: f32,
+
+    pub radius: RadiusDistribution,
+    // Above one when the particle cap has been hit, and desired radii have been enlarged to fit
+    pub radius_scale: f32,
+    // Normals that were missing or facing into the surface, and had to be fixed
+    pub repaired_normals: usize,
+    pub timing: PhaseTimings,
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RadiusDistribution {
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    pub std_dev: f32,
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PhaseTimings {
+    // Only one of these is non-zero, particles are either sampled for the first time or carried
+    pub initial_sampling: Duration,
+    pub carry: Duration,
+    pub relaxation: Duration,
+    // Materials, carrying shapes and desired radii are refreshed once particles have settled
+    pub attributes: Duration,
+}
+
+impl SamplingStats {
+    // churn is the fraction of particles that were born or died in the update
+    pub fn churn(&self) -> f32 {
+        let starting = self.living + self.died - self.born;
+        (self.born + self.died) as f32 / starting.max(1) as f32
+    }
+
+    // converged is true once further updates won't visibly change the sampling,
+    // so callers can stop updating until the surface changes
+    pub fn converged(&self) -> bool {
+        self.living > 0
+            && self.churn() <= CONVERGED_CHURN
+            && (self.mean_energy - self.desired_energy).abs()
+                <= CONVERGED_ENERGY_ERROR * self.desired_energy
+            && self.mean_surface_distance <= CONVERGED_SURFACE_DISTANCE * self.radius.mean
+    }
+}
+
+impl PhaseTimings {
+    pub fn total(&self) -> Duration {
+        self.initial_sampling + self.carry + self.relaxation + self.attributes
+    }
+}
+
+// mean_variance of an empty slice is zero
+pub(super) fn mean_variance(values: &[f32]) -> (f32, f32) {
+    if values.is_empty() {
+        return (0.0, 0.0);
+    }
+
+    let n = values.len() as f32;
+    let mean = values.iter().sum::<f32>() / n;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / n;
+
+    (mean, variance)
+}
+
+impl RadiusDistribution {
+    pub(super) fn from_radii(radii: &[f32]) -> Self {
+        let (mean, variance) = mean_variance(radii);
+
+        RadiusDistribution {
+            min: radii.iter().copied().reduce(f32::min).unwrap_or(0.0),
+            max: radii.iter().copied().reduce(f32::max).unwrap_or(0.0),
+            mean,
+            std_dev: variance.sqrt(),
+        }
+    }
+}
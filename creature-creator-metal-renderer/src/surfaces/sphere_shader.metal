@@ -15,6 +15,8 @@ struct VertexIn {
     float3 center   [[attribute(1)]];
     float radius    [[attribute(2)]];
     float3 normal    [[attribute(3)]];
+    float3 color    [[attribute(4)]];
+    float roughness [[attribute(5)]];
 };
 
 struct VertexOut {
@@ -37,10 +39,32 @@ float3 lambert_contribution(float3 object_normal,
     return light_color * light_intensity * object_k * dot(object_normal, normalize(object_origin - light_origin));
 }
 
+float3 specular_contribution(float3 object_normal,
+                             float3 object_origin,
+                             float object_roughness,
+                             float3 camera_origin,
+                             float3 light_origin,
+                             float3 light_color,
+                             float light_intensity)
+{
+    // Lights use the same direction convention as lambert_contribution
+    float3 to_light = normalize(object_origin - light_origin);
+    float3 to_camera = normalize(camera_origin - object_origin);
+    float3 halfway = normalize(to_light + to_camera);
+
+    float shininess = mix(128.0, 2.0, object_roughness);
+    float highlight = pow(max(dot(object_normal, halfway), 0.0), shininess);
+
+    return light_color * light_intensity * (1.0 - object_roughness) * highlight;
+}
+
 float3 light_sample(float3 sample_point,
-                    float3 sample_normal)
+                    float3 sample_normal,
+                    float3 sample_color,
+                    float sample_roughness,
+                    float3 camera_position)
 {
-    return (0.3 * backgroundColor) +
+    float3 diffuse = (0.3 * backgroundColor) +
     lambert_contribution(
                          sample_normal,
                          sample_point,
@@ -65,6 +89,37 @@ float3 light_sample(float3 sample_point,
                          blueColor,
                          1.00
                          );
+
+    float3 specular =
+    specular_contribution(
+                          sample_normal,
+                          sample_point,
+                          sample_roughness,
+                          camera_position,
+                          float3(-50.0, -50.0, -50.0),
+                          pinkColor,
+                          0.50
+                          ) +
+    specular_contribution(
+                          sample_normal,
+                          sample_point,
+                          sample_roughness,
+                          camera_position,
+                          float3(0.0, -50.0, -50.0),
+                          purpleColor,
+                          0.75
+                          ) +
+    specular_contribution(
+                          sample_normal,
+                          sample_point,
+                          sample_roughness,
+                          camera_position,
+                          float3(-50.0, -50.0, 0.0),
+                          blueColor,
+                          1.00
+                          );
+
+    return (sample_color * diffuse) + specular;
 }
 
 vertex VertexOut
@@ -77,7 +132,7 @@ vertex_main(VertexIn in [[stage_in]],
     
     VertexOut out;
     out.position = uniform.camera * float4(sphere_center + radius * in.position, 1.0);
-    out.color = float4(light_sample(sphere_center, in.normal), 1.0);
+    out.color = float4(light_sample(sphere_center, in.normal, in.color, in.roughness, uniform.camera_position), 1.0);
     return
     out;
 }
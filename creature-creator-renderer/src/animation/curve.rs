@@ -0,0 +1,252 @@
+use std::f32::consts::PI;
+
+use nalgebra::{UnitQuaternion, Vector3};
+
+use crate::NodeId;
+
+// Newton's method gets a Bézier's time to well within this in a few steps
+const BEZIER_EPSILON: f32 = 1e-6;
+const BEZIER_ITERATIONS: usize = 8;
+
+// Property is the part of a node's transform a channel animates
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Property {
+    Position,
+    // Degrees, like NodeTransform's rotation. It's interpolated the short way round
+    Rotation,
+    Scale,
+}
+
+// Interpolation is how a keyframe's value changes into the next keyframe's
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Interpolation {
+    // Holds the value until the next keyframe
+    Step,
+    Linear,
+    // A timing curve from (0, 0) to (1, 1) with these two control points, like CSS's
+    // cubic-bezier. The X of both points is kept between 0 and 1 so time only goes forwards
+    Bezier(f32, f32, f32, f32),
+}
+
+impl Interpolation {
+    pub const EASE_IN_OUT: Interpolation = Interpolation::Bezier(0.42, 0.0, 0.58, 1.0);
+
+    // progress is how far between two keyframes the value is, `t` being how far in time
+    pub fn progress(&self, t: f32) -> f32 {
+        match *self {
+            Interpolation::Step => 0.0,
+            Interpolation::Linear => t,
+            Interpolation::Bezier(x1, y1, x2, y2) => {
+                let s = bezier_parameter(x1.clamp(0.0, 1.0), x2.clamp(0.0, 1.0), t);
+                bezier(y1, y2, s)
+            }
+        }
+    }
+}
+
+// bezier is one coordinate of a cubic Bézier from 0 to 1, with control points `a` and `b`
+fn bezier(a: f32, b: f32, s: f32) -> f32 {
+    let r = 1.0 - s;
+    3.0 * r * r * s * a + 3.0 * r * s * s * b + s * s * s
+}
+
+// bezier_parameter finds where along the curve the X coordinate is `x`
+fn bezier_parameter(x1: f32, x2: f32, x: f32) -> f32 {
+    let mut s = x;
+    for _ in 0..BEZIER_ITERATIONS {
+        let error = bezier(x1, x2, s) - x;
+        if error.abs() < BEZIER_EPSILON {
+            return s;
+        }
+
+        let r = 1.0 - s;
+        let slope = 3.0 * r * r * x1 + 6.0 * r * s * (x2 - x1) + 3.0 * s * s * (1.0 - x2);
+        if slope.abs() < BEZIER_EPSILON {
+            break;
+        }
+        s = (s - error / slope).clamp(0.0, 1.0);
+    }
+
+    // Flat spots can stall Newton's method, X always increases so bisecting still works
+    let (mut low, mut high) = (0.0, 1.0);
+    while high - low > BEZIER_EPSILON {
+        s = (low + high) / 2.0;
+        if bezier(x1, x2, s) < x {
+            low = s
+        } else {
+            high = s
+        }
+    }
+
+    s
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Keyframe {
+    pub time: f32,
+    pub value: Vector3<f32>,
+    // How the value goes from this keyframe to the next one
+    pub interpolation: Interpolation,
+}
+
+// Channel animates one property of one node
+pub struct Channel {
+    pub node: NodeId,
+    pub property: Property,
+    keys: Vec<Keyframe>,
+}
+
+impl Channel {
+    pub fn new(node: NodeId, property: Property) -> Self {
+        Self {
+            node,
+            property,
+            keys: vec![],
+        }
+    }
+
+    // key adds a keyframe after the others
+    pub fn key(mut self, time: f32, value: Vector3<f32>, interpolation: Interpolation) -> Self {
+        assert!(
+            self.keys.last().is_none_or(|k| k.time < time),
+            "keyframes should be added in time order"
+        );
+
+        self.keys.push(Keyframe {
+            time,
+            value,
+            interpolation,
+        });
+        self
+    }
+
+    pub fn keys(&self) -> &[Keyframe] {
+        &self.keys
+    }
+
+    // sample is the value at `time`, holding the first and last values outside the keyframes
+    pub fn sample(&self, time: f32) -> Option<Vector3<f32>> {
+        let next = self.keys.partition_point(|k| k.time <= time);
+        if next == 0 {
+            return self.keys.first().map(|k| k.value);
+        }
+        let from = &self.keys[next - 1];
+        let Some(to) = self.keys.get(next) else {
+            return Some(from.value);
+        };
+
+        let t = (time - from.time) / (to.time - from.time);
+        let progress = from.interpolation.progress(t);
+
+        Some(match self.property {
+            Property::Rotation => {
+                from_quaternion(to_quaternion(from.value).slerp(&to_quaternion(to.value), progress))
+            }
+            Property::Position | Property::Scale => from.value.lerp(&to.value, progress),
+        })
+    }
+}
+
+pub(crate) fn to_quaternion(rotation: Vector3<f32>) -> UnitQuaternion<f32> {
+    UnitQuaternion::from_scaled_axis(rotation * (PI / 180.0))
+}
+
+pub(crate) fn from_quaternion(rotation: UnitQuaternion<f32>) -> Vector3<f32> {
+    rotation.scaled_axis() * (180.0 / PI)
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{vector, Vector3};
+
+    use crate::animation::curve::to_quaternion;
+    use crate::animation::{Channel, Interpolation, Property};
+    use crate::RenderGraph;
+
+    fn channel(property: Property, interpolation: Interpolation) -> Channel {
+        let node = RenderGraph::new().root().node_id();
+
+        Channel::new(node, property)
+            .key(1.0, vector![0.0, 0.0, 0.0], interpolation)
+            .key(3.0, vector![10.0, 20.0, 0.0], interpolation)
+            .key(4.0, vector![0.0, 0.0, 0.0], interpolation)
+    }
+
+    fn assert_near(a: Vector3<f32>, b: Vector3<f32>) {
+        assert!((a - b).magnitude() < 1e-4, "{a} != {b}");
+    }
+
+    #[test]
+    fn step_holds_until_the_next_key() {
+        let channel = channel(Property::Position, Interpolation::Step);
+
+        assert_eq!(channel.sample(2.9), Some(vector![0.0, 0.0, 0.0]));
+        assert_eq!(channel.sample(3.0), Some(vector![10.0, 20.0, 0.0]));
+        assert_eq!(channel.sample(3.5), Some(vector![10.0, 20.0, 0.0]));
+    }
+
+    #[test]
+    fn linear_is_proportional_to_time() {
+        let channel = channel(Property::Position, Interpolation::Linear);
+
+        assert_near(channel.sample(1.5).unwrap(), vector![2.5, 5.0, 0.0]);
+        assert_near(channel.sample(2.0).unwrap(), vector![5.0, 10.0, 0.0]);
+        assert_near(channel.sample(3.25).unwrap(), vector![7.5, 15.0, 0.0]);
+    }
+
+    #[test]
+    fn values_are_held_outside_the_keys() {
+        let channel = channel(Property::Scale, Interpolation::Linear);
+
+        assert_eq!(channel.sample(-5.0), Some(vector![0.0, 0.0, 0.0]));
+        assert_eq!(channel.sample(10.0), Some(vector![0.0, 0.0, 0.0]));
+        assert_eq!(
+            Channel::new(RenderGraph::new().root().node_id(), Property::Scale).sample(1.0),
+            None
+        );
+    }
+
+    #[test]
+    fn bezier_timing_curves() {
+        // Control points on the diagonal are linear
+        let straight = Interpolation::Bezier(0.25, 0.25, 0.75, 0.75);
+        for t in [0.0, 0.1, 0.5, 0.9, 1.0] {
+            assert!((straight.progress(t) - t).abs() < 1e-4);
+        }
+
+        // Easing in and out is symmetric, slow at the ends and fast in the middle
+        let ease = Interpolation::EASE_IN_OUT;
+        assert!(ease.progress(0.0).abs() < 1e-4);
+        assert!((ease.progress(0.5) - 0.5).abs() < 1e-4);
+        assert!((ease.progress(1.0) - 1.0).abs() < 1e-4);
+        assert!(ease.progress(0.1) < 0.1);
+        assert!(ease.progress(0.9) > 0.9);
+        assert!((ease.progress(0.2) + ease.progress(0.8) - 1.0).abs() < 1e-4);
+        // CSS's ease-in-out is at about 0.0198 a tenth of the way through
+        assert!((ease.progress(0.1) - 0.0198).abs() < 1e-3);
+
+        // Overshooting in value is allowed
+        let back = Interpolation::Bezier(0.3, 1.5, 0.7, 1.5);
+        assert!(back.progress(0.7) > 1.0);
+
+        let channel = channel(Property::Position, ease);
+        assert_near(channel.sample(2.0).unwrap(), vector![5.0, 10.0, 0.0]);
+    }
+
+    #[test]
+    fn rotations_turn_at_a_steady_rate() {
+        let node = RenderGraph::new().root().node_id();
+        let channel = Channel::new(node, Property::Rotation)
+            .key(0.0, vector![0.0, 0.0, 0.0], Interpolation::Linear)
+            .key(1.0, vector![0.0, 0.0, 90.0], Interpolation::Linear)
+            .key(2.0, vector![90.0, 0.0, 0.0], Interpolation::Linear);
+
+        assert_near(channel.sample(0.5).unwrap(), vector![0.0, 0.0, 45.0]);
+        // Between axes it's half the turn from one to the other, the same angle from each
+        let halfway = to_quaternion(channel.sample(1.5).unwrap());
+        let ends = [vector![0.0, 0.0, 90.0], vector![90.0, 0.0, 0.0]].map(to_quaternion);
+        for end in ends {
+            assert!((halfway.angle_to(&end) - ends[0].angle_to(&ends[1]) / 2.0).abs() < 1e-4);
+        }
+    }
+}
@@ -0,0 +1,165 @@
+pub(crate) use curve::{from_quaternion, to_quaternion};
+pub use curve::{Channel, Interpolation, Keyframe, Property};
+pub use pose::{Mask, Pose};
+pub use state::{StateId, StateMachine, Transition};
+
+mod curve;
+mod pose;
+mod state;
+
+// LoopMode is what a clip does after its last frame
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LoopMode {
+    // Holds the last frame
+    Once,
+    // Starts again from the beginning
+    Repeat,
+    // Plays backwards to the beginning, then forwards again
+    PingPong,
+}
+
+// Clip is an animation of some nodes, sampled into a pose at any time
+pub struct Clip {
+    pub name: String,
+    duration: f32,
+    looping: LoopMode,
+    channels: Vec<Channel>,
+}
+
+impl Clip {
+    pub fn new(name: impl Into<String>, duration: f32) -> Self {
+        Self {
+            name: name.into(),
+            duration: duration.max(0.0),
+            looping: LoopMode::Once,
+            channels: vec![],
+        }
+    }
+
+    pub fn looping(mut self, looping: LoopMode) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    pub fn channel(mut self, channel: Channel) -> Self {
+        self.channels.push(channel);
+        self
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    pub fn channels(&self) -> &[Channel] {
+        &self.channels
+    }
+
+    // local_time is where in the clip `time` lands after looping
+    pub fn local_time(&self, time: f32) -> f32 {
+        if self.duration <= 0.0 {
+            return 0.0;
+        }
+
+        match self.looping {
+            LoopMode::Once => time.clamp(0.0, self.duration),
+            LoopMode::Repeat => time.rem_euclid(self.duration),
+            LoopMode::PingPong => {
+                let t = time.rem_euclid(self.duration * 2.0);
+                self.duration - (t - self.duration).abs()
+            }
+        }
+    }
+
+    // sample writes the animated properties at `time` into the pose, everything else in the pose
+    // is left alone so it can start from a rest pose
+    pub fn sample(&self, time: f32, pose: &mut Pose) {
+        let time = self.local_time(time);
+
+        for channel in &self.channels {
+            let Some(value) = channel.sample(time) else {
+                continue;
+            };
+
+            let transform = pose.get_mut(channel.node);
+            match channel.property {
+                Property::Position => transform.position = value.into(),
+                Property::Rotation => transform.rotation = value,
+                Property::Scale => transform.scale = value,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{point, vector};
+
+    use crate::animation::{Channel, Clip, Interpolation, LoopMode, Pose, Property};
+    use crate::skeleton::Skeleton;
+    use crate::{NodeId, RenderGraph};
+
+    fn clip(node: NodeId, looping: LoopMode) -> Clip {
+        Clip::new("nod", 2.0).looping(looping).channel(
+            Channel::new(node, Property::Rotation)
+                .key(0.0, vector![0.0, 0.0, 0.0], Interpolation::Linear)
+                .key(2.0, vector![0.0, 0.0, 40.0], Interpolation::Linear),
+        )
+    }
+
+    #[test]
+    fn loop_modes() {
+        let node = RenderGraph::new().root().node_id();
+        let local = |looping, time| clip(node, looping).local_time(time);
+
+        assert_eq!(local(LoopMode::Once, 1.5), 1.5);
+        assert_eq!(local(LoopMode::Once, 7.0), 2.0);
+        assert_eq!(local(LoopMode::Once, -1.0), 0.0);
+
+        assert_eq!(local(LoopMode::Repeat, 2.5), 0.5);
+        assert_eq!(local(LoopMode::Repeat, 6.0), 0.0);
+        assert_eq!(local(LoopMode::Repeat, -0.5), 1.5);
+
+        assert_eq!(local(LoopMode::PingPong, 1.5), 1.5);
+        assert_eq!(local(LoopMode::PingPong, 2.5), 1.5);
+        assert_eq!(local(LoopMode::PingPong, 4.5), 0.5);
+    }
+
+    #[test]
+    fn sampling_only_changes_animated_properties() {
+        let mut graph = RenderGraph::new();
+        let mut root = graph.root_mut();
+        let mut node = root.push_empty();
+        node.with_transform(|t| t.position = point![1.0, 2.0, 3.0]);
+        let node_id = node.node_id();
+
+        let mut pose = Pose::from_graph(&graph, [node_id]);
+        clip(node_id, LoopMode::Repeat).sample(3.0, &mut pose);
+        pose.apply(&mut graph);
+
+        let transform = *graph.node(node_id).transform();
+        assert_eq!(transform.position, point![1.0, 2.0, 3.0]);
+        assert!((transform.rotation - vector![0.0, 0.0, 20.0]).magnitude() < 1e-4);
+    }
+
+    #[test]
+    fn clips_pose_skeletons() {
+        let mut skeleton = Skeleton::from_rig(
+            "arm      -    10  0  3 0 0  0 0 0
+             forearm  arm  10  0  0 0 0  0 0 0",
+        )
+        .unwrap();
+        let mut graph = RenderGraph::new();
+        let root_id = graph.root().node_id();
+        skeleton.build(&mut graph, root_id);
+        let [arm, forearm] = [0, 1].map(|b| skeleton.bone(b).joint_id().unwrap());
+
+        let mut pose = skeleton.rest_pose();
+        clip(forearm, LoopMode::Once).sample(1.0, &mut pose);
+        pose.apply(&mut graph);
+
+        assert_eq!(graph.node(arm).transform().position, point![3.0, 0.0, 0.0]);
+        assert!(
+            (graph.node(forearm).transform().rotation - vector![0.0, 0.0, 20.0]).magnitude() < 1e-4
+        );
+    }
+}
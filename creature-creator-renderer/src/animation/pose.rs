@@ -0,0 +1,221 @@
+use std::collections::{HashMap, HashSet};
+
+use nalgebra::{UnitQuaternion, Vector3};
+
+use crate::animation::curve::{from_quaternion, to_quaternion};
+use crate::{NodeId, NodeTransform, RenderGraph};
+
+// Mask is the nodes a blend is limited to, like a bone and everything below it
+#[derive(Clone, Default)]
+pub struct Mask {
+    nodes: HashSet<NodeId>,
+}
+
+impl Mask {
+    pub fn new(nodes: impl IntoIterator<Item = NodeId>) -> Self {
+        Self {
+            nodes: nodes.into_iter().collect(),
+        }
+    }
+
+    pub fn contains(&self, node: NodeId) -> bool {
+        self.nodes.contains(&node)
+    }
+}
+
+// Pose is the transforms of a set of nodes, sampled from clips and applied to the graph
+#[derive(Clone, Default)]
+pub struct Pose {
+    transforms: HashMap<NodeId, NodeTransform>,
+}
+
+impl Pose {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // from_graph is the nodes as they are in the graph right now
+    pub fn from_graph(graph: &RenderGraph, nodes: impl IntoIterator<Item = NodeId>) -> Self {
+        let mut pose = Self::new();
+        for node in nodes {
+            pose.set(node, *graph.node(node).transform());
+        }
+
+        pose
+    }
+
+    pub fn get(&self, node: NodeId) -> Option<&NodeTransform> {
+        self.transforms.get(&node)
+    }
+
+    // get_mut gives the node's transform, starting from identity if it isn't posed yet
+    pub fn get_mut(&mut self, node: NodeId) -> &mut NodeTransform {
+        self.transforms
+            .entry(node)
+            .or_insert_with(NodeTransform::identity)
+    }
+
+    pub fn set(&mut self, node: NodeId, transform: NodeTransform) {
+        self.transforms.insert(node, transform);
+    }
+
+    pub fn len(&self) -> usize {
+        self.transforms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &NodeTransform)> {
+        self.transforms.iter().map(|(node, t)| (*node, t))
+    }
+
+    // apply sets the transform of every posed node, others are left as they are
+    pub fn apply(&self, graph: &mut RenderGraph) {
+        for (node, transform) in &self.transforms {
+            *graph.node_mut(*node).transform() = *transform;
+        }
+    }
+
+    // blend moves the pose towards `other` by `weight`, 0 keeps this pose and 1 is all `other`
+    // Nodes only in `other` are taken as they are, and a mask leaves the nodes outside it alone
+    pub fn blend(&mut self, other: &Pose, weight: f32, mask: Option<&Mask>) {
+        for (node, target) in other.iter() {
+            if mask.is_some_and(|m| !m.contains(node)) {
+                continue;
+            }
+
+            match self.transforms.get_mut(&node) {
+                Some(transform) => *transform = interpolate(transform, target, weight),
+                None => self.set(node, *target),
+            }
+        }
+    }
+
+    // add layers the difference between `additive` and `reference` on top of this pose, so an
+    // animation made from the reference can be played over any other. Nodes missing from the
+    // reference have nothing to be different from and are skipped
+    pub fn add(&mut self, additive: &Pose, reference: &Pose, weight: f32, mask: Option<&Mask>) {
+        for (node, target) in additive.iter() {
+            if mask.is_some_and(|m| !m.contains(node)) {
+                continue;
+            }
+            let Some(base) = reference.get(node) else {
+                continue;
+            };
+
+            let turn = to_quaternion(target.rotation) * to_quaternion(base.rotation).inverse();
+            let stretch = target.scale.component_div(&base.scale);
+
+            let transform = self.get_mut(node);
+            transform.position += (target.position - base.position) * weight;
+            transform.rotation = from_quaternion(
+                UnitQuaternion::identity().slerp(&turn, weight) * to_quaternion(transform.rotation),
+            );
+            transform.scale = transform
+                .scale
+                .component_mul(&Vector3::repeat(1.0).lerp(&stretch, weight));
+        }
+    }
+}
+
+// interpolate is part way from one transform to another, turning the short way round
+fn interpolate(from: &NodeTransform, to: &NodeTransform, weight: f32) -> NodeTransform {
+    NodeTransform {
+        position: from.position.lerp(&to.position, weight),
+        rotation: from_quaternion(
+            to_quaternion(from.rotation).slerp(&to_quaternion(to.rotation), weight),
+        ),
+        scale: from.scale.lerp(&to.scale, weight),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{point, vector, Vector3};
+
+    use crate::animation::{Mask, Pose};
+    use crate::{NodeId, NodeTransform, RenderGraph};
+
+    fn nodes() -> [NodeId; 2] {
+        let mut graph = RenderGraph::new();
+        let mut root = graph.root_mut();
+        [root.push_empty().node_id(), root.push_empty().node_id()]
+    }
+
+    fn transform(x: f32, degrees: f32, scale: f32) -> NodeTransform {
+        NodeTransform {
+            position: point![x, 0.0, 0.0],
+            rotation: vector![0.0, 0.0, degrees],
+            scale: Vector3::repeat(scale),
+        }
+    }
+
+    fn assert_near(a: &NodeTransform, b: &NodeTransform) {
+        assert!((a.position - b.position).magnitude() < 1e-4);
+        assert!((a.rotation - b.rotation).magnitude() < 1e-3);
+        assert!((a.scale - b.scale).magnitude() < 1e-4);
+    }
+
+    #[test]
+    fn blending_is_weighted() {
+        let [a, b] = nodes();
+        let mut pose = Pose::new();
+        pose.set(a, transform(0.0, 0.0, 1.0));
+        let mut other = Pose::new();
+        other.set(a, transform(4.0, 80.0, 3.0));
+        other.set(b, transform(1.0, 0.0, 1.0));
+
+        let mut quarter = pose.clone();
+        quarter.blend(&other, 0.25, None);
+        assert_near(quarter.get(a).unwrap(), &transform(1.0, 20.0, 1.5));
+        // Nothing to blend with, so it's taken as it is
+        assert_near(quarter.get(b).unwrap(), &transform(1.0, 0.0, 1.0));
+
+        let mut all = pose.clone();
+        all.blend(&other, 1.0, None);
+        assert_near(all.get(a).unwrap(), other.get(a).unwrap());
+    }
+
+    #[test]
+    fn masks_keep_blends_to_part_of_the_pose() {
+        let [a, b] = nodes();
+        let mut pose = Pose::new();
+        pose.set(a, transform(0.0, 0.0, 1.0));
+        pose.set(b, transform(0.0, 0.0, 1.0));
+        let mut other = Pose::new();
+        other.set(a, transform(2.0, 0.0, 1.0));
+        other.set(b, transform(2.0, 0.0, 1.0));
+
+        pose.blend(&other, 1.0, Some(&Mask::new([b])));
+        assert_near(pose.get(a).unwrap(), &transform(0.0, 0.0, 1.0));
+        assert_near(pose.get(b).unwrap(), &transform(2.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn additive_layers_add_the_difference() {
+        let [a, _] = nodes();
+        let mut reference = Pose::new();
+        reference.set(a, transform(1.0, 10.0, 2.0));
+        let mut nod = Pose::new();
+        nod.set(a, transform(2.0, 40.0, 4.0));
+
+        // Played over a pose that's nothing like the reference, only the change carries over
+        let mut pose = Pose::new();
+        pose.set(a, transform(5.0, 45.0, 1.0));
+        pose.add(&nod, &reference, 1.0, None);
+        assert_near(pose.get(a).unwrap(), &transform(6.0, 75.0, 2.0));
+
+        let mut pose = Pose::new();
+        pose.set(a, transform(5.0, 45.0, 1.0));
+        pose.add(&nod, &reference, 0.5, None);
+        assert_near(pose.get(a).unwrap(), &transform(5.5, 60.0, 1.5));
+
+        // A layer that's the same as its reference changes nothing
+        let mut pose = Pose::new();
+        pose.set(a, transform(5.0, 45.0, 1.0));
+        pose.add(&reference, &reference, 1.0, None);
+        assert_near(pose.get(a).unwrap(), &transform(5.0, 45.0, 1.0));
+    }
+}
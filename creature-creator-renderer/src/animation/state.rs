@@ -0,0 +1,302 @@
+use crate::animation::{Clip, Pose};
+
+// StateId is a state's index in its state machine, in the order they were added
+pub type StateId = usize;
+
+// Transition is a way from one state to another, crossfading over `duration` seconds
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transition {
+    // None means it can be taken from any state
+    pub from: Option<StateId>,
+    pub to: StateId,
+    pub duration: f32,
+    // Taken by itself once `from` has played to the end of its clip, instead of when asked for
+    pub on_end: bool,
+}
+
+impl Transition {
+    pub fn new(from: StateId, to: StateId, duration: f32) -> Self {
+        Self {
+            from: Some(from),
+            to,
+            duration,
+            on_end: false,
+        }
+    }
+
+    pub fn from_any(to: StateId, duration: f32) -> Self {
+        Self {
+            from: None,
+            to,
+            duration,
+            on_end: false,
+        }
+    }
+
+    pub fn on_end(mut self) -> Self {
+        self.on_end = true;
+        self
+    }
+}
+
+// Playing is a state and how long it has been playing for
+#[derive(Copy, Clone)]
+struct Playing {
+    state: StateId,
+    time: f32,
+}
+
+// Fade is the state being faded out of and how far through the fade it is. It fades into the
+// state the next fade is from, or the current state for the newest fade
+#[derive(Copy, Clone)]
+struct Fade {
+    from: Playing,
+    elapsed: f32,
+    duration: f32,
+}
+
+// StateMachine plays one clip at a time, crossfading when it moves between them
+pub struct StateMachine {
+    states: Vec<Clip>,
+    transitions: Vec<Transition>,
+    current: Playing,
+    // Oldest first. Starting a transition part way through another fades out of the blend of
+    // the two, rather than jumping to the state it was fading into
+    fades: Vec<Fade>,
+}
+
+impl StateMachine {
+    // new starts out playing `initial`, as state 0
+    pub fn new(initial: Clip) -> Self {
+        Self {
+            states: vec![initial],
+            transitions: vec![],
+            current: Playing {
+                state: 0,
+                time: 0.0,
+            },
+            fades: vec![],
+        }
+    }
+
+    pub fn add_state(&mut self, clip: Clip) -> StateId {
+        self.states.push(clip);
+        self.states.len() - 1
+    }
+
+    pub fn add_transition(&mut self, transition: Transition) {
+        assert!(
+            transition.from.is_none_or(|s| s < self.states.len())
+                && transition.to < self.states.len(),
+            "transitions should be between existing states"
+        );
+        self.transitions.push(transition);
+    }
+
+    pub fn find(&self, name: &str) -> Option<StateId> {
+        self.states.iter().position(|c| c.name == name)
+    }
+
+    pub fn current(&self) -> StateId {
+        self.current.state
+    }
+
+    // is_fading is true while the last state is still being faded out
+    pub fn is_fading(&self) -> bool {
+        !self.fades.is_empty()
+    }
+
+    // go_to starts the transition from the current state to `to`, if there is one
+    pub fn go_to(&mut self, to: StateId) -> bool {
+        let transition = self.transitions.iter().find(|t| {
+            !t.on_end && t.to == to && t.from.is_none_or(|from| from == self.current.state)
+        });
+
+        match transition.copied() {
+            Some(transition) if to != self.current.state => {
+                self.start(transition);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn start(&mut self, transition: Transition) {
+        if transition.duration > 0.0 {
+            self.fades.push(Fade {
+                from: self.current,
+                elapsed: 0.0,
+                duration: transition.duration,
+            });
+        } else {
+            self.fades.clear();
+        }
+        self.current = Playing {
+            state: transition.to,
+            time: 0.0,
+        };
+    }
+
+    // update moves the clips and any fade on by `seconds`
+    pub fn update(&mut self, seconds: f32) {
+        let previous_time = self.current.time;
+        self.current.time += seconds;
+
+        for fade in &mut self.fades {
+            fade.from.time += seconds;
+            fade.elapsed += seconds;
+        }
+        // A finished fade is all the state it faded into, so it and everything before it can go
+        if let Some(finished) = self.fades.iter().rposition(|f| f.elapsed >= f.duration) {
+            self.fades.drain(..=finished);
+        }
+
+        // The end of the clip only counts the first time it's reached, even if it loops
+        let end = self.states[self.current.state].duration();
+        if previous_time < end && self.current.time >= end {
+            let transition = self
+                .transitions
+                .iter()
+                .find(|t| t.on_end && t.from == Some(self.current.state))
+                .copied();
+            if let Some(transition) = transition {
+                self.start(transition)
+            }
+        }
+    }
+
+    // sample writes the current state into the pose, blended with the ones it's fading from
+    pub fn sample(&self, pose: &mut Pose) {
+        let Some(first) = self.fades.first() else {
+            self.sample_state(self.current, pose);
+            return;
+        };
+
+        let base = pose.clone();
+        self.sample_state(first.from, pose);
+        for (i, fade) in self.fades.iter().enumerate() {
+            let to = self.fades.get(i + 1).map_or(self.current, |next| next.from);
+
+            let mut to_pose = base.clone();
+            self.sample_state(to, &mut to_pose);
+            pose.blend(&to_pose, fade.elapsed / fade.duration, None);
+        }
+    }
+
+    fn sample_state(&self, playing: Playing, pose: &mut Pose) {
+        self.states[playing.state].sample(playing.time, pose)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::vector;
+
+    use crate::animation::{
+        Channel, Clip, Interpolation, LoopMode, Pose, Property, StateMachine, Transition,
+    };
+    use crate::{NodeId, RenderGraph};
+
+    // held is a clip that keeps the node's X at `x`
+    fn held(name: &str, node: NodeId, x: f32, duration: f32) -> Clip {
+        Clip::new(name, duration).looping(LoopMode::Repeat).channel(
+            Channel::new(node, Property::Position).key(
+                0.0,
+                vector![x, 0.0, 0.0],
+                Interpolation::Step,
+            ),
+        )
+    }
+
+    fn machine() -> (StateMachine, NodeId) {
+        let node = RenderGraph::new().root().node_id();
+        let mut machine = StateMachine::new(held("idle", node, 0.0, 1.0));
+        let walk = machine.add_state(held("walk", node, 10.0, 1.0));
+        let attack = machine.add_state(held("attack", node, 20.0, 0.5));
+        machine.add_transition(Transition::new(0, walk, 1.0));
+        machine.add_transition(Transition::from_any(attack, 0.0));
+        machine.add_transition(Transition::new(attack, 0, 0.5).on_end());
+
+        (machine, node)
+    }
+
+    fn x(machine: &StateMachine, node: NodeId) -> f32 {
+        let mut pose = Pose::new();
+        machine.sample(&mut pose);
+        pose.get(node).unwrap().position.x
+    }
+
+    #[test]
+    fn only_defined_transitions_are_taken() {
+        let (mut machine, _) = machine();
+        let walk = machine.find("walk").unwrap();
+
+        assert!(machine.go_to(walk));
+        assert_eq!(machine.current(), walk);
+        // There's no way back to idle from walking, and walk is already playing
+        assert!(!machine.go_to(0));
+        assert!(!machine.go_to(walk));
+        // Attacking can happen from anywhere
+        assert!(machine.go_to(machine.find("attack").unwrap()));
+    }
+
+    #[test]
+    fn transitions_crossfade() {
+        let (mut machine, node) = machine();
+
+        machine.go_to(1);
+        assert_eq!(x(&machine, node), 0.0);
+        machine.update(0.25);
+        assert!((x(&machine, node) - 2.5).abs() < 1e-4);
+        machine.update(0.5);
+        assert!((x(&machine, node) - 7.5).abs() < 1e-4);
+        assert!(machine.is_fading());
+
+        machine.update(0.25);
+        assert!(!machine.is_fading());
+        assert_eq!(x(&machine, node), 10.0);
+    }
+
+    #[test]
+    fn clips_can_move_on_when_they_end() {
+        let (mut machine, node) = machine();
+
+        // No fade into the attack, then half a second back to idle after it
+        machine.go_to(2);
+        assert_eq!(x(&machine, node), 20.0);
+        machine.update(0.4);
+        assert_eq!(machine.current(), 2);
+        machine.update(0.1);
+        assert_eq!(machine.current(), 0);
+        machine.update(0.25);
+        assert!((x(&machine, node) - 10.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn interrupted_fades_carry_on_from_where_they_were() {
+        let node = RenderGraph::new().root().node_id();
+        let mut machine = StateMachine::new(held("idle", node, 0.0, 1.0));
+        let walk = machine.add_state(held("walk", node, 10.0, 1.0));
+        machine.add_transition(Transition::new(0, walk, 1.0));
+        machine.add_transition(Transition::new(walk, 0, 1.0));
+
+        machine.go_to(walk);
+        machine.update(0.5);
+        let before = x(&machine, node);
+        assert!((before - 5.0).abs() < 1e-4);
+
+        // Turning back half way starts from the blend, not from all the way at walk
+        assert!(machine.go_to(0));
+        assert!((x(&machine, node) - before).abs() < 1e-4);
+
+        let mut last = before;
+        for _ in 0..20 {
+            machine.update(0.1);
+            let now = x(&machine, node);
+            assert!((now - last).abs() < 1.5, "{last} jumped to {now}");
+            last = now;
+        }
+        assert!(!machine.is_fading());
+        assert_eq!(last, 0.0);
+    }
+}
@@ -1,6 +1,9 @@
 use std::f32::consts::PI;
 
-use nalgebra::{Isometry3, Perspective3, Point3, Vector3};
+use nalgebra::{Isometry3, Matrix4, Perspective3, point, Point2, Point3, Vector3, Vector4};
+
+pub(crate) const NEAR: f32 = 0.01;
+const FAR: f32 = 10000.0;
 
 pub struct Camera {
     eye: Point3<f32>,
@@ -27,10 +30,123 @@ impl Camera {
         self.eye.coords.data.0[0]
     }
     pub fn mvp_matrix(&self) -> [[f32; 4]; 4] {
-        let view = Isometry3::look_at_rh(&self.eye, &self.target, &Vector3::y());
+        self.view_projection(self.aspect_ratio).data.0
+    }
+
+    // view_projection takes world space to clip space, for an image with the given aspect ratio
+    pub(crate) fn view_projection(&self, aspect_ratio: f32) -> Matrix4<f32> {
+        self.projection(aspect_ratio).as_matrix() * self.view().to_homogeneous()
+    }
+
+    // view takes world space to camera space, where the camera looks down -Z with Y up
+    pub(crate) fn view(&self) -> Isometry3<f32> {
+        Isometry3::look_at_rh(&self.eye, &self.target, &Vector3::y())
+    }
+
+    pub(crate) fn projection(&self, aspect_ratio: f32) -> Perspective3<f32> {
+        Perspective3::new(aspect_ratio, self.fov * (180.0 / PI), NEAR, FAR)
+    }
+
+    pub(crate) fn eye(&self) -> Point3<f32> {
+        self.eye
+    }
+}
+
+// Viewport is a camera looking at an image `size` pixels across, it works out where things end up
+// in the image and how big they are there
+pub struct Viewport {
+    view_projection: Matrix4<f32>,
+    eye: Point3<f32>,
+    // The directions of the image's X and Y axes in world space
+    right: Vector3<f32>,
+    up: Vector3<f32>,
+    // Pixels per world unit, one unit in front of the camera
+    focal_length: f32,
+    size: (u32, u32),
+}
+
+impl Viewport {
+    pub fn new(camera: &Camera, size: (u32, u32)) -> Self {
+        let (width, height) = (size.0 as f32, size.1.max(1) as f32);
+        let aspect_ratio = width / height;
+        let view = camera.view();
 
-        let proj = Perspective3::new(self.aspect_ratio, self.fov * (180.0 / PI), 0.01, 10000.0);
+        Viewport {
+            view_projection: camera.view_projection(aspect_ratio),
+            eye: camera.eye(),
+            right: view.inverse_transform_vector(&Vector3::x()),
+            up: view.inverse_transform_vector(&Vector3::y()),
+            focal_length: camera.projection(aspect_ratio).as_matrix()[(1, 1)] * height / 2.0,
+            size,
+        }
+    }
 
-        (proj.as_matrix() * view.to_homogeneous()).data.0
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    pub fn eye(&self) -> Point3<f32> {
+        self.eye
+    }
+
+    pub fn right(&self) -> Vector3<f32> {
+        self.right
+    }
+
+    pub fn up(&self) -> Vector3<f32> {
+        self.up
+    }
+
+    // clip's w is the distance in front of the camera, it's negative behind it
+    pub fn clip(&self, p: Point3<f32>) -> Vector4<f32> {
+        self.view_projection * p.to_homogeneous()
+    }
+
+    // screen is in pixels from the top left of the image
+    pub fn screen(&self, clip: Vector4<f32>) -> Point2<f32> {
+        point![
+            (clip.x / clip.w + 1.0) / 2.0 * self.size.0 as f32,
+            (1.0 - clip.y / clip.w) / 2.0 * self.size.1 as f32
+        ]
+    }
+
+    // ray is the direction from the eye through a pixel, the inverse of screen
+    pub fn ray(&self, pixel: Point2<f32>) -> Vector3<f32> {
+        let forward = self.up.cross(&self.right);
+        let (width, height) = (self.size.0 as f32, self.size.1 as f32);
+
+        (forward * self.focal_length
+            + self.right * (pixel.x - width / 2.0)
+            + self.up * (height / 2.0 - pixel.y))
+            .normalize()
+    }
+
+    // pixels_per_unit is how many pixels a world unit covers, `distance` in front of the camera
+    // Anything closer than the near plane is as big as it would be on it
+    pub fn pixels_per_unit(&self, distance: f32) -> f32 {
+        self.focal_length / distance.max(NEAR)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::point;
+
+    use crate::{Camera, Viewport};
+
+    #[test]
+    fn rays_go_through_their_pixel() {
+        let camera = Camera::new(point![3.0, 4.0, 10.0], point![0.0, 1.0, 0.0], 1.0);
+        let viewport = Viewport::new(&camera, (640, 480));
+
+        for pixel in [point![320.0, 240.0], point![0.0, 0.0], point![600.0, 100.0]] {
+            let ray = viewport.ray(pixel);
+            let along = viewport.eye() + ray * 7.0;
+            let screen = viewport.screen(viewport.clip(along));
+            assert!(
+                (screen - pixel).magnitude() < 1e-2,
+                "{pixel} went to {screen}"
+            );
+        }
     }
 }
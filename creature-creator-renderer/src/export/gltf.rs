@@ -0,0 +1,605 @@
+use std::f32::consts::PI;
+use std::io;
+use std::io::Write;
+
+use nalgebra::{Matrix4, UnitQuaternion, Vector3};
+
+use crate::export::Joint;
+use crate::graph::{Kind, NodeRef, RenderGraph};
+use crate::mesh::{extract, Mesh};
+use crate::surface::Surface;
+
+// Binary glTF 2.0
+// Every graph node becomes a glTF node, so the hierarchy survives the export.
+// Symmetry nodes get a child node per copy, holding that copy's rotation and uniform scale,
+// with the symmetry node's subtree duplicated underneath each one.
+// The surface is meshed once in world space and rigidly skinned to the joints
+
+const GLB_MAGIC: u32 = 0x4654_6C67;
+const GLB_VERSION: u32 = 2;
+const CHUNK_JSON: u32 = 0x4E4F_534A;
+const CHUNK_BIN: u32 = 0x004E_4942;
+
+const FLOAT: u32 = 5126;
+const UNSIGNED_SHORT: u32 = 5123;
+const UNSIGNED_INT: u32 = 5125;
+
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+struct Node {
+    name: Option<String>,
+    translation: Vector3<f32>,
+    rotation: UnitQuaternion<f32>,
+    scale: Vector3<f32>,
+    children: Vec<usize>,
+}
+
+struct Document<'a> {
+    joints: &'a [Joint],
+
+    nodes: Vec<Node>,
+    // glTF node and world transform of every skin joint, the root of the graph is always first
+    skin_joints: Vec<(usize, Matrix4<f32>)>,
+
+    surface: Surface,
+    // Skin joint that each shape in the surface is attached to
+    shape_joints: Vec<usize>,
+
+    buffer: Vec<u8>,
+    buffer_views: Vec<String>,
+    accessors: Vec<String>,
+}
+
+pub fn write<W: Write>(
+    graph: &RenderGraph,
+    joints: &[Joint],
+    cell_size: f32,
+    writer: &mut W,
+) -> io::Result<()> {
+    let mut document = Document {
+        joints,
+        nodes: vec![],
+        skin_joints: vec![],
+        surface: Surface::new(),
+        shape_joints: vec![],
+        buffer: vec![],
+        buffer_views: vec![],
+        accessors: vec![],
+    };
+
+    let root = document.node(graph.root(), Matrix4::identity(), "", 0);
+    let mesh = extract(&document.surface, cell_size);
+
+    let json = document.json(root, &mesh);
+    write_glb(json.as_bytes(), &document.buffer, writer)
+}
+
+impl<'a> Document<'a> {
+    // node adds `graph_node` and everything under it, returning its glTF node index
+    // `suffix` tells copies made by symmetry nodes apart, `joint` is the closest skin joint above
+    fn node(
+        &mut self,
+        graph_node: NodeRef,
+        parent_transform: Matrix4<f32>,
+        suffix: &str,
+        joint: usize,
+    ) -> usize {
+        let transform = graph_node.transform();
+        let world_transform = parent_transform * transform.to_homogeneous();
+
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            name: None,
+            translation: transform.position.coords,
+            rotation: UnitQuaternion::from_scaled_axis(transform.rotation * (PI / 180.0)),
+            scale: transform.scale,
+            children: vec![],
+        });
+
+        let mut joint = joint;
+        if index == 0 {
+            self.nodes[index].name = Some("root".to_string());
+            self.skin_joints.push((index, world_transform));
+        } else if let Some(j) = self
+            .joints
+            .iter()
+            .find(|j| j.node_id == graph_node.node_id())
+        {
+            self.nodes[index].name = Some(format!("{}{}", j.name, suffix));
+            joint = self.skin_joints.len();
+            self.skin_joints.push((index, world_transform));
+        }
+
+        match graph_node.kind() {
+            Some(Kind::Shape(shape, material, displacements)) => {
+                self.surface.push(
+                    world_transform
+                        .try_inverse()
+                        .expect("transform can be inverted"),
+                    *shape,
+                    *material,
+                    displacements,
+                );
+                self.shape_joints.push(joint);
+            }
+            Some(Kind::Symmetry(symmetry)) => {
+                for (k, (rotation, scale)) in symmetry.copy_rotations().into_iter().enumerate() {
+                    // The first copy is the source subtree, so it keeps the original names
+                    let copy_suffix = match k {
+                        0 => suffix.to_string(),
+                        _ => format!("{suffix}.{k}"),
+                    };
+
+                    let copy = self.nodes.len();
+                    self.nodes.push(Node {
+                        name: None,
+                        translation: Vector3::zeros(),
+                        rotation,
+                        scale: Vector3::repeat(scale),
+                        children: vec![],
+                    });
+                    self.nodes[index].children.push(copy);
+
+                    let copy_transform =
+                        world_transform * rotation.to_homogeneous() * Matrix4::new_scaling(scale);
+                    for child in visible_children(&graph_node) {
+                        let child = self.node(child, copy_transform, &copy_suffix, joint);
+                        self.nodes[copy].children.push(child);
+                    }
+                }
+
+                return index;
+            }
+            _ => {}
+        }
+
+        for child in visible_children(&graph_node) {
+            let child = self.node(child, world_transform, suffix, joint);
+            self.nodes[index].children.push(child);
+        }
+
+        index
+    }
+
+    // accessor appends `data` to the binary buffer in its own buffer view
+    fn accessor(
+        &mut self,
+        data: Vec<u8>,
+        count: usize,
+        component_type: u32,
+        element_type: &str,
+        target: Option<u32>,
+        min_max: Option<(Vector3<f32>, Vector3<f32>)>,
+    ) -> usize {
+        // Accessors need to be aligned to their component size, 4 covers all of them
+        while !self.buffer.len().is_multiple_of(4) {
+            self.buffer.push(0);
+        }
+
+        let target = target
+            .map(|t| format!(",\"target\":{t}"))
+            .unwrap_or_default();
+        self.buffer_views.push(format!(
+            "{{\"buffer\":0,\"byteOffset\":{},\"byteLength\":{}{target}}}",
+            self.buffer.len(),
+            data.len()
+        ));
+        self.buffer.extend(data);
+
+        let min_max = min_max
+            .map(|(min, max)| format!(",\"min\":{},\"max\":{}", floats(&min), floats(&max)))
+            .unwrap_or_default();
+        self.accessors.push(format!(
+            "{{\"bufferView\":{},\"componentType\":{component_type},\"count\":{count},\"type\":\"{element_type}\"{min_max}}}",
+            self.buffer_views.len() - 1
+        ));
+
+        self.accessors.len() - 1
+    }
+
+    fn json(&mut self, root: usize, mesh: &Mesh) -> String {
+        let mut scene_nodes = vec![root];
+        let mut meshes = vec![];
+        let mut skins = vec![];
+        let mut surface_node = None;
+
+        // Empty meshes aren't allowed, so a graph without any shapes is only a hierarchy
+        if !mesh.triangles.is_empty() {
+            let mut attributes = vec![
+                format!("\"POSITION\":{}", self.positions(mesh)),
+                format!(
+                    "\"NORMAL\":{}",
+                    self.vectors(&mesh.normals, Some(ARRAY_BUFFER))
+                ),
+                format!(
+                    "\"COLOR_0\":{}",
+                    self.vectors(&mesh.colors, Some(ARRAY_BUFFER))
+                ),
+            ];
+
+            let indices = self.accessor(
+                mesh.triangles
+                    .iter()
+                    .flatten()
+                    .flat_map(|i| i.to_le_bytes())
+                    .collect(),
+                mesh.triangles.len() * 3,
+                UNSIGNED_INT,
+                "SCALAR",
+                Some(ELEMENT_ARRAY_BUFFER),
+                None,
+            );
+
+            let mut skin = String::new();
+            if !self.joints.is_empty() {
+                attributes.extend(self.skinning(mesh));
+                skin = ",\"skin\":0".to_string();
+                skins.push(self.skin());
+            }
+
+            meshes.push(format!(
+                "{{\"primitives\":[{{\"attributes\":{{{}}},\"indices\":{indices}}}]}}",
+                attributes.join(",")
+            ));
+
+            surface_node = Some(format!("{{\"name\":\"surface\",\"mesh\":0{skin}}}"));
+            scene_nodes.push(self.nodes.len());
+        }
+
+        let mut nodes: Vec<String> = self.nodes.iter().map(node_json).collect();
+        nodes.extend(surface_node);
+
+        let mut json = format!(
+            "{{\"asset\":{{\"version\":\"2.0\",\"generator\":\"creature-creator\"}},\"scene\":0,\"scenes\":[{{\"nodes\":{:?}}}],\"nodes\":[{}]",
+            scene_nodes,
+            nodes.join(",")
+        );
+
+        if !meshes.is_empty() {
+            json += &format!(
+                ",\"meshes\":[{}],\"buffers\":[{{\"byteLength\":{}}}],\"bufferViews\":[{}],\"accessors\":[{}]",
+                meshes.join(","),
+                self.buffer.len(),
+                self.buffer_views.join(","),
+                self.accessors.join(",")
+            );
+        }
+        if !skins.is_empty() {
+            json += &format!(",\"skins\":[{}]", skins.join(","));
+        }
+
+        json + "}"
+    }
+
+    fn positions(&mut self, mesh: &Mesh) -> usize {
+        // POSITION is the only attribute that needs bounds
+        let (min, max) = mesh.vertices.iter().fold(
+            (Vector3::repeat(f32::MAX), Vector3::repeat(f32::MIN)),
+            |(min, max), v| (min.inf(&v.coords), max.sup(&v.coords)),
+        );
+
+        let coords: Vec<Vector3<f32>> = mesh.vertices.iter().map(|v| v.coords).collect();
+        let data = coords
+            .iter()
+            .flat_map(|v| v.iter().copied())
+            .collect::<Vec<f32>>();
+
+        self.accessor(
+            float_bytes(&data),
+            coords.len(),
+            FLOAT,
+            "VEC3",
+            Some(ARRAY_BUFFER),
+            Some((min, max)),
+        )
+    }
+
+    fn vectors(&mut self, vectors: &[Vector3<f32>], target: Option<u32>) -> usize {
+        let data = vectors
+            .iter()
+            .flat_map(|v| v.iter().copied())
+            .collect::<Vec<f32>>();
+
+        self.accessor(
+            float_bytes(&data),
+            vectors.len(),
+            FLOAT,
+            "VEC3",
+            target,
+            None,
+        )
+    }
+
+    // skinning binds every vertex to the joint of its nearest shape
+    fn skinning(&mut self, mesh: &Mesh) -> Vec<String> {
+        let mut joints = vec![];
+        let mut weights = vec![];
+
+        for vertex in &mesh.vertices {
+            let joint = self
+                .surface
+                .nearest_shape(*vertex)
+                .map(|shape| self.shape_joints[shape])
+                .unwrap_or(0);
+
+            joints.extend([joint as u16, 0, 0, 0].map(u16::to_le_bytes).concat());
+            weights.extend([1.0, 0.0, 0.0, 0.0]);
+        }
+
+        let count = mesh.vertices.len();
+        vec![
+            format!(
+                "\"JOINTS_0\":{}",
+                self.accessor(
+                    joints,
+                    count,
+                    UNSIGNED_SHORT,
+                    "VEC4",
+                    Some(ARRAY_BUFFER),
+                    None
+                )
+            ),
+            format!(
+                "\"WEIGHTS_0\":{}",
+                self.accessor(
+                    float_bytes(&weights),
+                    count,
+                    FLOAT,
+                    "VEC4",
+                    Some(ARRAY_BUFFER),
+                    None
+                )
+            ),
+        ]
+    }
+
+    fn skin(&mut self) -> String {
+        // The mesh is in world space, so binding is undoing each joint's world transform
+        let inverse_binds: Vec<f32> = self
+            .skin_joints
+            .iter()
+            .flat_map(|(_, world)| {
+                world
+                    .try_inverse()
+                    .expect("transform can be inverted")
+                    .as_slice()
+                    .to_vec()
+            })
+            .collect();
+
+        let inverse_binds = self.accessor(
+            float_bytes(&inverse_binds),
+            self.skin_joints.len(),
+            FLOAT,
+            "MAT4",
+            None,
+            None,
+        );
+        let joints: Vec<usize> = self.skin_joints.iter().map(|(node, _)| *node).collect();
+
+        format!("{{\"inverseBindMatrices\":{inverse_binds},\"skeleton\":0,\"joints\":{joints:?}}}")
+    }
+}
+
+fn node_json(node: &Node) -> String {
+    let mut fields = vec![];
+
+    if let Some(name) = &node.name {
+        fields.push(format!("\"name\":{}", json_string(name)));
+    }
+    if !node.children.is_empty() {
+        fields.push(format!("\"children\":{:?}", node.children));
+    }
+
+    let rotation = node.rotation.coords;
+    fields.push(format!("\"translation\":{}", floats(&node.translation)));
+    fields.push(format!("\"rotation\":{}", floats(&rotation)));
+    fields.push(format!("\"scale\":{}", floats(&node.scale)));
+
+    format!("{{{}}}", fields.join(","))
+}
+
+fn floats<'a>(values: impl IntoIterator<Item = &'a f32>) -> String {
+    let values: Vec<String> = values.into_iter().map(|v| v.to_string()).collect();
+
+    format!("[{}]", values.join(","))
+}
+
+fn float_bytes(values: &[f32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::from("\"");
+
+    for c in value.chars() {
+        match c {
+            '"' => escaped += "\\\"",
+            '\\' => escaped += "\\\\",
+            c if c.is_control() => escaped += &format!("\\u{:04x}", c as u32),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped + "\""
+}
+
+// visible_children skips hidden nodes, along with everything under them
+fn visible_children<'a>(graph_node: &'a NodeRef) -> Vec<NodeRef<'a>> {
+    graph_node
+        .children()
+        .into_iter()
+        .filter(|child| !child.is_hidden())
+        .collect()
+}
+
+fn write_glb<W: Write>(json: &[u8], buffer: &[u8], writer: &mut W) -> io::Result<()> {
+    // Chunks are padded to 4 bytes, JSON with spaces and binary with zeros
+    let json_padding = (4 - (json.len() % 4)) % 4;
+    let buffer_padding = (4 - (buffer.len() % 4)) % 4;
+
+    let json_length = json.len() + json_padding;
+    let buffer_length = buffer.len() + buffer_padding;
+
+    let mut length = 12 + 8 + json_length;
+    if !buffer.is_empty() {
+        length += 8 + buffer_length;
+    }
+
+    writer.write_all(&GLB_MAGIC.to_le_bytes())?;
+    writer.write_all(&GLB_VERSION.to_le_bytes())?;
+    writer.write_all(&(length as u32).to_le_bytes())?;
+
+    writer.write_all(&(json_length as u32).to_le_bytes())?;
+    writer.write_all(&CHUNK_JSON.to_le_bytes())?;
+    writer.write_all(json)?;
+    writer.write_all(&b"   "[..json_padding])?;
+
+    if !buffer.is_empty() {
+        writer.write_all(&(buffer_length as u32).to_le_bytes())?;
+        writer.write_all(&CHUNK_BIN.to_le_bytes())?;
+        writer.write_all(buffer)?;
+        writer.write_all(&[0; 3][..buffer_padding])?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{Matrix4, point};
+
+    use crate::export::tests::test_creature;
+    use crate::export::{export, Format, Joint};
+    use crate::NodeId;
+
+    fn joints(shoulder_id: NodeId, elbow_id: NodeId) -> Vec<Joint> {
+        vec![
+            Joint {
+                name: "shoulder".to_string(),
+                node_id: shoulder_id,
+            },
+            Joint {
+                name: "elbow".to_string(),
+                node_id: elbow_id,
+            },
+        ]
+    }
+
+    fn world_transforms(
+        node: gltf::Node,
+        parent: Matrix4<f32>,
+        transforms: &mut Vec<(Option<String>, Matrix4<f32>)>,
+    ) {
+        let world = parent * Matrix4::from(node.transform().matrix());
+        transforms.push((node.name().map(str::to_string), world));
+
+        for child in node.children() {
+            world_transforms(child, world, transforms);
+        }
+    }
+
+    #[test]
+    fn glb_round_trips() {
+        let (graph, shoulder_id, elbow_id) = test_creature();
+        let joints = joints(shoulder_id, elbow_id);
+
+        let mut written = vec![];
+        export(&graph, &joints, Format::Glb, 0.25, &mut written).unwrap();
+
+        let document = gltf::Gltf::from_slice(&written).unwrap();
+        let blob = document.blob.as_deref().unwrap();
+
+        let mut transforms = vec![];
+        for node in document.default_scene().unwrap().nodes() {
+            world_transforms(node, Matrix4::identity(), &mut transforms);
+        }
+
+        // Both sides of the mirror are exported, the copy is named after its source
+        let position = |name: &str| {
+            let (_, world) = transforms
+                .iter()
+                .find(|(n, _)| n.as_deref() == Some(name))
+                .unwrap();
+            world.transform_point(&point![0.0, 0.0, 0.0])
+        };
+        assert!((position("shoulder") - point![2.0, 0.0, 0.0]).magnitude() < 0.0001);
+        assert!((position("shoulder.1") - point![-2.0, 0.0, 0.0]).magnitude() < 0.0001);
+        assert!((position("elbow.1").x + position("elbow").x).abs() < 0.0001);
+
+        let skin = document.skins().next().unwrap();
+        let mut names: Vec<&str> = skin.joints().map(|j| j.name().unwrap()).collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec!["elbow", "elbow.1", "root", "shoulder", "shoulder.1"]
+        );
+
+        let mesh = document.meshes().next().unwrap();
+        let primitive = mesh.primitives().next().unwrap();
+        let reader = primitive.reader(|_| Some(blob));
+
+        let positions: Vec<[f32; 3]> = reader.read_positions().unwrap().collect();
+        let joint_indices: Vec<[u16; 4]> = reader.read_joints(0).unwrap().into_u16().collect();
+        let indices: Vec<u32> = reader.read_indices().unwrap().into_u32().collect();
+
+        assert!(!positions.is_empty());
+        assert_eq!(joint_indices.len(), positions.len());
+        assert!(indices.iter().all(|i| (*i as usize) < positions.len()));
+
+        // Vertices on the red spheres are bound to a shoulder, never the root
+        for (position, joints) in positions.iter().zip(&joint_indices) {
+            let joint = skin.joints().nth(joints[0] as usize).unwrap();
+            let name = joint.name().unwrap();
+
+            assert_ne!(name, "root");
+            if position[1] < -0.5 {
+                assert!(name.starts_with("shoulder"));
+                assert_eq!(name == "shoulder", position[0] > 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn glb_skips_hidden_nodes() {
+        let (mut graph, shoulder_id, elbow_id) = test_creature();
+        graph.node_mut(elbow_id).set_hidden(true);
+
+        let mut written = vec![];
+        export(
+            &graph,
+            &joints(shoulder_id, elbow_id),
+            Format::Glb,
+            0.25,
+            &mut written,
+        )
+        .unwrap();
+
+        let document = gltf::Gltf::from_slice(&written).unwrap();
+        let blob = document.blob.as_deref().unwrap();
+
+        assert!(document
+            .nodes()
+            .all(|n| !n.name().unwrap_or_default().starts_with("elbow")));
+
+        // Only the shoulder spheres are left, the forearms reached up past them
+        let mesh = document.meshes().next().unwrap();
+        let primitive = mesh.primitives().next().unwrap();
+        let reader = primitive.reader(|_| Some(blob));
+        assert!(reader.read_positions().unwrap().all(|p| p[1] < 1.25));
+    }
+
+    #[test]
+    fn glb_without_joints_has_no_skin() {
+        let (graph, _, _) = test_creature();
+
+        let mut written = vec![];
+        export(&graph, &[], Format::Glb, 0.5, &mut written).unwrap();
+
+        let document = gltf::Gltf::from_slice(&written).unwrap();
+        assert_eq!(document.skins().count(), 0);
+        assert_eq!(document.meshes().count(), 1);
+        assert_eq!(written.len() % 4, 0);
+    }
+}
@@ -0,0 +1,101 @@
+// Exporters write a RenderGraph out to files other tools can read
+// Only shapes are exported, they're turned into a single mesh with `mesh::extract`
+// Surface samples can be written separately as point clouds, see `points`, and lines drawn
+// as diagrams, see `svg`
+
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+use crate::graph::{NodeId, RenderGraph};
+use crate::mesh::{extract, Mesh};
+use crate::surface::Surface;
+
+pub mod gltf;
+pub mod obj;
+pub mod points;
+pub mod stl;
+pub mod svg;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Format {
+    Obj,
+    Stl,
+    Glb,
+}
+
+impl Format {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "obj" => Some(Format::Obj),
+            "stl" => Some(Format::Stl),
+            "glb" => Some(Format::Glb),
+            _ => None,
+        }
+    }
+}
+
+// A named node, exported as a joint of the glTF skin
+pub struct Joint {
+    pub name: String,
+    pub node_id: NodeId,
+}
+
+// export writes `graph` to `writer`, the surface is meshed with cells of `cell_size`
+// Joints are only used by glTF, other formats don't have a node hierarchy
+pub fn export<W: Write>(
+    graph: &RenderGraph,
+    joints: &[Joint],
+    format: Format,
+    cell_size: f32,
+    writer: &mut W,
+) -> io::Result<()> {
+    match format {
+        Format::Obj => obj::write(&surface_mesh(graph, cell_size), writer),
+        Format::Stl => stl::write(&surface_mesh(graph, cell_size), writer),
+        Format::Glb => gltf::write(graph, joints, cell_size, writer),
+    }
+}
+
+fn surface_mesh(graph: &RenderGraph, cell_size: f32) -> Mesh {
+    extract(&Surface::from_graph(graph), cell_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{point, vector};
+
+    use crate::graph::RenderGraph;
+    use crate::shapes::{Material, Shape};
+    use crate::symmetry::Symmetry;
+    use crate::NodeId;
+
+    // A mirrored pair of two bone arms, like the one in the app
+    pub(super) fn test_creature() -> (RenderGraph, NodeId, NodeId) {
+        let mut graph = RenderGraph::new();
+        let mut root = graph.root_mut();
+
+        let mut mirror = root.push_symmetry(Symmetry::new_mirror(vector![1.0, 0.0, 0.0]));
+
+        let mut shoulder = mirror.push_empty();
+        shoulder.with_transform(|t| t.position = point![2.0, 0.0, 0.0]);
+        let shoulder_id = shoulder.node_id();
+
+        shoulder
+            .push_shape(Shape::Sphere(1.0))
+            .set_material(Material::new(vector![1.0, 0.0, 0.0]));
+
+        let mut elbow = shoulder.push_empty();
+        elbow.with_transform(|t| {
+            t.position = point![0.0, 1.5, 0.0];
+            t.rotation = vector![0.0, 0.0, 30.0];
+        });
+        let elbow_id = elbow.node_id();
+
+        elbow
+            .push_shape(Shape::Ellipsoid(vector![0.5, 1.0, 0.5]))
+            .set_material(Material::new(vector![0.0, 0.0, 1.0]));
+
+        (graph, shoulder_id, elbow_id)
+    }
+}
@@ -0,0 +1,87 @@
+use std::io;
+use std::io::Write;
+
+use crate::mesh::Mesh;
+
+// Wavefront OBJ, vertex colors are written after the position which most tools understand
+pub fn write<W: Write>(mesh: &Mesh, writer: &mut W) -> io::Result<()> {
+    writeln!(writer, "# creature-creator")?;
+
+    for (v, c) in mesh.vertices.iter().zip(&mesh.colors) {
+        writeln!(writer, "v {} {} {} {} {} {}", v.x, v.y, v.z, c.x, c.y, c.z)?;
+    }
+
+    for n in &mesh.normals {
+        writeln!(writer, "vn {} {} {}", n.x, n.y, n.z)?;
+    }
+
+    // OBJ indices start at 1
+    for [a, b, c] in &mesh.triangles {
+        let (a, b, c) = (a + 1, b + 1, c + 1);
+        writeln!(writer, "f {a}//{a} {b}//{b} {c}//{c}")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{Point3, Vector3};
+
+    use crate::export::tests::test_creature;
+    use crate::export::{export, surface_mesh, Format};
+    use crate::mesh::Mesh;
+
+    fn parse(obj: &str) -> Mesh {
+        let mut mesh = Mesh::new();
+
+        for line in obj.lines() {
+            let mut parts = line.split_whitespace();
+            let floats = |parts: std::str::SplitWhitespace| -> Vec<f32> {
+                parts.map(|p| p.parse().unwrap()).collect()
+            };
+
+            match parts.next() {
+                Some("v") => {
+                    let v = floats(parts);
+                    mesh.vertices.push(Point3::new(v[0], v[1], v[2]));
+                    mesh.colors.push(Vector3::new(v[3], v[4], v[5]));
+                }
+                Some("vn") => {
+                    let n = floats(parts);
+                    mesh.normals.push(Vector3::new(n[0], n[1], n[2]));
+                }
+                Some("f") => {
+                    let face: Vec<u32> = parts
+                        .map(|p| {
+                            let (v, n) = p.split_once("//").unwrap();
+                            assert_eq!(v, n);
+                            v.parse::<u32>().unwrap() - 1
+                        })
+                        .collect();
+                    mesh.triangles.push([face[0], face[1], face[2]]);
+                }
+                _ => {}
+            }
+        }
+
+        mesh
+    }
+
+    #[test]
+    fn obj_round_trips() {
+        let (graph, _, _) = test_creature();
+
+        let mut written = vec![];
+        export(&graph, &[], Format::Obj, 0.25, &mut written).unwrap();
+        let mesh = surface_mesh(&graph, 0.25);
+
+        let parsed = parse(&String::from_utf8(written).unwrap());
+
+        assert!(!parsed.triangles.is_empty());
+        assert_eq!(parsed.vertices, mesh.vertices);
+        assert_eq!(parsed.normals, mesh.normals);
+        assert_eq!(parsed.colors, mesh.colors);
+        assert_eq!(parsed.triangles, mesh.triangles);
+    }
+}
@@ -0,0 +1,182 @@
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+use nalgebra::{Point3, Vector3};
+
+// Point clouds are for looking at surface samples in other tools, rather than the surface itself
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Point {
+    pub position: Point3<f32>,
+    pub normal: Vector3<f32>,
+    pub radius: f32,
+    pub color: Vector3<f32>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PointFormat {
+    AsciiPly,
+    BinaryPly,
+    // Positions only, one point per line
+    Xyz,
+}
+
+impl PointFormat {
+    // PLY files are written as binary, which is smaller and just as widely supported
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "ply" => Some(PointFormat::BinaryPly),
+            "xyz" => Some(PointFormat::Xyz),
+            _ => None,
+        }
+    }
+}
+
+pub fn write<W: Write>(points: &[Point], format: PointFormat, writer: &mut W) -> io::Result<()> {
+    match format {
+        PointFormat::AsciiPly => {
+            write_ply_header(points.len(), "ascii", writer)?;
+
+            for p in points {
+                let [r, g, b] = color_bytes(p.color);
+                writeln!(
+                    writer,
+                    "{} {} {} {} {} {} {} {r} {g} {b}",
+                    p.position.x,
+                    p.position.y,
+                    p.position.z,
+                    p.normal.x,
+                    p.normal.y,
+                    p.normal.z,
+                    p.radius
+                )?;
+            }
+        }
+        PointFormat::BinaryPly => {
+            write_ply_header(points.len(), "binary_little_endian", writer)?;
+
+            for p in points {
+                for v in p.position.iter().chain(p.normal.iter()) {
+                    writer.write_all(&v.to_le_bytes())?;
+                }
+                writer.write_all(&p.radius.to_le_bytes())?;
+                writer.write_all(&color_bytes(p.color))?;
+            }
+        }
+        PointFormat::Xyz => {
+            for p in points {
+                writeln!(writer, "{} {} {}", p.position.x, p.position.y, p.position.z)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn write_ply_header<W: Write>(count: usize, format: &str, writer: &mut W) -> io::Result<()> {
+    writeln!(writer, "ply")?;
+    writeln!(writer, "format {format} 1.0")?;
+    writeln!(writer, "comment creature-creator particles")?;
+    writeln!(writer, "element vertex {count}")?;
+    for property in ["x", "y", "z", "nx", "ny", "nz", "radius"] {
+        writeln!(writer, "property float {property}")?;
+    }
+    for property in ["red", "green", "blue"] {
+        writeln!(writer, "property uchar {property}")?;
+    }
+    writeln!(writer, "end_header")
+}
+
+// Viewers expect 8 bit colors
+fn color_bytes(color: Vector3<f32>) -> [u8; 3] {
+    [color.x, color.y, color.z].map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{point, vector};
+
+    use crate::export::points::{write, Point, PointFormat};
+
+    fn points() -> Vec<Point> {
+        vec![
+            Point {
+                position: point![1.0, 2.0, 3.0],
+                normal: vector![0.0, 1.0, 0.0],
+                radius: 0.25,
+                color: vector![1.0, 0.0, 0.5],
+            },
+            Point {
+                position: point![-1.5, 0.0, 0.125],
+                normal: vector![0.0, 0.0, -1.0],
+                radius: 0.5,
+                color: vector![0.0, 2.0, 0.0],
+            },
+        ]
+    }
+
+    fn split_header(written: &[u8]) -> (String, &[u8]) {
+        let end = b"end_header\n";
+        let at = written
+            .windows(end.len())
+            .position(|w| w == end)
+            .expect("has a header")
+            + end.len();
+
+        (
+            String::from_utf8(written[..at].to_vec()).unwrap(),
+            &written[at..],
+        )
+    }
+
+    #[test]
+    fn ascii_ply_round_trips() {
+        let mut written = vec![];
+        write(&points(), PointFormat::AsciiPly, &mut written).unwrap();
+
+        let (header, body) = split_header(&written);
+        assert!(header.starts_with("ply\nformat ascii 1.0\n"));
+        assert!(header.contains("element vertex 2\n"));
+
+        let rows: Vec<Vec<f32>> = String::from_utf8(body.to_vec())
+            .unwrap()
+            .lines()
+            .map(|l| l.split(' ').map(|v| v.parse().unwrap()).collect())
+            .collect();
+
+        assert_eq!(
+            rows[0],
+            vec![1.0, 2.0, 3.0, 0.0, 1.0, 0.0, 0.25, 255.0, 0.0, 128.0]
+        );
+        // Colors are clamped
+        assert_eq!(rows[1][7..], [0.0, 255.0, 0.0]);
+    }
+
+    #[test]
+    fn binary_ply_round_trips() {
+        let mut written = vec![];
+        write(&points(), PointFormat::BinaryPly, &mut written).unwrap();
+
+        let (header, body) = split_header(&written);
+        assert!(header.starts_with("ply\nformat binary_little_endian 1.0\n"));
+
+        // 7 floats and 3 bytes per point
+        assert_eq!(body.len(), 2 * 31);
+
+        let second = &body[31..];
+        let float = |i: usize| f32::from_le_bytes(second[i * 4..(i + 1) * 4].try_into().unwrap());
+        assert_eq!((float(0), float(1), float(2)), (-1.5, 0.0, 0.125));
+        assert_eq!(float(5), -1.0);
+        assert_eq!(float(6), 0.5);
+        assert_eq!(second[28..], [0, 255, 0]);
+    }
+
+    #[test]
+    fn xyz_has_positions() {
+        let mut written = vec![];
+        write(&points(), PointFormat::Xyz, &mut written).unwrap();
+
+        assert_eq!(String::from_utf8(written).unwrap(), "1 2 3\n-1.5 0 0.125\n");
+    }
+}
@@ -0,0 +1,75 @@
+use std::io;
+use std::io::Write;
+
+use nalgebra::Vector3;
+
+use crate::mesh::Mesh;
+
+// Binary STL, it has no colors or shared vertices so only the triangles are written
+pub fn write<W: Write>(mesh: &Mesh, writer: &mut W) -> io::Result<()> {
+    let mut header = [0u8; 80];
+    let name = b"creature-creator";
+    header[..name.len()].copy_from_slice(name);
+    writer.write_all(&header)?;
+
+    writer.write_all(&(mesh.triangles.len() as u32).to_le_bytes())?;
+
+    for triangle in &mesh.triangles {
+        let [a, b, c] = triangle.map(|i| mesh.vertices[i as usize]);
+        let normal = (b - a)
+            .cross(&(c - a))
+            .try_normalize(0.0)
+            .unwrap_or_else(Vector3::zeros);
+
+        for v in [normal, a.coords, b.coords, c.coords] {
+            for component in v.iter() {
+                writer.write_all(&component.to_le_bytes())?;
+            }
+        }
+
+        // Attribute byte count, unused
+        writer.write_all(&0u16.to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Point3;
+
+    use crate::export::tests::test_creature;
+    use crate::export::{export, surface_mesh, Format};
+
+    fn read_f32(bytes: &[u8], offset: usize) -> f32 {
+        f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn stl_round_trips() {
+        let (graph, _, _) = test_creature();
+
+        let mut written = vec![];
+        export(&graph, &[], Format::Stl, 0.25, &mut written).unwrap();
+        let mesh = surface_mesh(&graph, 0.25);
+
+        let count = u32::from_le_bytes(written[80..84].try_into().unwrap()) as usize;
+        assert_eq!(count, mesh.triangles.len());
+        assert_eq!(written.len(), 84 + (count * 50));
+
+        for (i, triangle) in mesh.triangles.iter().enumerate() {
+            let offset = 84 + (i * 50);
+
+            for (corner, vertex) in triangle.iter().enumerate() {
+                let at = offset + 12 + (corner * 12);
+                let parsed = Point3::new(
+                    read_f32(&written, at),
+                    read_f32(&written, at + 4),
+                    read_f32(&written, at + 8),
+                );
+
+                assert_eq!(parsed, mesh.vertices[*vertex as usize]);
+            }
+        }
+    }
+}
@@ -0,0 +1,563 @@
+use std::io;
+use std::io::Write;
+
+use nalgebra::{center, Matrix4, Point2, Point3, Vector2, Vector3};
+
+use crate::camera::NEAR;
+use crate::export::points::Point;
+use crate::labels::{Anchor, GLYPH_HEIGHT, Label};
+use crate::lines::{Segment, SegmentStyle};
+use crate::trace::BACKGROUND_COLOR;
+use crate::Viewport;
+
+// SVG diagrams draw lines the way the window does, for documentation and rig diagrams that stay
+// sharp at any size. There's no depth buffer, everything is drawn back to front instead, with the
+// surface particles as discs so they hide the lines behind them
+
+// Lines are cut into pieces no longer than this many pixels, so each piece is sorted on its own
+// and a line passing through the surface is only hidden where it's behind it
+const PIECE_LENGTH: f32 = 8.0;
+// Particles are lit from the camera, just enough to show the shape of the surface
+const PARTICLE_AMBIENT: f32 = 0.5;
+
+enum Item {
+    Disc {
+        center: Point2<f32>,
+        radius: f32,
+        color: Vector3<f32>,
+    },
+    Line {
+        from: Point2<f32>,
+        to: Point2<f32>,
+        width: f32,
+        color: Vector3<f32>,
+        // Dash and gap lengths in pixels, and how far into them the line starts
+        dashes: Option<(Vec<f32>, f32)>,
+    },
+    // Drawn by the marker with the same index
+    ArrowHead {
+        from: Point2<f32>,
+        to: Point2<f32>,
+        marker: usize,
+    },
+    // A line of a label, `at` is the middle, start or end of its top edge
+    Text {
+        at: Point2<f32>,
+        text: String,
+        size: f32,
+        color: Vector3<f32>,
+        align: &'static str,
+    },
+}
+
+// Markers are sized in pixels, so every arrow head gets its own
+struct Marker {
+    length: f32,
+    width: f32,
+    color: Vector3<f32>,
+}
+
+// write draws the segments, labels and particles as they're seen through the viewport
+pub fn write<W: Write>(
+    segments: &[Segment],
+    labels: &[(Matrix4<f32>, Label)],
+    particles: &[Point],
+    viewport: &Viewport,
+    writer: &mut W,
+) -> io::Result<()> {
+    let (width, height) = viewport.size();
+
+    let mut items = vec![];
+    let mut markers = vec![];
+    for p in particles {
+        particle(viewport, p, &mut items);
+    }
+    for segment in segments {
+        match segment.style {
+            SegmentStyle::Line => line(viewport, segment, &mut items),
+            SegmentStyle::ArrowHead => arrow_head(viewport, segment, &mut items, &mut markers),
+            SegmentStyle::Dot => dot(viewport, segment, &mut items),
+        }
+    }
+    for (transform, l) in labels {
+        label(viewport, l, transform, &mut items);
+    }
+
+    // Painter's algorithm, the furthest items are drawn first and covered by nearer ones
+    items.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+
+    writeln!(
+        writer,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+    )?;
+
+    if !markers.is_empty() {
+        writeln!(writer, "<defs>")?;
+        for (i, m) in markers.iter().enumerate() {
+            // The marker's tip is at the end of its path, and it's stretched to the head's size
+            writeln!(
+                writer,
+                r#"<marker id="arrow-{i}" viewBox="0 0 1 1" refX="1" refY="0.5" markerUnits="userSpaceOnUse" markerWidth="{:.2}" markerHeight="{:.2}" preserveAspectRatio="none" orient="auto"><path d="M 0 0 L 1 0.5 L 0 1 z" fill="{}"/></marker>"#,
+                m.length,
+                m.width,
+                hex(m.color)
+            )?;
+        }
+        writeln!(writer, "</defs>")?;
+    }
+
+    writeln!(
+        writer,
+        r#"<rect width="100%" height="100%" fill="{}"/>"#,
+        hex(BACKGROUND_COLOR)
+    )?;
+
+    for (_, item) in &items {
+        match item {
+            Item::Disc {
+                center,
+                radius,
+                color,
+            } => writeln!(
+                writer,
+                r#"<circle cx="{:.2}" cy="{:.2}" r="{:.2}" fill="{}"/>"#,
+                center.x,
+                center.y,
+                radius,
+                hex(*color)
+            )?,
+            Item::Line {
+                from,
+                to,
+                width,
+                color,
+                dashes,
+            } => writeln!(
+                writer,
+                r#"<line x1="{:.2}" y1="{:.2}" x2="{:.2}" y2="{:.2}" stroke="{}" stroke-width="{:.2}"{}/>"#,
+                from.x,
+                from.y,
+                to.x,
+                to.y,
+                hex(*color),
+                width,
+                dashes.as_ref().map(dash_attributes).unwrap_or_default()
+            )?,
+            Item::ArrowHead { from, to, marker } => writeln!(
+                writer,
+                r#"<path d="M {:.2} {:.2} L {:.2} {:.2}" stroke="none" marker-end="url(#arrow-{marker})"/>"#,
+                from.x, from.y, to.x, to.y
+            )?,
+            Item::Text {
+                at,
+                text,
+                size,
+                color,
+                align,
+            } => writeln!(
+                writer,
+                r#"<text x="{:.2}" y="{:.2}" font-family="monospace" font-size="{:.2}" fill="{}" text-anchor="{align}" dominant-baseline="text-before-edge">{}</text>"#,
+                at.x,
+                at.y,
+                size,
+                hex(*color),
+                escape(text)
+            )?,
+        }
+    }
+
+    writeln!(writer, "</svg>")
+}
+
+fn particle(viewport: &Viewport, p: &Point, items: &mut Vec<(f32, Item)>) {
+    let clip = viewport.clip(p.position);
+    let facing = p.normal.dot(&(viewport.eye() - p.position).normalize());
+
+    // Particles facing away are behind the ones facing the camera, so they're never seen
+    if clip.w < NEAR || facing <= 0.0 {
+        return;
+    }
+
+    items.push((
+        clip.w,
+        Item::Disc {
+            center: viewport.screen(clip),
+            radius: p.radius * viewport.pixels_per_unit(clip.w),
+            color: p
+                .color
+                .scale(PARTICLE_AMBIENT + (1.0 - PARTICLE_AMBIENT) * facing),
+        },
+    ));
+}
+
+fn line(viewport: &Viewport, segment: &Segment, items: &mut Vec<(f32, Item)>) {
+    let (a, b) = (segment.end, segment.start);
+    let Some((t_a, t_b)) = clip_near(viewport, a, b) else {
+        return;
+    };
+
+    let at = |t: f32| a + (b - a).scale(t);
+    let world_length = (b - a).magnitude();
+    let length = (viewport.screen(viewport.clip(at(t_b)))
+        - viewport.screen(viewport.clip(at(t_a))))
+    .magnitude();
+    let pieces = (length / PIECE_LENGTH).ceil().max(1.0) as usize;
+
+    for i in 0..pieces {
+        let t_from = t_a + (t_b - t_a) * i as f32 / pieces as f32;
+        let t_to = t_a + (t_b - t_a) * (i + 1) as f32 / pieces as f32;
+        let (from, to) = (viewport.clip(at(t_from)), viewport.clip(at(t_to)));
+        let depth = (from.w + to.w) / 2.0;
+
+        let (from, to) = (viewport.screen(from), viewport.screen(to));
+        if (to - from).magnitude() <= f32::EPSILON {
+            continue;
+        }
+
+        // Dashes are sized for each piece's own depth, and each piece picks the pattern up where
+        // the one before it left off
+        let pixels = viewport.pixels_per_unit(depth);
+        let dashes = segment.dashes.as_ref().map(|dashes| {
+            let period: f32 = dashes.lengths.iter().sum();
+            let offset = (dashes.offset + t_from * world_length).rem_euclid(period);
+
+            (
+                dashes.lengths.iter().map(|l| l * pixels).collect(),
+                offset * pixels,
+            )
+        });
+
+        items.push((
+            depth,
+            Item::Line {
+                from,
+                to,
+                width: segment.thickness * pixels,
+                color: segment.color,
+                dashes,
+            },
+        ));
+    }
+}
+
+// dot is drawn like a particle, without the shading
+fn dot(viewport: &Viewport, segment: &Segment, items: &mut Vec<(f32, Item)>) {
+    let clip = viewport.clip(center(&segment.start, &segment.end));
+    if clip.w < NEAR {
+        return;
+    }
+
+    items.push((
+        clip.w,
+        Item::Disc {
+            center: viewport.screen(clip),
+            radius: segment.thickness / 2.0 * viewport.pixels_per_unit(clip.w),
+            color: segment.color,
+        },
+    ));
+}
+
+// label is laid out like label_quads does it, a line of text at a time
+fn label(
+    viewport: &Viewport,
+    label: &Label,
+    transform: &Matrix4<f32>,
+    items: &mut Vec<(f32, Item)>,
+) {
+    let clip = viewport.clip(transform.transform_point(&label.origin()));
+    if clip.w < NEAR {
+        return;
+    }
+
+    let pixel = label.size / GLYPH_HEIGHT as f32;
+    let line_height = label.size + pixel;
+    let lines: Vec<&str> = label.text.lines().collect();
+    let height = (lines.len() as f32 * line_height - pixel).max(0.0);
+
+    // How far down from the origin the top of the text is, and which end of it the origin is at
+    let (top, align) = match label.anchor {
+        Anchor::Center => (-height / 2.0, "middle"),
+        Anchor::Right => (-height / 2.0, "start"),
+        Anchor::Left => (-height / 2.0, "end"),
+        Anchor::Above => (-height, "middle"),
+        Anchor::Below => (0.0, "middle"),
+    };
+
+    let origin = viewport.screen(clip);
+    for (row, line) in lines.iter().enumerate() {
+        items.push((
+            clip.w,
+            Item::Text {
+                at: origin + Vector2::new(0.0, top + row as f32 * line_height),
+                text: line.to_string(),
+                size: label.size,
+                color: label.color,
+                align,
+            },
+        ));
+    }
+}
+
+fn arrow_head(
+    viewport: &Viewport,
+    segment: &Segment,
+    items: &mut Vec<(f32, Item)>,
+    markers: &mut Vec<Marker>,
+) {
+    let (from, to) = (viewport.clip(segment.start), viewport.clip(segment.end));
+    if from.w < NEAR || to.w < NEAR {
+        return;
+    }
+
+    let depth = (from.w + to.w) / 2.0;
+    let (from, to) = (viewport.screen(from), viewport.screen(to));
+    let length = (to - from).magnitude();
+    // Arrows pointing at the camera have no direction to orient a marker with
+    if length <= f32::EPSILON {
+        return;
+    }
+
+    items.push((
+        depth,
+        Item::ArrowHead {
+            from,
+            to,
+            marker: markers.len(),
+        },
+    ));
+    markers.push(Marker {
+        length,
+        width: segment.thickness * viewport.pixels_per_unit(depth),
+        color: segment.color,
+    });
+}
+
+// clip_near returns the part of the segment from a to b that's in front of the near plane,
+// as fractions of the way from a to b
+fn clip_near(viewport: &Viewport, a: Point3<f32>, b: Point3<f32>) -> Option<(f32, f32)> {
+    let (w_a, w_b) = (viewport.clip(a).w, viewport.clip(b).w);
+    if w_a < NEAR && w_b < NEAR {
+        return None;
+    }
+
+    let crossing = (NEAR - w_a) / (w_b - w_a);
+    Some((
+        if w_a < NEAR { crossing } else { 0.0 },
+        if w_b < NEAR { crossing } else { 1.0 },
+    ))
+}
+
+fn dash_attributes((lengths, offset): &(Vec<f32>, f32)) -> String {
+    let lengths: Vec<_> = lengths.iter().map(|l| format!("{l:.2}")).collect();
+
+    format!(
+        r#" stroke-dasharray="{}" stroke-dashoffset="{offset:.2}""#,
+        lengths.join(" ")
+    )
+}
+
+// escape makes text safe to put between XML tags
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '&' => escaped += "&amp;",
+            '<' => escaped += "&lt;",
+            '>' => escaped += "&gt;",
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+fn hex(color: Vector3<f32>) -> String {
+    let [r, g, b] = color
+        .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+        .data
+        .0[0];
+
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{Matrix4, point, vector, Vector3};
+
+    use crate::export::points::Point;
+    use crate::export::svg::write;
+    use crate::labels::{Anchor, Label, labels_from_graph};
+    use crate::lines::{
+        dashed_line_segments, Fill, Line, line_segments, Segment, segments_from_graph,
+    };
+    use crate::{Camera, RenderGraph, Viewport};
+
+    const RED: Vector3<f32> = vector![1.0, 0.0, 0.0];
+    const BLUE: Vector3<f32> = vector![0.0, 0.0, 1.0];
+
+    fn camera() -> Camera {
+        Camera::new(point![0.0, 0.0, 10.0], point![0.0, 0.0, 0.0], 60.0)
+    }
+
+    fn viewport() -> Viewport {
+        Viewport::new(&camera(), (200, 100))
+    }
+
+    fn svg(segments: &[Segment], particles: &[Point]) -> String {
+        let mut written = vec![];
+        write(segments, &[], particles, &viewport(), &mut written).unwrap();
+
+        String::from_utf8(written).unwrap()
+    }
+
+    fn segments(line: Line, transform: Matrix4<f32>) -> Vec<Segment> {
+        let mut segments = vec![];
+        line_segments(&line, &mut segments, &transform, &viewport());
+
+        segments
+    }
+
+    fn dashed_segments(line: Line, transform: Matrix4<f32>) -> Vec<Segment> {
+        let mut segments = vec![];
+        dashed_line_segments(&line, &mut segments, &transform, &viewport());
+
+        segments
+    }
+
+    // A flat patch of particles facing the camera, covering the middle of the image
+    fn wall() -> Vec<Point> {
+        (-4..=4)
+            .flat_map(|x| (-4..=4).map(move |y| (x, y)))
+            .map(|(x, y)| Point {
+                position: point![x as f32 * 0.5, y as f32 * 0.5, 0.0],
+                normal: vector![0.0, 0.0, 1.0],
+                radius: 0.5,
+                color: vector![1.0, 1.0, 1.0],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lines_are_sorted_against_particles() {
+        // A horizontal line in front of the wall and one behind it
+        let horizontal = Matrix4::new_rotation(vector![0.0, 0.0, std::f32::consts::FRAC_PI_2]);
+        let mut lines = segments(
+            Line::new(2.0).color(RED),
+            Matrix4::new_translation(&vector![0.0, 0.0, 1.0]) * horizontal,
+        );
+        lines.extend(segments(
+            Line::new(2.0).color(BLUE),
+            Matrix4::new_translation(&vector![0.0, 0.0, -1.0]) * horizontal,
+        ));
+
+        let svg = svg(&lines, &wall());
+
+        let first_disc = svg.find("<circle").unwrap();
+        let last_disc = svg.rfind("<circle").unwrap();
+        assert!(svg.matches("<circle").count() > 0);
+        assert!(svg.rfind(r##"stroke="#0000ff""##).unwrap() < first_disc);
+        assert!(svg.find(r##"stroke="#ff0000""##).unwrap() > last_disc);
+    }
+
+    #[test]
+    fn particles_facing_away_are_skipped() {
+        let mut particles = wall();
+        for p in &mut particles {
+            p.normal = -p.normal;
+        }
+
+        assert!(!svg(&[], &particles).contains("<circle"));
+    }
+
+    #[test]
+    fn dashes_are_stroked_and_dots_are_drawn_separately() {
+        // Dashes and dots are as big as the line's pattern says, 10 units away
+        let focal_length = camera().projection(2.0).as_matrix()[(1, 1)] * 50.0;
+        let pixels = focal_length / 10.0;
+
+        let line = Line::new(2.0).fill(Fill::Dashed(0.25)).dash_phase(0.1);
+        let svg_dashed = svg(&dashed_segments(line, Matrix4::identity()), &[]);
+        // The line is split into pieces for sorting, each one dashed where it is in the pattern
+        let pieces = svg_dashed.matches("<line").count();
+        assert!(pieces > 1);
+        assert_eq!(svg_dashed.matches("stroke-dasharray").count(), pieces);
+        let dash = 0.25 * pixels;
+        assert!(
+            svg_dashed.contains(&format!(
+                r#"stroke-dasharray="{dash:.2} {dash:.2}" stroke-dashoffset="{:.2}""#,
+                0.1 * pixels
+            )),
+            "{svg_dashed}"
+        );
+        assert!(!svg(&segments(Line::new(2.0), Matrix4::identity()), &[]).contains("dasharray"));
+
+        let dots = segments(
+            Line::new(2.0).fill(Fill::Dotted(0.5)).thickness(0.2),
+            Matrix4::identity(),
+        );
+        let svg = svg(&dots, &[]);
+        assert_eq!(svg.matches("<circle").count(), 4);
+        let radius = 0.1 * pixels;
+        assert!(svg.contains(&format!(r#"r="{radius:.2}""#)), "{svg}");
+    }
+
+    #[test]
+    fn arrow_heads_use_markers() {
+        let lines = segments(
+            Line::new_arrow(2.0).thickness(0.1).color(RED),
+            Matrix4::identity(),
+        );
+        let svg = svg(&lines, &[]);
+
+        assert_eq!(svg.matches("<marker").count(), 1);
+        assert!(svg.contains(r##"fill="#ff0000""##));
+        assert!(svg.contains(r#"marker-end="url(#arrow-0)""#));
+    }
+
+    #[test]
+    fn lines_through_the_camera_are_clipped() {
+        // From in front of the camera to behind it
+        let lines = segments(
+            Line::new(20.0),
+            Matrix4::new_translation(&vector![0.0, 1.0, 5.0])
+                * Matrix4::new_rotation(vector![std::f32::consts::FRAC_PI_2, 0.0, 0.0]),
+        );
+        let svg = svg(&lines, &[]);
+
+        assert!(svg.contains("<line"));
+        assert!(!svg.contains("NaN") && !svg.contains("inf"), "{svg}");
+    }
+
+    #[test]
+    fn labels_are_written_as_text() {
+        let mut graph = RenderGraph::new();
+        graph.root_mut().push_label(
+            Label::new("arm & <hand>\nleft")
+                .anchor(Anchor::Right)
+                .color(RED)
+                .leader(vector![1.0, 0.0, 0.0]),
+        );
+
+        let mut written = vec![];
+        write(
+            &segments_from_graph(&graph, &viewport()),
+            &labels_from_graph(&graph),
+            &[],
+            &viewport(),
+            &mut written,
+        )
+        .unwrap();
+        let svg = String::from_utf8(written).unwrap();
+
+        // The leader goes along with the text, a line of it at a time
+        assert!(svg.contains("<line"));
+        assert_eq!(svg.matches("<text").count(), 2);
+        assert!(svg.contains(r#"text-anchor="start""#));
+        assert!(svg.contains(r##"fill="#ff0000""##));
+        assert!(svg.contains(">arm &amp; &lt;hand&gt;</text>"), "{svg}");
+
+        // The text starts at the end of the leader, a unit right of the middle of the image
+        let x = (viewport().screen(viewport().clip(point![1.0, 0.0, 0.0]))).x;
+        assert!(svg.contains(&format!(r#"<text x="{x:.2}""#)), "{svg}");
+    }
+}
@@ -1,20 +1,29 @@
 use generational_arena::Arena;
-use nalgebra::Matrix4;
+use nalgebra::{Matrix4, Point3, Vector3};
 
+use crate::labels::Label;
 use crate::lines::Line;
-use crate::shapes::Shape;
+use crate::shapes::{Displacement, Material, Shape};
+use crate::symmetry::Symmetry;
 use crate::transform::NodeTransform;
 
 pub type NodeId = generational_arena::Index;
 
 pub enum Kind {
     Line(Line),
-    Shape(Shape),
+    // Text facing the camera
+    Label(Label),
+    // Displacements are applied in the shape's local space, in order
+    Shape(Shape, Material, Vec<Displacement>),
+    // Children of a symmetry node are walked once for every copy
+    Symmetry(Symmetry),
 }
 
 pub struct Node {
     pub transform: NodeTransform,
     kind: Option<Kind>,
+    // Hidden nodes and everything under them are skipped when walking the graph
+    hidden: bool,
 }
 
 impl Node {
@@ -22,11 +31,13 @@ impl Node {
         Self {
             transform: NodeTransform::identity(),
             kind,
+            hidden: false,
         }
     }
 }
 
-type NodeStorage = Arena<(Node, Vec<NodeId>)>;
+// Each node is stored with its children and the node it was pushed onto
+type NodeStorage = Arena<(Node, Vec<NodeId>, Option<NodeId>)>;
 
 pub struct NodeRef<'a> {
     nodes: &'a NodeStorage,
@@ -34,7 +45,7 @@ pub struct NodeRef<'a> {
 }
 
 impl<'a> NodeRef<'a> {
-    fn node(&self) -> &(Node, Vec<NodeId>) {
+    fn node(&self) -> &(Node, Vec<NodeId>, Option<NodeId>) {
         &self.nodes[self.id]
     }
     pub fn node_id(&self) -> NodeId {
@@ -43,8 +54,14 @@ impl<'a> NodeRef<'a> {
     pub fn transform(&self) -> &NodeTransform {
         &self.node().0.transform
     }
+    pub fn kind(&self) -> Option<&Kind> {
+        self.node().0.kind.as_ref()
+    }
+    pub fn is_hidden(&self) -> bool {
+        self.node().0.hidden
+    }
 
-    pub fn children(&self) -> Vec<NodeRef> {
+    pub fn children(&self) -> Vec<NodeRef<'_>> {
         self.node()
             .1
             .iter()
@@ -62,7 +79,7 @@ pub struct NodeMut<'a> {
 }
 
 impl<'a> NodeMut<'a> {
-    fn node(&mut self) -> &mut (Node, Vec<NodeId>) {
+    fn node(&mut self) -> &mut (Node, Vec<NodeId>, Option<NodeId>) {
         &mut self.nodes[self.id]
     }
 
@@ -78,8 +95,8 @@ impl<'a> NodeMut<'a> {
     {
         f(&mut self.node().0.transform)
     }
-    pub fn push(&mut self, node: Node) -> NodeMut {
-        let child_index = self.nodes.insert((node, vec![]));
+    pub fn push(&mut self, node: Node) -> NodeMut<'_> {
+        let child_index = self.nodes.insert((node, vec![], Some(self.id)));
 
         self.node().1.push(child_index);
 
@@ -89,16 +106,55 @@ impl<'a> NodeMut<'a> {
         }
     }
 
-    pub fn push_empty(&mut self) -> NodeMut {
+    pub fn push_empty(&mut self) -> NodeMut<'_> {
         self.push(Node::new(None))
     }
 
-    pub fn push_line(&mut self, line: Line) -> NodeMut {
+    pub fn push_line(&mut self, line: Line) -> NodeMut<'_> {
         self.push(Node::new(Some(Kind::Line(line))))
     }
 
-    pub fn push_shape(&mut self, shape: Shape) -> NodeMut {
-        self.push(Node::new(Some(Kind::Shape(shape))))
+    pub fn push_label(&mut self, label: Label) -> NodeMut<'_> {
+        self.push(Node::new(Some(Kind::Label(label))))
+    }
+
+    pub fn push_symmetry(&mut self, symmetry: Symmetry) -> NodeMut<'_> {
+        symmetry.assert_valid();
+        self.push(Node::new(Some(Kind::Symmetry(symmetry))))
+    }
+
+    pub fn push_shape(&mut self, shape: Shape) -> NodeMut<'_> {
+        self.push(Node::new(Some(Kind::Shape(
+            shape,
+            Material::default(),
+            vec![],
+        ))))
+    }
+
+    // set_hidden stops this node and its children being drawn, they keep their transforms
+    pub fn set_hidden(&mut self, hidden: bool) -> &mut Self {
+        self.node().0.hidden = hidden;
+        self
+    }
+
+    // set_material replaces the material of this node, which must be a shape
+    pub fn set_material(&mut self, material: Material) -> &mut Self {
+        match &mut self.node().0.kind {
+            Some(Kind::Shape(_, shape_material, _)) => *shape_material = material,
+            _ => panic!("only shape nodes have a material"),
+        }
+
+        self
+    }
+
+    // displace attaches a displacement to this node, which must be a shape
+    pub fn displace(&mut self, displacement: Displacement) -> &mut Self {
+        match &mut self.node().0.kind {
+            Some(Kind::Shape(_, _, displacements)) => displacements.push(displacement),
+            _ => panic!("only shape nodes can be displaced"),
+        }
+
+        self
     }
 }
 
@@ -107,10 +163,16 @@ pub struct RenderGraph {
     root: NodeId,
 }
 
+impl Default for RenderGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl RenderGraph {
     pub fn new() -> Self {
         let mut node_storage: NodeStorage = Arena::new();
-        let root_id = node_storage.insert((Node::new(None), vec![]));
+        let root_id = node_storage.insert((Node::new(None), vec![], None));
 
         RenderGraph {
             nodes: node_storage,
@@ -118,46 +180,213 @@ impl RenderGraph {
         }
     }
 
-    pub fn node(&self, id: NodeId) -> NodeRef {
+    pub fn node(&self, id: NodeId) -> NodeRef<'_> {
         NodeRef {
             nodes: &self.nodes,
             id,
         }
     }
 
-    pub fn root(&self) -> NodeRef {
+    pub fn root(&self) -> NodeRef<'_> {
         self.node(self.root)
     }
 
-    pub fn node_mut(&mut self, id: NodeId) -> NodeMut {
+    pub fn node_mut(&mut self, id: NodeId) -> NodeMut<'_> {
         NodeMut {
             nodes: &mut self.nodes,
             id,
         }
     }
 
-    pub fn root_mut(&mut self) -> NodeMut {
+    pub fn root_mut(&mut self) -> NodeMut<'_> {
         self.node_mut(self.root)
     }
 
+    // parent is the node `id` was pushed onto, the root doesn't have one
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id].2
+    }
+
+    // world_transform is the node's transform all the way from the root
+    // Under a symmetry node it's the source subtree's, not any of the copies
+    pub fn world_transform(&self, id: NodeId) -> Matrix4<f32> {
+        let mut transform = self.nodes[id].0.transform.to_homogeneous();
+        let mut node = id;
+        while let Some(parent) = self.parent(node) {
+            transform = self.nodes[parent].0.transform.to_homogeneous() * transform;
+            node = parent;
+        }
+
+        transform
+    }
+
     pub fn walk<F>(&self, mut f: F)
     where
         F: FnMut(Matrix4<f32>, &Kind),
+    {
+        self.walk_instances(|_, transform, kind| f(transform, kind))
+    }
+
+    // walk_instances is like walk, but also gives the id of the node each instance came from
+    // Nodes under a symmetry node are visited once per copy, always with the same source id
+    pub fn walk_instances<F>(&self, mut f: F)
+    where
+        F: FnMut(NodeId, Matrix4<f32>, &Kind),
     {
         let mut to_visit = vec![(Matrix4::identity(), self.root)];
 
         while let Some((previous_transform, index)) = to_visit.pop() {
-            let (node, children) = &self.nodes[index];
+            let (node, children, _) = &self.nodes[index];
+            if node.hidden {
+                continue;
+            }
 
             let transform = previous_transform * node.transform.to_homogeneous();
 
             if let Some(kind) = &node.kind {
-                f(transform, kind);
+                f(index, transform, kind);
             }
 
-            for i in children {
-                to_visit.push((transform, *i))
+            match &node.kind {
+                Some(Kind::Symmetry(symmetry)) => {
+                    for copy in symmetry.copies() {
+                        let copy_transform = transform * copy;
+
+                        for i in children {
+                            to_visit.push((copy_transform, *i))
+                        }
+                    }
+                }
+                _ => {
+                    for i in children {
+                        to_visit.push((transform, *i))
+                    }
+                }
             }
         }
     }
+
+    // pick returns the closest shape node hit by a ray, using each shape's bounding sphere
+    // Hitting a mirrored or repeated instance returns the node it was copied from
+    pub fn pick(&self, origin: Point3<f32>, direction: Vector3<f32>) -> Option<NodeId> {
+        let direction = direction.normalize();
+        let mut closest: Option<(f32, NodeId)> = None;
+
+        self.walk_instances(|id, transform, kind| {
+            let Kind::Shape(shape, ..) = kind else {
+                return;
+            };
+
+            let center = transform.transform_point(&Point3::origin());
+            let scale = (0..3)
+                .map(|i| transform.fixed_view::<3, 1>(0, i).magnitude())
+                .fold(0.0, f32::max);
+            let radius = shape.bounding_radius() * scale;
+
+            let to_origin = origin - center;
+            let b = to_origin.dot(&direction);
+            let discriminant = (b * b) - (to_origin.dot(&to_origin) - (radius * radius));
+            if discriminant < 0.0 {
+                return;
+            }
+
+            let mut t = -b - discriminant.sqrt();
+            if t < 0.0 {
+                // The ray starts inside the shape
+                t = -b + discriminant.sqrt();
+            }
+
+            if t >= 0.0 && closest.is_none_or(|(closest_t, _)| t < closest_t) {
+                closest = Some((t, id))
+            }
+        });
+
+        closest.map(|(_, id)| id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{point, vector};
+
+    use crate::graph::{Kind, RenderGraph};
+    use crate::shapes::Shape;
+    use crate::symmetry::Symmetry;
+
+    fn mirrored_graph() -> (RenderGraph, crate::NodeId) {
+        let mut graph = RenderGraph::new();
+        let mut root = graph.root_mut();
+
+        let mut mirror = root.push_symmetry(Symmetry::new_mirror(vector![1.0, 0.0, 0.0]));
+        let mut shape = mirror.push_shape(Shape::Sphere(1.0));
+        shape.with_transform(|t| t.position = point![5.0, 0.0, 0.0]);
+
+        let shape_id = shape.node_id();
+        (graph, shape_id)
+    }
+
+    #[test]
+    fn walk_emits_mirrored_shapes() {
+        let (graph, shape_id) = mirrored_graph();
+
+        let mut centers = vec![];
+        graph.walk_instances(|id, transform, kind| {
+            if let Kind::Shape(..) = kind {
+                assert_eq!(id, shape_id);
+                centers.push(transform.transform_point(&point![0.0, 0.0, 0.0]));
+            }
+        });
+
+        centers.sort_by(|a, b| a.x.total_cmp(&b.x));
+        assert_eq!(centers, vec![point![-5.0, 0.0, 0.0], point![5.0, 0.0, 0.0]]);
+    }
+
+    #[test]
+    fn world_transforms_go_through_every_parent() {
+        let (mut graph, shape_id) = mirrored_graph();
+        graph
+            .root_mut()
+            .with_transform(|t| t.position = point![0.0, 1.0, 0.0]);
+
+        let mirror_id = graph.parent(shape_id).unwrap();
+        assert_eq!(graph.parent(mirror_id), Some(graph.root().node_id()));
+        assert_eq!(graph.parent(graph.root().node_id()), None);
+        assert_eq!(
+            graph
+                .world_transform(shape_id)
+                .transform_point(&point![0.0, 0.0, 0.0]),
+            point![5.0, 1.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn pick_resolves_mirrored_instance() {
+        let (graph, shape_id) = mirrored_graph();
+
+        let picked = graph.pick(point![-5.0, 10.0, 0.0], vector![0.0, -1.0, 0.0]);
+        assert_eq!(picked, Some(shape_id));
+
+        let missed = graph.pick(point![0.0, 10.0, 0.0], vector![0.0, -1.0, 0.0]);
+        assert_eq!(missed, None);
+    }
+
+    #[test]
+    fn hidden_nodes_are_skipped() {
+        let (mut graph, shape_id) = mirrored_graph();
+        let mirror_id = graph.parent(shape_id).unwrap();
+
+        graph.node_mut(mirror_id).set_hidden(true);
+        assert!(graph.node(mirror_id).is_hidden());
+        let mut visited = 0;
+        graph.walk(|_, _| visited += 1);
+        assert_eq!(visited, 0);
+        assert_eq!(
+            graph.pick(point![5.0, 10.0, 0.0], vector![0.0, -1.0, 0.0]),
+            None
+        );
+
+        graph.node_mut(mirror_id).set_hidden(false);
+        graph.walk(|_, _| visited += 1);
+        assert_eq!(visited, 3);
+    }
 }
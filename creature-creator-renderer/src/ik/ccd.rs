@@ -0,0 +1,58 @@
+use nalgebra::Point3;
+
+use crate::ik::{Chain, MAX_ITERATIONS, TOLERANCE};
+use crate::RenderGraph;
+
+// ccd is cyclic coordinate descent, it turns each joint from the end up to point the end at the
+// target, over and over. It returns how far the end is left from the target
+pub fn ccd(graph: &mut RenderGraph, chain: &Chain, target: Point3<f32>) -> f32 {
+    for _ in 0..MAX_ITERATIONS {
+        if (chain.end_position(graph) - target).magnitude() < TOLERANCE {
+            break;
+        }
+
+        for i in (0..chain.joints.len()).rev() {
+            let end = chain.end_position(graph);
+            chain.aim(graph, i, end, target);
+        }
+    }
+
+    (chain.end_position(graph) - target).magnitude()
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::point;
+
+    use crate::ik::tests::limb;
+    use crate::ik::{ccd, TOLERANCE};
+
+    #[test]
+    fn ccd_reaches_targets_in_range() {
+        for target in [
+            point![4.0, 6.0, 2.0],
+            point![-3.0, 1.0, 0.0],
+            point![0.0, -7.0, 5.0],
+        ] {
+            let (mut graph, chain) = limb(&[3.0, 3.0, 3.0, 3.0]);
+
+            assert!(ccd(&mut graph, &chain, target) < TOLERANCE);
+            assert!((chain.end_position(&graph) - target).magnitude() < TOLERANCE);
+            // Only the joints turned, the root stays put and the bones keep their lengths
+            let positions = chain.positions(&graph);
+            assert!(positions[0].coords.magnitude() < 1e-5);
+            for pair in positions.windows(2) {
+                assert!(((pair[1] - pair[0]).magnitude() - 3.0).abs() < 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn ccd_reaches_towards_targets_out_of_range() {
+        let (mut graph, chain) = limb(&[3.0, 3.0]);
+
+        let left = ccd(&mut graph, &chain, point![10.0, 0.0, 0.0]);
+        assert!((left - 4.0).abs() < 1e-2, "{left}");
+        assert!((chain.end_position(&graph) - point![6.0, 0.0, 0.0]).magnitude() < 1e-2);
+    }
+}
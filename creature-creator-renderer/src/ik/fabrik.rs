@@ -0,0 +1,96 @@
+use nalgebra::Point3;
+
+use crate::ik::{direction, Chain, MAX_ITERATIONS, TOLERANCE};
+use crate::RenderGraph;
+
+// fabrik is forward and backward reaching IK. It drags the chain's points to the target and back
+// to the root keeping the bones' lengths, then turns the joints to match. It returns how far the
+// end is left from the target
+pub fn fabrik(graph: &mut RenderGraph, chain: &Chain, target: Point3<f32>) -> f32 {
+    let positions = chain.positions(graph);
+    let root = positions[0];
+    let lengths: Vec<f32> = positions
+        .windows(2)
+        .map(|pair| (pair[1] - pair[0]).magnitude())
+        .collect();
+
+    for _ in 0..MAX_ITERATIONS {
+        let mut points = chain.positions(graph);
+        if (points[lengths.len()] - target).magnitude() < TOLERANCE {
+            break;
+        }
+
+        // From the target back up to the root, then from the root down again
+        points[lengths.len()] = target;
+        for (i, length) in lengths.iter().enumerate().rev() {
+            points[i] = points[i + 1] + direction(points[i + 1], points[i]) * *length;
+        }
+        points[0] = root;
+        for (i, length) in lengths.iter().enumerate() {
+            points[i + 1] = points[i] + direction(points[i], points[i + 1]) * *length;
+        }
+
+        // Joints are turned from the top down, so each one starts from where its parents put it
+        for (i, point) in points.iter().enumerate().skip(1) {
+            let current = chain.positions(graph)[i];
+            chain.aim(graph, i - 1, current, *point);
+        }
+    }
+
+    (chain.end_position(graph) - target).magnitude()
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{point, vector};
+
+    use crate::ik::tests::{assert_within, limb};
+    use crate::ik::{fabrik, Limit, TOLERANCE};
+
+    #[test]
+    fn fabrik_reaches_targets_in_range() {
+        for target in [
+            point![4.0, 6.0, 2.0],
+            point![-3.0, 1.0, 0.0],
+            point![0.0, -7.0, 5.0],
+        ] {
+            let (mut graph, chain) = limb(&[3.0, 3.0, 3.0, 3.0]);
+
+            assert!(fabrik(&mut graph, &chain, target) < TOLERANCE);
+            assert!((chain.end_position(&graph) - target).magnitude() < TOLERANCE);
+            let positions = chain.positions(&graph);
+            assert!(positions[0].coords.magnitude() < 1e-5);
+            for pair in positions.windows(2) {
+                assert!(((pair[1] - pair[0]).magnitude() - 3.0).abs() < 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn fabrik_straightens_towards_targets_out_of_range() {
+        let (mut graph, chain) = limb(&[2.0, 2.0, 2.0]);
+
+        fabrik(&mut graph, &chain, point![0.0, 0.0, 20.0]);
+        for (i, position) in chain.positions(&graph).iter().enumerate() {
+            assert!((position - point![0.0, 0.0, 2.0 * i as f32]).magnitude() < 1e-2);
+        }
+    }
+
+    #[test]
+    fn fabrik_keeps_to_limits() {
+        // Every joint is a hinge around Z, so the chain can only bend in the XY plane
+        let limit = Limit::new(vector![0.0, 0.0, -60.0], vector![0.0, 0.0, 60.0]);
+        let (mut graph, chain) = limb(&[3.0, 3.0, 3.0]);
+        let chain = chain.limit(0, limit).limit(1, limit).limit(2, limit);
+
+        for target in [point![2.0, 3.0, 4.0], point![-6.0, -2.0, 0.0]] {
+            fabrik(&mut graph, &chain, target);
+            for joint in &chain.joints {
+                assert_within(limit, graph.node(*joint).transform().rotation);
+            }
+            for position in chain.positions(&graph) {
+                assert!(position.z.abs() < 1e-3, "{position} left the XY plane");
+            }
+        }
+    }
+}
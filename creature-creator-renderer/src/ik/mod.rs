@@ -0,0 +1,263 @@
+use std::f32::consts::PI;
+
+use nalgebra::{Matrix3, Matrix4, Point3, Quaternion, Unit, UnitQuaternion, Vector3};
+
+use crate::animation::{from_quaternion, to_quaternion};
+use crate::{NodeId, RenderGraph};
+
+pub use ccd::ccd;
+pub use fabrik::fabrik;
+pub use two_bone::two_bone;
+
+mod ccd;
+mod fabrik;
+mod two_bone;
+
+// Solvers stop once the end is this close to the target
+pub const TOLERANCE: f32 = 1e-3;
+// and give up after this many passes over the chain when it can't get there
+pub const MAX_ITERATIONS: usize = 64;
+
+// Limit is how far a joint can turn, in degrees around X, then Y, then Z. A hinge turns around
+// one axis, so the other two have a range of zero. The turn around Y is only ever within ±90
+// degrees, past that it's the same as turning the other way around X and Z
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Limit {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl Limit {
+    pub fn new(min: Vector3<f32>, max: Vector3<f32>) -> Self {
+        Self { min, max }
+    }
+
+    // clamp is the closest rotation, like NodeTransform's, that's within the limit. Each angle is
+    // clamped on its own and then they're put back together
+    pub fn clamp(&self, rotation: Vector3<f32>) -> Vector3<f32> {
+        let angles = euler_angles(rotation).sup(&self.min).inf(&self.max);
+        let [x, y, z] = angles.map(f32::to_radians).into();
+        from_quaternion(UnitQuaternion::from_euler_angles(x, y, z))
+    }
+}
+
+// euler_angles are a rotation like NodeTransform's as degrees around X, then Y, then Z
+fn euler_angles(rotation: Vector3<f32>) -> Vector3<f32> {
+    let (x, y, z) = to_quaternion(rotation).euler_angles();
+    Vector3::new(x, y, z).map(f32::to_degrees)
+}
+
+// Chain is a limb for the solvers to move, joints that each turn the ones below them
+pub struct Chain {
+    // From the top of the limb down, every joint is somewhere below the one before
+    pub joints: Vec<NodeId>,
+    // The node at the end of the last bone, the one that's moved to the target
+    pub end: NodeId,
+    limits: Vec<Option<Limit>>,
+}
+
+impl Chain {
+    pub fn new(joints: Vec<NodeId>, end: NodeId) -> Self {
+        assert!(!joints.is_empty(), "chains should have a joint to turn");
+
+        Self {
+            limits: vec![None; joints.len()],
+            joints,
+            end,
+        }
+    }
+
+    // limit keeps the joint at `index` in the chain within `limit`
+    pub fn limit(mut self, index: usize, limit: Limit) -> Self {
+        self.limits[index] = Some(limit);
+        self
+    }
+
+    // positions are where the joints and then the end are in the world
+    pub fn positions(&self, graph: &RenderGraph) -> Vec<Point3<f32>> {
+        self.joints
+            .iter()
+            .chain([&self.end])
+            .map(|node| origin(&graph.world_transform(*node)))
+            .collect()
+    }
+
+    pub fn end_position(&self, graph: &RenderGraph) -> Point3<f32> {
+        origin(&graph.world_transform(self.end))
+    }
+
+    // aim turns the joint at `index` so `point`, somewhere below it, lines up with `target` as
+    // seen from the joint. Limits can stop it short
+    fn aim(&self, graph: &mut RenderGraph, index: usize, point: Point3<f32>, target: Point3<f32>) {
+        let joint = self.joints[index];
+        let joint_origin = origin(&graph.world_transform(joint));
+        let (Some(from), Some(to)) = (
+            (point - joint_origin).try_normalize(f32::EPSILON),
+            (target - joint_origin).try_normalize(f32::EPSILON),
+        ) else {
+            return;
+        };
+
+        // The turn is in world space, it's moved into the space the joint's rotation is in
+        let parent = match graph.parent(joint) {
+            Some(parent) => rotation(&graph.world_transform(parent)),
+            None => UnitQuaternion::identity(),
+        };
+        let turn = parent.inverse() * turn_between(from, to) * parent;
+
+        let mut node = graph.node_mut(joint);
+        let transform = node.transform();
+        let rotation = from_quaternion(turn * to_quaternion(transform.rotation));
+        transform.rotation = match &self.limits[index] {
+            Some(limit) => limit.clamp(rotation),
+            None => rotation,
+        };
+    }
+}
+
+fn origin(transform: &Matrix4<f32>) -> Point3<f32> {
+    transform.transform_point(&Point3::origin())
+}
+
+// rotation is the transform's rotation without its scale
+fn rotation(transform: &Matrix4<f32>) -> UnitQuaternion<f32> {
+    let mut m: Matrix3<f32> = transform.fixed_view::<3, 3>(0, 0).into();
+    for mut column in m.column_iter_mut() {
+        column.normalize_mut();
+    }
+
+    UnitQuaternion::from_matrix(&m)
+}
+
+// turn_between is the smallest turn from one unit direction to another. It's built from the
+// halfway vector rather than the angle between them, which loses small turns to rounding
+fn turn_between(from: Vector3<f32>, to: Vector3<f32>) -> UnitQuaternion<f32> {
+    let w = 1.0 + from.dot(&to);
+    if w < f32::EPSILON {
+        // Facing opposite ways, any half turn around a perpendicular axis will do
+        return UnitQuaternion::from_axis_angle(&Unit::new_unchecked(perpendicular(from)), PI);
+    }
+
+    UnitQuaternion::new_normalize(Quaternion::from_parts(w, from.cross(&to)))
+}
+
+// perpendicular is some unit vector at right angles to `v`
+fn perpendicular(v: Vector3<f32>) -> Vector3<f32> {
+    v.cross(&Vector3::x())
+        .try_normalize(f32::EPSILON)
+        .unwrap_or_else(|| v.cross(&Vector3::y()).normalize())
+}
+
+// direction is the unit vector from one point towards another, or up if they're the same point
+fn direction(from: Point3<f32>, to: Point3<f32>) -> Vector3<f32> {
+    (to - from)
+        .try_normalize(f32::EPSILON)
+        .unwrap_or_else(Vector3::y)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use nalgebra::{point, vector, UnitQuaternion, Vector3};
+
+    use crate::animation::from_quaternion;
+    use crate::ik::{ccd, euler_angles, Chain, Limit};
+    use crate::skeleton::{Bone, Skeleton};
+    use crate::RenderGraph;
+
+    // limb is a straight chain of bones up the Y axis from the origin
+    pub(crate) fn limb(lengths: &[f32]) -> (RenderGraph, Chain) {
+        let mut skeleton = Skeleton::new();
+        let mut parent = None;
+        for (i, length) in lengths.iter().enumerate() {
+            parent = Some(skeleton.add_bone(parent, Bone::new(format!("bone {i}"), *length)));
+        }
+
+        let mut graph = RenderGraph::new();
+        let root_id = graph.root().node_id();
+        skeleton.build(&mut graph, root_id);
+
+        let chain = skeleton.chain(0, lengths.len() - 1);
+        (graph, chain)
+    }
+
+    // assert_within checks a rotation like NodeTransform's is inside the limit
+    pub(crate) fn assert_within(limit: Limit, rotation: Vector3<f32>) {
+        let angles = euler_angles(rotation);
+        assert!(
+            angles
+                .iter()
+                .zip(&limit.min)
+                .all(|(a, min)| a - min > -1e-2)
+                && angles
+                    .iter()
+                    .zip(&limit.max)
+                    .all(|(a, max)| max - a > -1e-2),
+            "{angles} is outside {limit:?}"
+        );
+    }
+
+    #[test]
+    fn chains_go_from_joint_to_joint() {
+        let (graph, chain) = limb(&[2.0, 3.0, 1.0]);
+
+        assert_eq!(
+            chain.positions(&graph),
+            [
+                point![0.0, 0.0, 0.0],
+                point![0.0, 2.0, 0.0],
+                point![0.0, 5.0, 0.0],
+                point![0.0, 6.0, 0.0]
+            ]
+        );
+    }
+
+    #[test]
+    fn limits_stop_joints_turning_too_far() {
+        // The second joint is a hinge that can bend up to 45 degrees around Z
+        let (mut graph, chain) = limb(&[5.0, 5.0]);
+        let limit = Limit::new(vector![0.0, 0.0, 0.0], vector![0.0, 0.0, 45.0]);
+        let chain = chain.limit(1, limit);
+
+        // Folding back on itself needs more than the hinge allows
+        ccd(&mut graph, &chain, point![0.0, 2.0, 0.0]);
+        let rotation = graph.node(chain.joints[1]).transform().rotation;
+        assert_within(limit, rotation);
+        let [_, elbow, end] = chain.positions(&graph)[..] else {
+            unreachable!()
+        };
+        assert!(((end - elbow).angle(&(elbow - point![0.0, 0.0, 0.0]))).to_degrees() <= 45.01);
+    }
+
+    #[test]
+    fn limits_clamp_each_axis_on_its_own() {
+        let limit = Limit::new(vector![-30.0, -20.0, -10.0], vector![30.0, 20.0, 10.0]);
+        let near = |a: Vector3<f32>, b: Vector3<f32>| (a - b).magnitude() < 1e-3;
+
+        // Rotations inside the limit are left alone
+        let inside = vector![20.0, -15.0, 5.0];
+        assert!(near(limit.clamp(inside), inside));
+
+        // Turning 90 degrees around Z only stops Z, it doesn't lean around X or Y
+        assert!(near(
+            euler_angles(limit.clamp(vector![0.0, 0.0, 90.0])),
+            vector![0.0, 0.0, 10.0]
+        ));
+
+        // Only the angles that are too far are changed
+        let [x, y, z] = [50.0f32, 10.0, -40.0].map(f32::to_radians);
+        let rotation = from_quaternion(UnitQuaternion::from_euler_angles(x, y, z));
+        assert!(near(
+            euler_angles(limit.clamp(rotation)),
+            vector![30.0, 10.0, -10.0]
+        ));
+
+        // Whatever the rotation, it ends up within the limit
+        for x in (-180..180).step_by(40) {
+            for y in (-90..90).step_by(30) {
+                for z in (-180..180).step_by(40) {
+                    assert_within(limit, limit.clamp(vector![x as f32, y as f32, z as f32]));
+                }
+            }
+        }
+    }
+}
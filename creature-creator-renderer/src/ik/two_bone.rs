@@ -0,0 +1,143 @@
+use nalgebra::Point3;
+
+use crate::ik::{perpendicular, Chain, TOLERANCE};
+use crate::RenderGraph;
+
+// two_bone solves a limb like an arm or a leg exactly, the elbow bending towards `pole`. Targets
+// out of reach get the limb pointing straight at them. It returns how far the end is left from
+// the target
+pub fn two_bone(
+    graph: &mut RenderGraph,
+    chain: &Chain,
+    target: Point3<f32>,
+    pole: Point3<f32>,
+) -> f32 {
+    assert_eq!(chain.joints.len(), 2, "two bone chains have two joints");
+
+    let [shoulder, elbow, end] = chain.positions(graph)[..] else {
+        unreachable!()
+    };
+    let upper = (elbow - shoulder).magnitude();
+    let lower = (end - elbow).magnitude();
+
+    let to_target = target - shoulder;
+    let Some(direction) = to_target.try_normalize(f32::EPSILON) else {
+        return (end - target).magnitude();
+    };
+    let distance = to_target
+        .magnitude()
+        .clamp((upper - lower).abs() + TOLERANCE, upper + lower);
+
+    // The elbow goes off the line to the target on the pole's side, or the side it's already on
+    let off_line = |p: Point3<f32>| {
+        let v = p - shoulder;
+        (v - direction * direction.dot(&v)).try_normalize(f32::EPSILON)
+    };
+    let bend = off_line(pole)
+        .or_else(|| off_line(elbow))
+        .unwrap_or_else(|| perpendicular(direction));
+
+    // The law of cosines gives the shoulder's angle away from the target
+    let cos = ((upper * upper + distance * distance - lower * lower) / (2.0 * upper * distance))
+        .clamp(-1.0, 1.0);
+    let sin = (1.0 - cos * cos).sqrt();
+    let new_elbow = shoulder + (direction * cos + bend * sin) * upper;
+
+    chain.aim(graph, 0, elbow, new_elbow);
+    let end = chain.end_position(graph);
+    chain.aim(graph, 1, end, target);
+
+    (chain.end_position(graph) - target).magnitude()
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{point, vector};
+
+    use crate::ik::tests::{assert_within, limb};
+    use crate::ik::{two_bone, Chain, Limit, TOLERANCE};
+    use crate::RenderGraph;
+
+    #[test]
+    fn two_bone_reaches_targets_in_range() {
+        for target in [
+            point![5.0, 12.0, 3.0],
+            point![-8.0, 0.0, 8.0],
+            point![0.0, 3.0, 0.0],
+        ] {
+            let (mut graph, chain) = limb(&[10.0, 10.0]);
+
+            assert!(two_bone(&mut graph, &chain, target, point![0.0, 0.0, 10.0]) < TOLERANCE);
+            assert!((chain.end_position(&graph) - target).magnitude() < TOLERANCE);
+        }
+    }
+
+    #[test]
+    fn elbows_bend_towards_the_pole() {
+        let target = point![0.0, 12.0, 0.0];
+
+        for pole in [point![0.0, 5.0, 10.0], point![-10.0, 5.0, 0.0]] {
+            let (mut graph, chain) = limb(&[10.0, 10.0]);
+            two_bone(&mut graph, &chain, target, pole);
+
+            // Off to the pole's side of the line from the shoulder, as far as the bones allow
+            let elbow = chain.positions(&graph)[1];
+            let side = pole - point![0.0, pole.y, 0.0];
+            assert!((elbow.y - 6.0).abs() < 1e-3);
+            assert!((elbow - point![0.0, 6.0, 0.0] - side.normalize() * 8.0).magnitude() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn two_bone_points_at_targets_out_of_range() {
+        let (mut graph, chain) = limb(&[10.0, 10.0]);
+
+        let pole = point![0.0, 0.0, 10.0];
+
+        let left = two_bone(&mut graph, &chain, point![30.0, 0.0, 0.0], pole);
+        assert!((left - 10.0).abs() < 1e-2);
+        assert!((chain.end_position(&graph) - point![20.0, 0.0, 0.0]).magnitude() < 1e-2);
+    }
+
+    #[test]
+    fn elbows_keep_to_limits() {
+        // An elbow like the app's, bending one way around Z by up to 150 degrees
+        let limit = Limit::new(vector![0.0, 0.0, 0.0], vector![0.0, 0.0, 150.0]);
+        let limited = || {
+            let (graph, chain) = limb(&[10.0, 10.0]);
+            (graph, chain.limit(1, limit))
+        };
+        let elbow =
+            |graph: &RenderGraph, chain: &Chain| graph.node(chain.joints[1]).transform().rotation;
+
+        // Bending the way the elbow goes reaches the target
+        let (mut graph, chain) = limited();
+        let target = point![8.0, 8.0, 0.0];
+        assert!(two_bone(&mut graph, &chain, target, point![10.0, 0.0, 0.0]) < TOLERANCE);
+        assert_within(limit, elbow(&graph, &chain));
+
+        // Bending the other way would need a negative angle, so the elbow stays straight
+        let (mut graph, chain) = limited();
+        assert!(two_bone(&mut graph, &chain, target, point![-10.0, 20.0, 0.0]) > 1.0);
+        assert_within(limit, elbow(&graph, &chain));
+        let [shoulder, _, end] = chain.positions(&graph)[..] else {
+            unreachable!()
+        };
+        assert!(((end - shoulder).magnitude() - 20.0).abs() < 1e-3);
+
+        // Folding up further than 150 degrees leaves the hand that far from the shoulder
+        let (mut graph, chain) = limited();
+        two_bone(
+            &mut graph,
+            &chain,
+            point![0.0, 1.0, 0.0],
+            point![10.0, 0.0, 0.0],
+        );
+        assert_within(limit, elbow(&graph, &chain));
+        let [shoulder, _, end] = chain.positions(&graph)[..] else {
+            unreachable!()
+        };
+        let folded = 20.0 * 15.0f32.to_radians().sin();
+        assert!(((end - shoulder).magnitude() - folded).abs() < 1e-2);
+    }
+}
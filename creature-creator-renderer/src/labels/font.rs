@@ -0,0 +1,228 @@
+use nalgebra::{point, Point2};
+
+// A 5x8 pixel font for printable ASCII. Each glyph is a row per byte from the top, with the
+// leftmost pixel in bit 4. Rows 0 to 6 sit on the baseline, row 7 is for descenders
+pub const GLYPH_WIDTH: usize = 5;
+pub const GLYPH_HEIGHT: usize = 8;
+const FIRST_GLYPH: u8 = b' ';
+// Characters without a glyph are drawn as this instead
+const MISSING_GLYPH: u8 = b'?';
+
+#[rustfmt::skip]
+const GLYPHS: [[u8; GLYPH_HEIGHT]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // space
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04, 0x00], // !
+    [0x0A, 0x0A, 0x0A, 0x00, 0x00, 0x00, 0x00, 0x00], // "
+    [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A, 0x00], // #
+    [0x04, 0x0F, 0x14, 0x0E, 0x05, 0x1E, 0x04, 0x00], // $
+    [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03, 0x00], // %
+    [0x0C, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0D, 0x00], // &
+    [0x04, 0x04, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00], // '
+    [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02, 0x00], // (
+    [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08, 0x00], // )
+    [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00, 0x00], // *
+    [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00, 0x00], // +
+    [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08, 0x00], // ,
+    [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00, 0x00], // -
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C, 0x00], // .
+    [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00, 0x00], // /
+    [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E, 0x00], // 0
+    [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E, 0x00], // 1
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F, 0x00], // 2
+    [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E, 0x00], // 3
+    [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02, 0x00], // 4
+    [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E, 0x00], // 5
+    [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E, 0x00], // 6
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08, 0x00], // 7
+    [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E, 0x00], // 8
+    [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C, 0x00], // 9
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00, 0x00], // :
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x04, 0x08, 0x00], // ;
+    [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02, 0x00], // <
+    [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00, 0x00], // =
+    [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08, 0x00], // >
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04, 0x00], // ?
+    [0x0E, 0x11, 0x01, 0x0D, 0x15, 0x15, 0x0E, 0x00], // @
+    [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11, 0x00], // A
+    [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E, 0x00], // B
+    [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E, 0x00], // C
+    [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C, 0x00], // D
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F, 0x00], // E
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10, 0x00], // F
+    [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F, 0x00], // G
+    [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11, 0x00], // H
+    [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E, 0x00], // I
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C, 0x00], // J
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11, 0x00], // K
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F, 0x00], // L
+    [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11, 0x00], // M
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11, 0x00], // N
+    [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E, 0x00], // O
+    [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10, 0x00], // P
+    [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D, 0x00], // Q
+    [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11, 0x00], // R
+    [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E, 0x00], // S
+    [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x00], // T
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E, 0x00], // U
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04, 0x00], // V
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A, 0x00], // W
+    [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11, 0x00], // X
+    [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04, 0x00], // Y
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F, 0x00], // Z
+    [0x0E, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0E, 0x00], // [
+    [0x00, 0x10, 0x08, 0x04, 0x02, 0x01, 0x00, 0x00], // \
+    [0x0E, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0E, 0x00], // ]
+    [0x04, 0x0A, 0x11, 0x00, 0x00, 0x00, 0x00, 0x00], // ^
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F, 0x00], // _
+    [0x08, 0x04, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00], // `
+    [0x00, 0x00, 0x0E, 0x01, 0x0F, 0x11, 0x0F, 0x00], // a
+    [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x1E, 0x00], // b
+    [0x00, 0x00, 0x0E, 0x10, 0x10, 0x11, 0x0E, 0x00], // c
+    [0x01, 0x01, 0x0D, 0x13, 0x11, 0x11, 0x0F, 0x00], // d
+    [0x00, 0x00, 0x0E, 0x11, 0x1F, 0x10, 0x0E, 0x00], // e
+    [0x06, 0x09, 0x08, 0x1C, 0x08, 0x08, 0x08, 0x00], // f
+    [0x00, 0x00, 0x0F, 0x11, 0x11, 0x0F, 0x01, 0x0E], // g
+    [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x11, 0x00], // h
+    [0x04, 0x00, 0x0C, 0x04, 0x04, 0x04, 0x0E, 0x00], // i
+    [0x02, 0x00, 0x06, 0x02, 0x02, 0x02, 0x12, 0x0C], // j
+    [0x10, 0x10, 0x12, 0x14, 0x18, 0x14, 0x12, 0x00], // k
+    [0x0C, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E, 0x00], // l
+    [0x00, 0x00, 0x1A, 0x15, 0x15, 0x11, 0x11, 0x00], // m
+    [0x00, 0x00, 0x16, 0x19, 0x11, 0x11, 0x11, 0x00], // n
+    [0x00, 0x00, 0x0E, 0x11, 0x11, 0x11, 0x0E, 0x00], // o
+    [0x00, 0x00, 0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10], // p
+    [0x00, 0x00, 0x0F, 0x11, 0x11, 0x0F, 0x01, 0x01], // q
+    [0x00, 0x00, 0x16, 0x19, 0x10, 0x10, 0x10, 0x00], // r
+    [0x00, 0x00, 0x0F, 0x10, 0x0E, 0x01, 0x1E, 0x00], // s
+    [0x08, 0x08, 0x1C, 0x08, 0x08, 0x09, 0x06, 0x00], // t
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x13, 0x0D, 0x00], // u
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x0A, 0x04, 0x00], // v
+    [0x00, 0x00, 0x11, 0x11, 0x15, 0x15, 0x0A, 0x00], // w
+    [0x00, 0x00, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x00], // x
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x0F, 0x01, 0x0E], // y
+    [0x00, 0x00, 0x1F, 0x02, 0x04, 0x08, 0x1F, 0x00], // z
+    [0x02, 0x04, 0x04, 0x08, 0x04, 0x04, 0x02, 0x00], // {
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x00], // |
+    [0x08, 0x04, 0x04, 0x02, 0x04, 0x04, 0x08, 0x00], // }
+    [0x00, 0x00, 0x08, 0x15, 0x02, 0x00, 0x00, 0x00], // ~
+];
+
+// Glyphs are packed into the atlas with a pixel of padding all round, so sampling at the edge of
+// one doesn't pick up its neighbour
+const CELL_WIDTH: usize = GLYPH_WIDTH + 2;
+const CELL_HEIGHT: usize = GLYPH_HEIGHT + 2;
+const ATLAS_COLUMNS: usize = 16;
+const ATLAS_ROWS: usize = GLYPHS.len().div_ceil(ATLAS_COLUMNS);
+const ATLAS_WIDTH: usize = ATLAS_COLUMNS * CELL_WIDTH;
+const ATLAS_HEIGHT: usize = ATLAS_ROWS * CELL_HEIGHT;
+
+// FontAtlas is the font as a single channel image, for backends to upload as a texture
+pub struct FontAtlas {
+    pub width: usize,
+    pub height: usize,
+    // Coverage from the top left, a row at a time, 255 where there's ink
+    pub pixels: Vec<u8>,
+}
+
+impl FontAtlas {
+    pub fn new() -> Self {
+        let mut pixels = vec![0; ATLAS_WIDTH * ATLAS_HEIGHT];
+
+        for (i, glyph) in GLYPHS.iter().enumerate() {
+            let (left, top) = glyph_corner(i);
+
+            for (y, row) in glyph.iter().enumerate() {
+                for x in 0..GLYPH_WIDTH {
+                    if row & (1 << (GLYPH_WIDTH - 1 - x)) != 0 {
+                        pixels[(top + y) * ATLAS_WIDTH + left + x] = 255;
+                    }
+                }
+            }
+        }
+
+        FontAtlas {
+            width: ATLAS_WIDTH,
+            height: ATLAS_HEIGHT,
+            pixels,
+        }
+    }
+}
+
+impl Default for FontAtlas {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// glyph_uv is the corners of a character's glyph in the atlas, from 0 to 1 across it starting at
+// the top left, so the first is the glyph's top left and the second its bottom right
+pub fn glyph_uv(c: char) -> (Point2<f32>, Point2<f32>) {
+    let (left, top) = glyph_corner(glyph_index(c));
+    let size = (ATLAS_WIDTH as f32, ATLAS_HEIGHT as f32);
+
+    (
+        point![left as f32 / size.0, top as f32 / size.1],
+        point![
+            (left + GLYPH_WIDTH) as f32 / size.0,
+            (top + GLYPH_HEIGHT) as f32 / size.1
+        ],
+    )
+}
+
+// glyph_corner is the top left pixel of the i'th glyph, inside its padding
+fn glyph_corner(i: usize) -> (usize, usize) {
+    (
+        (i % ATLAS_COLUMNS) * CELL_WIDTH + 1,
+        (i / ATLAS_COLUMNS) * CELL_HEIGHT + 1,
+    )
+}
+
+fn glyph_index(c: char) -> usize {
+    let index = |b: u8| (b - FIRST_GLYPH) as usize;
+
+    match u8::try_from(c) {
+        Ok(b) if (FIRST_GLYPH..FIRST_GLYPH + GLYPHS.len() as u8).contains(&b) => index(b),
+        _ => index(MISSING_GLYPH),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::labels::font::{FontAtlas, GLYPH_HEIGHT, glyph_uv, GLYPH_WIDTH};
+
+    // ink is the glyph for `c` cut out of the atlas, as rows of '#' and '.'
+    fn ink(atlas: &FontAtlas, c: char) -> Vec<String> {
+        let (min, _) = glyph_uv(c);
+        let left = (min.x * atlas.width as f32).round() as usize;
+        let top = (min.y * atlas.height as f32).round() as usize;
+
+        (top..top + GLYPH_HEIGHT)
+            .map(|y| {
+                (left..left + GLYPH_WIDTH)
+                    .map(|x| match atlas.pixels[y * atlas.width + x] {
+                        0 => '.',
+                        _ => '#',
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn glyphs_are_drawn_into_the_atlas() {
+        let atlas = FontAtlas::new();
+
+        assert_eq!(
+            ink(&atlas, 'L'),
+            ["#....", "#....", "#....", "#....", "#....", "#....", "#####", "....."]
+        );
+        assert!(ink(&atlas, ' ').iter().all(|row| row == "....."));
+    }
+
+    #[test]
+    fn missing_glyphs_are_question_marks() {
+        assert_eq!(glyph_uv('é'), glyph_uv('?'));
+        assert_eq!(glyph_uv('\n'), glyph_uv('?'));
+        assert_ne!(glyph_uv('~'), glyph_uv('?'));
+    }
+}
@@ -0,0 +1,97 @@
+use nalgebra::{Matrix4, Point3, Vector3};
+
+use crate::graph::{Kind, RenderGraph};
+use crate::lines::Line;
+
+pub use font::{FontAtlas, GLYPH_HEIGHT, glyph_uv, GLYPH_WIDTH};
+pub use quads::{GlyphQuad, label_quads};
+
+mod font;
+mod quads;
+
+// Anchor is which side of the node the text goes
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Anchor {
+    // Centered on the node
+    Center,
+    // Starting at the node and going right, centered vertically
+    Right,
+    // Ending at the node, centered vertically
+    Left,
+    // Sitting on the node, centered horizontally
+    Above,
+    // Hanging from the node, centered horizontally
+    Below,
+}
+
+// Label is text drawn facing the camera, the same size on screen however far away it is
+#[derive(Clone, Debug, PartialEq)]
+pub struct Label {
+    pub text: String,
+    // The height of a line of text in pixels
+    pub size: f32,
+    pub color: Vector3<f32>,
+    pub anchor: Anchor,
+    // Where the text goes in the node's space, with a line back to the node's origin
+    pub leader: Option<Vector3<f32>>,
+}
+
+impl Label {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            size: 16.0,
+            color: Default::default(),
+            anchor: Anchor::Center,
+            leader: None,
+        }
+    }
+
+    pub fn size(mut self, pixels: f32) -> Self {
+        self.size = pixels;
+        self
+    }
+
+    pub fn color(mut self, color: Vector3<f32>) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn anchor(mut self, anchor: Anchor) -> Self {
+        self.anchor = anchor;
+        self
+    }
+
+    pub fn leader(mut self, offset: Vector3<f32>) -> Self {
+        self.leader = Some(offset);
+        self
+    }
+
+    // origin is where the text is anchored in the node's space
+    pub fn origin(&self) -> Point3<f32> {
+        Point3::from(self.leader.unwrap_or_else(Vector3::zeros))
+    }
+
+    // leader_line is the line from the node to the text, if it has one
+    pub fn leader_line(&self) -> Option<Line> {
+        self.leader.map(|offset| {
+            Line::new_polyline(vec![Point3::origin(), Point3::from(offset)])
+                .color(self.color)
+                .pixel_thickness(1.0)
+        })
+    }
+}
+
+// labels_from_graph collects every label in the graph with its node's transform, symmetry copies
+// included
+pub fn labels_from_graph(graph: &RenderGraph) -> Vec<(Matrix4<f32>, Label)> {
+    let mut labels = vec![];
+
+    graph.walk(|transform, kind| {
+        if let Kind::Label(label) = kind {
+            labels.push((transform, label.clone()))
+        }
+    });
+
+    labels
+}
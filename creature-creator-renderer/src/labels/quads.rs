@@ -0,0 +1,206 @@
+use nalgebra::{Matrix4, Point2, Point3, Vector3};
+
+use crate::labels::font::{GLYPH_HEIGHT, glyph_uv, GLYPH_WIDTH};
+use crate::labels::{Anchor, Label};
+use crate::Viewport;
+
+// GlyphQuad is a character of a label, a rectangle in world space facing the camera
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GlyphQuad {
+    // The bottom left corner
+    pub origin: Point3<f32>,
+    // The bottom and left edges
+    pub right: Vector3<f32>,
+    pub up: Vector3<f32>,
+    // The glyph's top left and bottom right in the font atlas
+    pub uv_min: Point2<f32>,
+    pub uv_max: Point2<f32>,
+    pub color: Vector3<f32>,
+}
+
+// label_quads lays the label's text out in pixels, then sizes it for its distance from the camera
+// Labels behind the camera aren't drawn
+pub fn label_quads(
+    label: &Label,
+    quads: &mut Vec<GlyphQuad>,
+    transform: &Matrix4<f32>,
+    viewport: &Viewport,
+) {
+    let origin = transform.transform_point(&label.origin());
+    let distance = viewport.clip(origin).w;
+    if distance <= 0.0 {
+        return;
+    }
+
+    // A glyph's pixels are square, with one between characters and between lines
+    let pixel = label.size / GLYPH_HEIGHT as f32;
+    let advance = (GLYPH_WIDTH + 1) as f32 * pixel;
+    let line_height = label.size + pixel;
+
+    let lines: Vec<&str> = label.text.lines().collect();
+    let columns = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let width = (columns as f32 * advance - pixel).max(0.0);
+    let height = (lines.len() as f32 * line_height - pixel).max(0.0);
+
+    // The top left of the text, from the origin in pixels with Y up
+    let (left, top) = match label.anchor {
+        Anchor::Center => (-width / 2.0, height / 2.0),
+        Anchor::Right => (0.0, height / 2.0),
+        Anchor::Left => (-width, height / 2.0),
+        Anchor::Above => (-width / 2.0, height),
+        Anchor::Below => (-width / 2.0, 0.0),
+    };
+
+    let units_per_pixel = 1.0 / viewport.pixels_per_unit(distance);
+    let right = viewport.right() * units_per_pixel;
+    let up = viewport.up() * units_per_pixel;
+
+    for (row, line) in lines.iter().enumerate() {
+        let bottom = top - row as f32 * line_height - label.size;
+
+        for (column, c) in line.chars().enumerate() {
+            if c.is_whitespace() {
+                continue;
+            }
+
+            let x = left + column as f32 * advance;
+            let (uv_min, uv_max) = glyph_uv(c);
+            quads.push(GlyphQuad {
+                origin: origin + right * x + up * bottom,
+                right: right * GLYPH_WIDTH as f32 * pixel,
+                up: up * label.size,
+                uv_min,
+                uv_max,
+                color: label.color,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{Matrix4, Point2, Point3, vector};
+
+    use crate::labels::{Anchor, GlyphQuad, Label, label_quads};
+    use crate::lines::line_segments;
+    use crate::lines::tests::viewport;
+    use crate::Viewport;
+
+    fn quads(label: &Label, viewport: &Viewport) -> Vec<GlyphQuad> {
+        let mut quads = vec![];
+        label_quads(label, &mut quads, &Matrix4::identity(), viewport);
+
+        quads
+    }
+
+    fn screen(viewport: &Viewport, p: Point3<f32>) -> Point2<f32> {
+        viewport.screen(viewport.clip(p))
+    }
+
+    // screen_bounds is the top left and bottom right of all the quads, in pixels
+    fn screen_bounds(quads: &[GlyphQuad], viewport: &Viewport) -> (Point2<f32>, Point2<f32>) {
+        let corners: Vec<_> = quads
+            .iter()
+            .flat_map(|q| [q.origin, q.origin + q.right + q.up])
+            .map(|p| screen(viewport, p))
+            .collect();
+        let min = corners.iter().fold(corners[0], |a, b| a.inf(b));
+        let max = corners.iter().fold(corners[0], |a, b| a.sup(b));
+
+        (min, max)
+    }
+
+    #[test]
+    fn every_visible_character_gets_a_quad() {
+        let quads = quads(&Label::new("arm 1\nelbow"), &viewport(20.0));
+
+        assert_eq!(quads.len(), 9);
+        // The second line is below the first, and starts at the same place
+        assert!(quads[4].origin.y < quads[0].origin.y);
+        assert!((quads[4].origin.x - quads[0].origin.x).abs() < 1e-5);
+    }
+
+    #[test]
+    fn labels_are_the_same_size_on_screen() {
+        let label = Label::new("hip").size(24.0);
+
+        for distance in [5.0, 50.0, 500.0] {
+            let viewport = viewport(distance);
+            let (min, max) = screen_bounds(&quads(&label, &viewport), &viewport);
+
+            // Three glyphs and the two gaps between them, at three pixels per glyph pixel
+            let size = max - min;
+            assert!((size.x - 17.0 * 3.0).abs() < 0.01, "{size}");
+            assert!((size.y - 24.0).abs() < 0.01, "{size}");
+        }
+    }
+
+    #[test]
+    fn quads_face_the_camera() {
+        let viewport = viewport(20.0);
+
+        // They're parallel to the image, so square to the camera looking at the node
+        let to_camera = viewport.eye() - Point3::origin();
+        for q in quads(&Label::new("knee"), &viewport) {
+            assert!(q.right.dot(&to_camera).abs() < 1e-4);
+            assert!(q.up.dot(&to_camera).abs() < 1e-4);
+            // Not mirrored, X goes right on screen and Y goes up
+            let a = screen(&viewport, q.origin);
+            assert!(screen(&viewport, q.origin + q.right).x > a.x);
+            assert!(screen(&viewport, q.origin + q.up).y < a.y);
+        }
+    }
+
+    #[test]
+    fn anchors_put_the_text_beside_the_node() {
+        let viewport = viewport(20.0);
+        let node = screen(&viewport, Point3::origin());
+        let bounds = |anchor| {
+            let quads = quads(&Label::new("toe").anchor(anchor), &viewport);
+            screen_bounds(&quads, &viewport)
+        };
+
+        let (min, max) = bounds(Anchor::Right);
+        assert!((min.x - node.x).abs() < 0.01);
+        assert!(((min.y + max.y) / 2.0 - node.y).abs() < 0.01);
+
+        let (_, max) = bounds(Anchor::Left);
+        assert!((max.x - node.x).abs() < 0.01);
+
+        let (_, max) = bounds(Anchor::Above);
+        assert!((max.y - node.y).abs() < 0.01);
+
+        let (min, _) = bounds(Anchor::Below);
+        assert!((min.y - node.y).abs() < 0.01);
+
+        let (min, max) = bounds(Anchor::Center);
+        assert!(((min + max.coords) / 2.0 - node).magnitude() < 0.01);
+    }
+
+    #[test]
+    fn leaders_move_the_text_and_point_back_at_the_node() {
+        let viewport = viewport(20.0);
+        let label = Label::new("tip")
+            .anchor(Anchor::Right)
+            .leader(vector![3.0, 0.0, 0.0]);
+
+        let (min, _) = screen_bounds(&quads(&label, &viewport), &viewport);
+        assert!((min.x - screen(&viewport, Point3::new(3.0, 0.0, 0.0)).x).abs() < 0.01);
+
+        let leader = label.leader_line().unwrap();
+        let mut segments = vec![];
+        line_segments(&leader, &mut segments, &Matrix4::identity(), &viewport);
+        assert_eq!(segments.len(), 1);
+        assert_eq!(
+            [segments[0].start, segments[0].end],
+            [Point3::new(3.0, 0.0, 0.0), Point3::origin()]
+        );
+    }
+
+    #[test]
+    fn labels_behind_the_camera_are_skipped() {
+        let label = Label::new("heel").leader(vector![0.0, 0.0, 30.0]);
+
+        assert!(quads(&label, &viewport(20.0)).is_empty());
+    }
+}
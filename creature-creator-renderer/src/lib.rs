@@ -1,12 +1,22 @@
 // These are the only exports
-pub use camera::Camera;
+pub use camera::{Camera, Viewport};
 pub use graph::{Kind, NodeId, NodeMut, NodeRef, RenderGraph};
 pub use transform::NodeTransform;
 
+pub mod animation;
 mod camera;
+pub mod export;
 mod graph;
+pub mod ik;
+pub mod labels;
 pub mod lines;
+pub mod mesh;
+pub mod reconstruction;
 pub mod shapes;
+pub mod skeleton;
+pub mod surface;
+pub mod symmetry;
+pub mod trace;
 mod transform;
 
 pub trait Renderer {
@@ -0,0 +1,137 @@
+use nalgebra::Matrix4;
+
+use crate::graph::{Kind, RenderGraph};
+use crate::labels::Label;
+use crate::lines::segments::{line_segments, Segment};
+use crate::lines::Line;
+use crate::Viewport;
+
+// LineBatch collects a frame's segments for a backend to upload, there's no limit on how many
+// Clearing it keeps the allocation, so a renderer can reuse one from frame to frame
+#[derive(Default)]
+pub struct LineBatch {
+    segments: Vec<Segment>,
+}
+
+impl LineBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // from_graph tessellates every line in the graph and labels' leaders, symmetry copies included
+    pub fn from_graph(graph: &RenderGraph, viewport: &Viewport) -> Self {
+        let mut batch = Self::new();
+
+        graph.walk(|transform, kind| match kind {
+            Kind::Line(line) => batch.push(line, &transform, viewport),
+            Kind::Label(label) => batch.push_leader(label, &transform, viewport),
+            _ => {}
+        });
+
+        batch
+    }
+
+    pub fn push(&mut self, line: &Line, transform: &Matrix4<f32>, viewport: &Viewport) {
+        line_segments(line, &mut self.segments, transform, viewport)
+    }
+
+    // push_leader adds the line from a label back to its node, if it has one
+    pub fn push_leader(&mut self, label: &Label, transform: &Matrix4<f32>, viewport: &Viewport) {
+        if let Some(line) = label.leader_line() {
+            self.push(&line, transform, viewport)
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.segments.clear()
+    }
+
+    pub fn len(&self) -> usize {
+        self.segments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    pub fn into_segments(self) -> Vec<Segment> {
+        self.segments
+    }
+
+    // chunks splits the segments into draws of at most `max`, for backends that can only draw
+    // so many at once. Dashes are already cut into their own segments, so a chunk can end anywhere
+    pub fn chunks(&self, max: usize) -> impl Iterator<Item = &[Segment]> {
+        self.segments.chunks(max.max(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{Matrix4, vector};
+
+    use crate::lines::tests::viewport;
+    use crate::lines::{Line, LineBatch};
+    use crate::RenderGraph;
+
+    // More lines than the old fixed size line buffer could hold
+    fn dense_graph() -> RenderGraph {
+        let mut graph = RenderGraph::new();
+        let mut root = graph.root_mut();
+
+        root.push_line(Line::new_grid(100.0, 1.0));
+        for i in 0..20 {
+            let mut node = root.push_empty();
+            node.with_transform(|t| t.position.x = i as f32);
+            node.push_line(Line::new_sphere(1.0));
+        }
+
+        graph
+    }
+
+    #[test]
+    fn batches_keep_every_segment() {
+        let batch = LineBatch::from_graph(&dense_graph(), &viewport(20.0));
+
+        assert!(batch.len() > 1000, "{}", batch.len());
+        // The grid alone has a line across and along for each of its 101 steps
+        let grid = batch
+            .segments()
+            .iter()
+            .filter(|s| {
+                s.start.y == 0.0 && s.end.y == 0.0 && (s.start - s.end).magnitude() == 100.0
+            })
+            .count();
+        assert_eq!(grid, 101 * 2);
+    }
+
+    #[test]
+    fn chunks_cover_the_batch_in_order() {
+        let batch = LineBatch::from_graph(&dense_graph(), &viewport(20.0));
+
+        let chunks: Vec<_> = batch.chunks(1000).collect();
+        assert_eq!(chunks.len(), batch.len().div_ceil(1000));
+        assert!(chunks.iter().all(|c| !c.is_empty() && c.len() <= 1000));
+        assert_eq!(chunks.concat(), batch.segments());
+    }
+
+    #[test]
+    fn clearing_keeps_lines_from_the_last_frame_out() {
+        let mut batch = LineBatch::new();
+        let viewport = viewport(20.0);
+
+        batch.push(&Line::new(1.0), &Matrix4::identity(), &viewport);
+        batch.clear();
+        batch.push(
+            &Line::new(2.0).color(vector![1.0, 0.0, 0.0]),
+            &Matrix4::identity(),
+            &viewport,
+        );
+
+        assert_eq!(batch.len(), 1);
+        assert_eq!(batch.segments()[0].color, vector![1.0, 0.0, 0.0]);
+    }
+}
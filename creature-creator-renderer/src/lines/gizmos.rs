@@ -0,0 +1,331 @@
+use std::f32::consts::FRAC_PI_2;
+
+use nalgebra::{Matrix4, point, Point3, vector, Vector3};
+
+use crate::lines::Line;
+use crate::lines::segments::{push_path, shape_arrow_segments, shape_circle_segments, Segment};
+use crate::Viewport;
+
+// Gizmos are debug shapes built out of the simpler ones, every edge is its own path so dashes
+// start again at each corner
+
+// Turning the XZ circles and Y arrows onto the other axes
+fn y_to_x() -> Matrix4<f32> {
+    Matrix4::new_rotation(vector![0.0, 0.0, -FRAC_PI_2])
+}
+
+fn y_to_z() -> Matrix4<f32> {
+    Matrix4::new_rotation(vector![FRAC_PI_2, 0.0, 0.0])
+}
+
+fn push_edge(
+    line: &Line,
+    segments: &mut Vec<Segment>,
+    transform: &Matrix4<f32>,
+    viewport: &Viewport,
+    a: Point3<f32>,
+    b: Point3<f32>,
+) {
+    push_path(
+        line,
+        segments,
+        viewport,
+        &[transform.transform_point(&a), transform.transform_point(&b)],
+    )
+}
+
+pub(super) fn bounds_segments(
+    line: &Line,
+    segments: &mut Vec<Segment>,
+    transform: &Matrix4<f32>,
+    viewport: &Viewport,
+    min: Point3<f32>,
+    max: Point3<f32>,
+) {
+    // Each bit of a corner's index picks min or max on one axis, edges join corners one bit apart
+    let corner = |i: usize| {
+        Point3::from(Vector3::from_fn(|axis, _| {
+            if i & (1 << axis) == 0 {
+                min[axis]
+            } else {
+                max[axis]
+            }
+        }))
+    };
+
+    for i in 0..8 {
+        for bit in [1, 2, 4] {
+            if i & bit == 0 {
+                push_edge(
+                    line,
+                    segments,
+                    transform,
+                    viewport,
+                    corner(i),
+                    corner(i | bit),
+                );
+            }
+        }
+    }
+}
+
+pub(super) fn sphere_segments(
+    line: &Line,
+    segments: &mut Vec<Segment>,
+    transform: &Matrix4<f32>,
+    viewport: &Viewport,
+    radius: f32,
+) {
+    for axis in [Matrix4::identity(), y_to_x(), y_to_z()] {
+        shape_circle_segments(line, segments, &(transform * axis), viewport, radius);
+    }
+}
+
+pub(super) fn cone_segments(
+    line: &Line,
+    segments: &mut Vec<Segment>,
+    transform: &Matrix4<f32>,
+    viewport: &Viewport,
+    angle: f32,
+    length: f32,
+) {
+    let radius = length * angle.to_radians().tan();
+    let base = transform * Matrix4::new_translation(&vector![0.0, length, 0.0]);
+    shape_circle_segments(line, segments, &base, viewport, radius);
+
+    // Four sides, a quarter turn apart starting where the circle does
+    for side in [
+        point![0.0, length, -radius],
+        point![radius, length, 0.0],
+        point![0.0, length, radius],
+        point![-radius, length, 0.0],
+    ] {
+        push_edge(line, segments, transform, viewport, Point3::origin(), side);
+    }
+}
+
+pub(super) fn frustum_segments(
+    line: &Line,
+    segments: &mut Vec<Segment>,
+    transform: &Matrix4<f32>,
+    viewport: &Viewport,
+    fov: f32,
+    aspect_ratio: f32,
+    (near, far): (f32, f32),
+) {
+    let corners = |distance: f32| {
+        let half_height = (fov.to_radians() / 2.0).tan() * distance;
+        let half_width = half_height * aspect_ratio;
+
+        [
+            point![-half_width, -half_height, -distance],
+            point![half_width, -half_height, -distance],
+            point![half_width, half_height, -distance],
+            point![-half_width, half_height, -distance],
+        ]
+    };
+
+    for distance in [near, far] {
+        let corners = corners(distance);
+        for i in 0..4 {
+            push_edge(
+                line,
+                segments,
+                transform,
+                viewport,
+                corners[i],
+                corners[(i + 1) % 4],
+            );
+        }
+    }
+
+    // The sides go back to the camera, through the corners of the near plane
+    for corner in corners(far) {
+        push_edge(
+            line,
+            segments,
+            transform,
+            viewport,
+            Point3::origin(),
+            corner,
+        );
+    }
+}
+
+pub(super) fn axes_segments(
+    line: &Line,
+    segments: &mut Vec<Segment>,
+    transform: &Matrix4<f32>,
+    viewport: &Viewport,
+    length: f32,
+) {
+    for (axis, color) in [
+        (y_to_x(), vector![1.0, 0.0, 0.0]),
+        (Matrix4::identity(), vector![0.0, 1.0, 0.0]),
+        (y_to_z(), vector![0.0, 0.0, 1.0]),
+    ] {
+        let first = segments.len();
+        shape_arrow_segments(line, segments, &(transform * axis), viewport, length);
+
+        for segment in &mut segments[first..] {
+            segment.color = color;
+        }
+    }
+}
+
+pub(super) fn grid_segments(
+    line: &Line,
+    segments: &mut Vec<Segment>,
+    transform: &Matrix4<f32>,
+    viewport: &Viewport,
+    size: f32,
+    step: f32,
+) {
+    let half = size / 2.0;
+    // A little slack, so a size that's a multiple of step still gets the line on the far edge
+    let steps = (size / step + 1e-4).floor() as usize;
+
+    for i in 0..=steps {
+        let position = -half + step * i as f32;
+
+        push_edge(
+            line,
+            segments,
+            transform,
+            viewport,
+            point![position, 0.0, -half],
+            point![position, 0.0, half],
+        );
+        push_edge(
+            line,
+            segments,
+            transform,
+            viewport,
+            point![-half, 0.0, position],
+            point![half, 0.0, position],
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{Matrix4, point, vector};
+
+    use crate::lines::{Line, line_segments, Segment, SegmentStyle};
+    use crate::lines::tests::viewport;
+
+    fn segments(line: &Line) -> Vec<Segment> {
+        let mut segments = vec![];
+        line_segments(line, &mut segments, &Matrix4::identity(), &viewport(20.0));
+
+        segments
+    }
+
+    fn length(segment: &Segment) -> f32 {
+        (segment.start - segment.end).magnitude()
+    }
+
+    #[test]
+    fn bounds_has_twelve_edges() {
+        let segments = segments(&Line::new_box(vector![1.0, 2.0, 3.0]));
+
+        assert_eq!(segments.len(), 12);
+        let mut lengths: Vec<f32> = segments.iter().map(length).collect();
+        lengths.sort_by(f32::total_cmp);
+        assert_eq!(
+            lengths,
+            [[1.0; 4], [2.0; 4], [3.0; 4]].concat(),
+            "four edges along each axis"
+        );
+        for s in &segments {
+            assert!(s.start.x.abs() == 0.5 && s.start.y.abs() == 1.0 && s.start.z.abs() == 1.5);
+        }
+    }
+
+    #[test]
+    fn sphere_circles_go_around_each_axis() {
+        let segments = segments(&Line::new_sphere(2.0));
+
+        for s in &segments {
+            assert!((s.start.coords.magnitude() - 2.0).abs() < 1e-5);
+        }
+        // Every circle is flat on one of the axes
+        let circle = segments.len() / 3;
+        for (i, axis) in [1, 0, 2].into_iter().enumerate() {
+            for s in &segments[i * circle..(i + 1) * circle] {
+                assert!(s.start[axis].abs() < 1e-5, "{}", s.start);
+            }
+        }
+    }
+
+    #[test]
+    fn cone_opens_at_the_angle() {
+        let segments = segments(&Line::new_cone(45.0, 2.0));
+
+        // Every segment starts on the rim, the sides end at the tip
+        for s in &segments {
+            let p = s.start;
+            assert!((p.y - 2.0).abs() < 1e-5);
+            assert!((p.xz().coords.magnitude() - 2.0).abs() < 1e-4, "{p}");
+        }
+        assert_eq!(
+            segments
+                .iter()
+                .filter(|s| s.end == point![0.0, 0.0, 0.0])
+                .count(),
+            4
+        );
+    }
+
+    #[test]
+    fn frustum_sides_meet_at_the_camera() {
+        let segments = segments(&Line::new_frustum(90.0, 2.0, 1.0, 10.0));
+
+        assert_eq!(segments.len(), 12);
+        let sides: Vec<_> = segments
+            .iter()
+            .filter(|s| s.end == point![0.0, 0.0, 0.0])
+            .collect();
+        assert_eq!(sides.len(), 4);
+
+        // A 90 degree fov is as tall as it is far, and twice as wide with this aspect ratio
+        for s in sides {
+            assert_eq!(s.start.z, -10.0);
+            assert!((s.start.x.abs() - 20.0).abs() < 1e-4);
+            assert!((s.start.y.abs() - 10.0).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn axes_are_colored_by_axis() {
+        let segments = segments(&Line::new_axes(5.0).color(vector![1.0, 1.0, 1.0]));
+
+        let heads: Vec<_> = segments
+            .iter()
+            .filter(|s| s.style == SegmentStyle::ArrowHead)
+            .collect();
+        assert_eq!(heads.len(), 3);
+        for (axis, head) in heads.into_iter().enumerate() {
+            assert!((head.end[axis] - 5.0).abs() < 1e-5);
+            assert_eq!(head.color[axis], 1.0);
+            assert_eq!(head.color.sum(), 1.0);
+        }
+    }
+
+    #[test]
+    fn grid_includes_both_edges() {
+        let segments = segments(&Line::new_grid(100.0, 5.0));
+
+        assert_eq!(segments.len(), 21 * 2);
+        for s in &segments {
+            assert_eq!(s.start.y, 0.0);
+            assert_eq!(length(s), 100.0);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "grid lines should be a positive step apart")]
+    fn grids_need_a_step() {
+        Line::new_grid(100.0, 0.0);
+    }
+}
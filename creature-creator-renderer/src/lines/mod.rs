@@ -0,0 +1,250 @@
+use nalgebra::{Point3, Vector3};
+
+pub use batch::LineBatch;
+pub use segments::{
+    Dashes, dashed_line_segments, dashed_segments_from_graph, line_segments, Segment,
+    segments_from_graph, SegmentStyle,
+};
+
+mod batch;
+mod gizmos;
+mod pattern;
+mod segments;
+
+pub enum Shape {
+    // A regular line with it's origin in the middle
+    None {
+        length: f32,
+    },
+    // A line with an arrow cap, it's origin is at the non-arrow end
+    Arrow {
+        magnitude: f32,
+    },
+    // A circle with it's origin in the center
+    Circle {
+        radius: f32,
+    },
+    // Straight lines through each point in turn
+    Polyline {
+        points: Vec<Point3<f32>>,
+    },
+    // Cubic Bézier curves joined end to end, every curve after the first starts where the last
+    // one ended. The points are start, control, control, end, control, control, end...
+    Bezier {
+        points: Vec<Point3<f32>>,
+    },
+    // A smooth curve through every point, with the tangent at each one parallel to its neighbours
+    CatmullRom {
+        points: Vec<Point3<f32>>,
+    },
+    // A spring around the Y axis with it's origin in the middle, winding the same way as Circle
+    Helix {
+        radius: f32,
+        length: f32,
+        turns: f32,
+    },
+
+    // Gizmos are made of several lines, but are still a single node
+
+    // The twelve edges of an axis aligned box
+    Bounds {
+        min: Point3<f32>,
+        max: Point3<f32>,
+    },
+    // Three circles around the X, Y and Z axes, with it's origin in the center
+    Sphere {
+        radius: f32,
+    },
+    // A cone with it's tip at the origin opening up the Y axis, `angle` degrees from it
+    Cone {
+        angle: f32,
+        length: f32,
+    },
+    // What a camera at the origin looking down -Z sees, with a vertical fov in degrees
+    Frustum {
+        fov: f32,
+        aspect_ratio: f32,
+        near: f32,
+        far: f32,
+    },
+    // Arrows along X, Y and Z colored red, green and blue, the line's color isn't used
+    Axes {
+        length: f32,
+    },
+    // A square grid on the XZ plane with it's origin in the middle, a line every `step`
+    Grid {
+        size: f32,
+        step: f32,
+    },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Thickness {
+    // In world units, lines get thinner further from the camera
+    World(f32),
+    // The same width on screen however far away the line is
+    Pixels(f32),
+}
+
+pub enum Fill {
+    Solid,
+    // Dashes with gaps as long as them
+    Dashed(f32),
+    // Alternating dash and gap lengths, starting with a dash. An odd number of them is repeated
+    Pattern(Vec<f32>),
+    // Round dots as wide as the line, this far apart
+    Dotted(f32),
+}
+
+// Cap is what's drawn on the ends of a straight line
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Cap {
+    // The line stops at its end
+    Butt,
+    // The line carries on for half its thickness
+    Square,
+    // A half circle past the end
+    Round,
+    // An arrow head, with its tip at the end
+    Arrow,
+}
+
+pub struct Line {
+    pub shape: Shape,
+    pub fill: Fill,
+    pub thickness: Thickness,
+    pub color: Vector3<f32>,
+    // How far into the fill's pattern the line starts, counting it up marches the dashes along
+    pub dash_phase: f32,
+    // The caps on the start and end of Shape::None, it starts at +Y
+    pub caps: [Cap; 2],
+}
+
+impl Line {
+    fn with_shape(shape: Shape) -> Self {
+        Self {
+            shape,
+            fill: Fill::Solid,
+            thickness: Thickness::World(0.1),
+            color: Default::default(),
+            dash_phase: 0.0,
+            caps: [Cap::Butt; 2],
+        }
+    }
+
+    pub fn new(length: f32) -> Self {
+        Self::with_shape(Shape::None { length })
+    }
+
+    pub fn new_arrow(magnitude: f32) -> Self {
+        Self::with_shape(Shape::Arrow { magnitude })
+    }
+
+    pub fn new_circle(radius: f32) -> Self {
+        Self::with_shape(Shape::Circle { radius })
+    }
+
+    pub fn new_polyline(points: Vec<Point3<f32>>) -> Self {
+        Self::with_shape(Shape::Polyline { points })
+    }
+
+    pub fn new_bezier(points: Vec<Point3<f32>>) -> Self {
+        assert_eq!(
+            points.len() % 3,
+            1,
+            "bezier points are a start and then two controls and an end per curve"
+        );
+        Self::with_shape(Shape::Bezier { points })
+    }
+
+    pub fn new_catmull_rom(points: Vec<Point3<f32>>) -> Self {
+        Self::with_shape(Shape::CatmullRom { points })
+    }
+
+    pub fn new_helix(radius: f32, length: f32, turns: f32) -> Self {
+        Self::with_shape(Shape::Helix {
+            radius,
+            length,
+            turns,
+        })
+    }
+
+    pub fn new_box(size: Vector3<f32>) -> Self {
+        Self::new_bounds(Point3::from(-size / 2.0), Point3::from(size / 2.0))
+    }
+
+    pub fn new_bounds(min: Point3<f32>, max: Point3<f32>) -> Self {
+        Self::with_shape(Shape::Bounds { min, max })
+    }
+
+    pub fn new_sphere(radius: f32) -> Self {
+        Self::with_shape(Shape::Sphere { radius })
+    }
+
+    pub fn new_cone(angle: f32, length: f32) -> Self {
+        Self::with_shape(Shape::Cone { angle, length })
+    }
+
+    pub fn new_frustum(fov: f32, aspect_ratio: f32, near: f32, far: f32) -> Self {
+        Self::with_shape(Shape::Frustum {
+            fov,
+            aspect_ratio,
+            near,
+            far,
+        })
+    }
+
+    pub fn new_axes(length: f32) -> Self {
+        Self::with_shape(Shape::Axes { length })
+    }
+
+    pub fn new_grid(size: f32, step: f32) -> Self {
+        assert!(step > 0.0, "grid lines should be a positive step apart");
+        Self::with_shape(Shape::Grid { size, step })
+    }
+
+    pub fn fill(mut self, fill: Fill) -> Self {
+        self.fill = fill;
+        self
+    }
+
+    pub fn thickness(mut self, thickness: f32) -> Self {
+        self.thickness = Thickness::World(thickness);
+        self
+    }
+
+    pub fn pixel_thickness(mut self, pixels: f32) -> Self {
+        self.thickness = Thickness::Pixels(pixels);
+        self
+    }
+
+    pub fn color(mut self, color: Vector3<f32>) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn dash_phase(mut self, phase: f32) -> Self {
+        self.dash_phase = phase;
+        self
+    }
+
+    pub fn caps(mut self, start: Cap, end: Cap) -> Self {
+        self.caps = [start, end];
+        self
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use nalgebra::point;
+
+    use crate::{Camera, Viewport};
+
+    // A camera `distance` away from the origin, looking at it
+    pub(crate) fn viewport(distance: f32) -> Viewport {
+        Viewport::new(
+            &Camera::new(point![0.0, 0.0, distance], point![0.0, 0.0, 0.0], 60.0),
+            (800, 600),
+        )
+    }
+}
@@ -0,0 +1,171 @@
+use std::sync::Arc;
+
+use crate::lines::Fill;
+
+// Pattern is what a fill draws along a line, in world units from the start of the line
+pub(super) enum Pattern {
+    Solid,
+    // Alternating dash and gap lengths, starting with a dash
+    Dashes(Arc<[f32]>),
+    // The distance between the centers of the dots
+    Dots(f32),
+}
+
+impl Pattern {
+    pub(super) fn new(fill: &Fill) -> Self {
+        match fill {
+            Fill::Solid => Pattern::Solid,
+            Fill::Dashed(length) => Self::from_lengths(&[*length, *length]),
+            Fill::Pattern(lengths) => Self::from_lengths(lengths),
+            Fill::Dotted(spacing) if *spacing > 0.0 => Pattern::Dots(*spacing),
+            Fill::Dotted(_) => Pattern::Solid,
+        }
+    }
+
+    // An odd number of lengths is repeated, so every dash is followed by a gap, like SVG does
+    fn from_lengths(lengths: &[f32]) -> Self {
+        let mut lengths: Vec<f32> = lengths.iter().map(|l| l.max(0.0)).collect();
+        if lengths.len() % 2 == 1 {
+            lengths.extend_from_within(..);
+        }
+
+        // Without any gaps it's solid, and without any dashes there's nothing to repeat
+        let gaps = lengths.iter().skip(1).step_by(2).sum::<f32>();
+        if gaps <= 0.0 || lengths.iter().sum::<f32>() <= 0.0 {
+            return Pattern::Solid;
+        }
+
+        Pattern::Dashes(lengths.into())
+    }
+
+    // shortest is the shortest dash or gap, curves are split at least this finely so the
+    // pattern follows them. It's zero for solid lines
+    pub(super) fn shortest(&self) -> f32 {
+        match self {
+            Pattern::Solid => 0.0,
+            Pattern::Dashes(lengths) => lengths
+                .iter()
+                .copied()
+                .filter(|l| *l > 0.0)
+                .fold(f32::INFINITY, f32::min),
+            Pattern::Dots(spacing) => *spacing,
+        }
+    }
+}
+
+// dashes are the parts of the line from `from` to `to` that are drawn, `phase` is how far into
+// the pattern the line starts
+pub(super) fn dashes(lengths: &[f32], phase: f32, (from, to): (f32, f32)) -> Vec<(f32, f32)> {
+    let period: f32 = lengths.iter().sum();
+    let mut dashes = vec![];
+
+    // Find where in the pattern `from` lands
+    let mut position = (from + phase).rem_euclid(period);
+    let mut i = 0;
+    while i < lengths.len() - 1 && position >= lengths[i] {
+        position -= lengths[i];
+        i += 1;
+    }
+
+    let mut at = from;
+    while at < to {
+        let end = (at + lengths[i] - position).min(to);
+        if i % 2 == 0 && end > at {
+            dashes.push((at, end));
+        }
+
+        at = end;
+        position = 0.0;
+        i = (i + 1) % lengths.len();
+    }
+
+    dashes
+}
+
+// dots are where the dots between `from` and `to` are centered, not including `to` so paths
+// don't get a dot twice where two pieces meet
+pub(super) fn dots(spacing: f32, phase: f32, (from, to): (f32, f32)) -> Vec<f32> {
+    let first = ((from + phase) / spacing).ceil() as i64;
+
+    (first..)
+        .map(|i| i as f32 * spacing - phase)
+        .take_while(|d| *d < to)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lines::pattern::{dashes, dots, Pattern};
+    use crate::lines::Fill;
+
+    fn lengths(fill: Fill) -> Vec<f32> {
+        match Pattern::new(&fill) {
+            Pattern::Dashes(lengths) => lengths.to_vec(),
+            _ => panic!("expected dashes"),
+        }
+    }
+
+    #[test]
+    fn fills_become_patterns() {
+        assert_eq!(lengths(Fill::Dashed(0.5)), [0.5, 0.5]);
+        assert_eq!(lengths(Fill::Pattern(vec![3.0, 1.0])), [3.0, 1.0]);
+        assert_eq!(
+            lengths(Fill::Pattern(vec![3.0, 1.0, 1.0])),
+            [3.0, 1.0, 1.0, 3.0, 1.0, 1.0]
+        );
+
+        // Patterns with no gaps are solid
+        assert!(matches!(
+            Pattern::new(&Fill::Pattern(vec![1.0, 0.0])),
+            Pattern::Solid
+        ));
+        assert!(matches!(
+            Pattern::new(&Fill::Pattern(vec![])),
+            Pattern::Solid
+        ));
+        assert_eq!(Pattern::new(&Fill::Pattern(vec![3.0, 1.0])).shortest(), 1.0);
+    }
+
+    #[test]
+    fn dashes_repeat_the_pattern() {
+        let pattern = [3.0, 1.0, 1.0, 1.0];
+
+        assert_eq!(
+            dashes(&pattern, 0.0, (0.0, 13.0)),
+            [
+                (0.0, 3.0),
+                (4.0, 5.0),
+                (6.0, 9.0),
+                (10.0, 11.0),
+                (12.0, 13.0)
+            ]
+        );
+        // A piece starting part way through the pattern picks it up from there
+        assert_eq!(
+            dashes(&pattern, 0.0, (2.0, 7.0)),
+            [(2.0, 3.0), (4.0, 5.0), (6.0, 7.0)]
+        );
+        // Starting in a gap
+        assert_eq!(dashes(&pattern, 0.0, (3.5, 4.5)), [(4.0, 4.5)]);
+    }
+
+    #[test]
+    fn phase_moves_the_dashes_back_along_the_line() {
+        let pattern = [1.0, 1.0];
+
+        assert_eq!(dashes(&pattern, 0.5, (0.0, 3.0)), [(0.0, 0.5), (1.5, 2.5)]);
+        // A whole period on is the same as no phase, marching ants can keep counting up
+        assert_eq!(
+            dashes(&pattern, 2.0, (0.0, 3.0)),
+            dashes(&pattern, 0.0, (0.0, 3.0))
+        );
+        assert_eq!(dashes(&pattern, -0.5, (0.0, 2.0)), [(0.5, 1.5)]);
+    }
+
+    #[test]
+    fn dots_are_evenly_spaced() {
+        assert_eq!(dots(1.0, 0.0, (0.0, 3.0)), [0.0, 1.0, 2.0]);
+        assert_eq!(dots(1.0, 0.0, (3.0, 4.5)), [3.0, 4.0]);
+        assert_eq!(dots(1.0, 0.25, (0.0, 2.0)), [0.75, 1.75]);
+    }
+}
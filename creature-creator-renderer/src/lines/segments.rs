@@ -0,0 +1,914 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+use nalgebra::{center, Matrix4, point, Point3, vector, Vector3};
+
+use crate::graph::{Kind, RenderGraph};
+use crate::lines::gizmos::{
+    axes_segments, bounds_segments, cone_segments, frustum_segments, grid_segments, sphere_segments,
+};
+use crate::lines::pattern::{dashes, dots, Pattern};
+use crate::lines::{Cap, Line, LineBatch, Shape, Thickness};
+use crate::Viewport;
+
+// Circles and curves are split until no segment is further than this many pixels from the curve
+// it's drawn for, and until each one is no longer than a dash or gap, so dashes follow the curve
+const FLATNESS_PIXELS: f32 = 0.25;
+const MIN_CIRCLE_SEGMENTS: usize = 8;
+const MAX_CIRCLE_SEGMENTS: usize = 256;
+const MAX_SUBDIVISION_DEPTH: usize = 10;
+// Curves are split this many times before checking flatness, so an S bend with its middle on the
+// straight line between its ends isn't drawn straight
+const MIN_CURVE_PIECES: usize = 4;
+const MIN_HELIX_PIECES_PER_TURN: f32 = 8.0;
+// Arrow heads are this many times as wide as the line, and this many times longer than that
+const ARROW_HEAD_WIDTH: f32 = 4.0;
+const ARROW_HEAD_LENGTH: f32 = 1.5;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SegmentStyle {
+    Line,
+    // A triangle, as wide as the thickness at the start and coming to a point at the end
+    ArrowHead,
+    // A circle as wide as the thickness, across from the start to the end
+    Dot,
+}
+
+// Dashes is the pattern along a segment that hasn't been cut into dashes, for backends that
+// draw dashes themselves
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dashes {
+    // Alternating dash and gap lengths in world units, starting with a dash
+    pub lengths: Arc<[f32]>,
+    // How far into the pattern the segment's end is, paths run from each segment's end to its start
+    pub offset: f32,
+}
+
+// Segment is a straight piece of a line in world space, what renderers actually draw
+// Dashes are already cut out unless they were asked to be left in, see dashed_line_segments
+#[derive(Clone, Debug, PartialEq)]
+pub struct Segment {
+    pub start: Point3<f32>,
+    pub end: Point3<f32>,
+    pub color: Vector3<f32>,
+    pub thickness: f32,
+    pub style: SegmentStyle,
+    pub dashes: Option<Dashes>,
+}
+
+impl Segment {
+    fn new(
+        line: &Line,
+        start: Point3<f32>,
+        end: Point3<f32>,
+        thickness: f32,
+        style: SegmentStyle,
+    ) -> Self {
+        Segment {
+            start,
+            end,
+            color: line.color,
+            thickness,
+            style,
+            dashes: None,
+        }
+    }
+
+    // dot is a dot centered on `at`, lying along `direction`
+    fn dot(line: &Line, viewport: &Viewport, at: Point3<f32>, direction: Vector3<f32>) -> Self {
+        let thickness = thickness(line, viewport, at);
+        let radius = direction.normalize() * (thickness / 2.0);
+
+        Segment::new(line, at + radius, at - radius, thickness, SegmentStyle::Dot)
+    }
+}
+
+// segments_from_graph tessellates every line in the graph, symmetry copies included
+pub fn segments_from_graph(graph: &RenderGraph, viewport: &Viewport) -> Vec<Segment> {
+    LineBatch::from_graph(graph, viewport).into_segments()
+}
+
+// dashed_segments_from_graph is segments_from_graph with dashed lines left whole
+pub fn dashed_segments_from_graph(graph: &RenderGraph, viewport: &Viewport) -> Vec<Segment> {
+    let mut segments = vec![];
+
+    graph.walk(|transform, kind| match kind {
+        Kind::Line(line) => dashed_line_segments(line, &mut segments, &transform, viewport),
+        Kind::Label(label) => {
+            if let Some(line) = label.leader_line() {
+                dashed_line_segments(&line, &mut segments, &transform, viewport)
+            }
+        }
+        _ => {}
+    });
+
+    segments
+}
+
+// line_segments tessellates a line as it will be seen through the viewport, so pixel thicknesses
+// can be worked out and curves are only as detailed as they need to be to look smooth
+// Dashes are cut into their own segments, so every segment is solid
+pub fn line_segments(
+    line: &Line,
+    segments: &mut Vec<Segment>,
+    transform: &Matrix4<f32>,
+    viewport: &Viewport,
+) {
+    let first = segments.len();
+    dashed_line_segments(line, segments, transform, viewport);
+    cut_dashes(line, segments, viewport, first);
+}
+
+// dashed_line_segments is line_segments without cutting out the dashes, each segment of a dashed
+// line keeps the pattern instead, for backends like SVG that have dashed strokes of their own
+pub fn dashed_line_segments(
+    line: &Line,
+    segments: &mut Vec<Segment>,
+    transform: &Matrix4<f32>,
+    viewport: &Viewport,
+) {
+    match &line.shape {
+        Shape::None { length } => shape_none_segments(line, segments, transform, viewport, *length),
+        Shape::Arrow { magnitude } => {
+            shape_arrow_segments(line, segments, transform, viewport, *magnitude)
+        }
+        Shape::Circle { radius } => {
+            shape_circle_segments(line, segments, transform, viewport, *radius)
+        }
+        Shape::Polyline { points } => {
+            let points: Vec<_> = points
+                .iter()
+                .map(|p| transform.transform_point(p))
+                .collect();
+            push_path(line, segments, viewport, &points)
+        }
+        Shape::Bezier { points } => {
+            shape_bezier_segments(line, segments, transform, viewport, points)
+        }
+        Shape::CatmullRom { points } => {
+            shape_catmull_rom_segments(line, segments, transform, viewport, points)
+        }
+        Shape::Helix {
+            radius,
+            length,
+            turns,
+        } => shape_helix_segments(
+            line, segments, transform, viewport, *radius, *length, *turns,
+        ),
+        Shape::Bounds { min, max } => {
+            bounds_segments(line, segments, transform, viewport, *min, *max)
+        }
+        Shape::Sphere { radius } => sphere_segments(line, segments, transform, viewport, *radius),
+        Shape::Cone { angle, length } => {
+            cone_segments(line, segments, transform, viewport, *angle, *length)
+        }
+        Shape::Frustum {
+            fov,
+            aspect_ratio,
+            near,
+            far,
+        } => frustum_segments(
+            line,
+            segments,
+            transform,
+            viewport,
+            *fov,
+            *aspect_ratio,
+            (*near, *far),
+        ),
+        Shape::Axes { length } => axes_segments(line, segments, transform, viewport, *length),
+        Shape::Grid { size, step } => {
+            grid_segments(line, segments, transform, viewport, *size, *step)
+        }
+    }
+}
+
+// thickness is the line's thickness in world units at `at`
+fn thickness(line: &Line, viewport: &Viewport, at: Point3<f32>) -> f32 {
+    match line.thickness {
+        Thickness::World(thickness) => thickness,
+        Thickness::Pixels(pixels) => pixels / viewport.pixels_per_unit(viewport.clip(at).w),
+    }
+}
+
+// circle_segment_count is enough segments that the circle looks round where it is on screen,
+// and that no segment is longer than a dash
+fn circle_segment_count(
+    line: &Line,
+    transform: &Matrix4<f32>,
+    viewport: &Viewport,
+    radius: f32,
+) -> usize {
+    let center = transform.transform_point(&Point3::origin());
+    let scale = (0..3)
+        .map(|i| transform.fixed_view::<3, 1>(0, i).magnitude())
+        .fold(0.0, f32::max);
+    let radius = radius * scale;
+
+    // A segment cuts inside the circle the most in its middle, by radius * (1 - cos(half angle))
+    let radius_pixels = radius * viewport.pixels_per_unit(viewport.clip(center).w);
+    let half_angle = (1.0 - FLATNESS_PIXELS / radius_pixels)
+        .clamp(-1.0, 1.0)
+        .acos();
+    let for_roundness = PI / half_angle;
+
+    let dash = Pattern::new(&line.fill).shortest();
+    let for_dashes = if dash > 0.0 {
+        2.0 * PI * radius / dash
+    } else {
+        0.0
+    };
+
+    (for_roundness.max(for_dashes).ceil() as usize).clamp(MIN_CIRCLE_SEGMENTS, MAX_CIRCLE_SEGMENTS)
+}
+
+fn shape_none_segments(
+    line: &Line,
+    segments: &mut Vec<Segment>,
+    transform: &Matrix4<f32>,
+    viewport: &Viewport,
+    length: f32,
+) {
+    let start = point![0.0, length / 2.0, 0.0];
+    let end = point![0.0, -(length / 2.0), 0.0];
+
+    let (start, end) = (
+        transform.transform_point(&start),
+        transform.transform_point(&end),
+    );
+    let thickness = thickness(line, viewport, center(&start, &end));
+    let Some(direction) = (start - end).try_normalize(f32::EPSILON) else {
+        return;
+    };
+
+    // Arrow heads come out of the line, they're shrunk to fit if it's too short for both
+    let arrows = line
+        .caps
+        .iter()
+        .filter(|c| **c == Cap::Arrow)
+        .count()
+        .max(1);
+    let head_width = thickness * ARROW_HEAD_WIDTH;
+    let head_length =
+        (head_width * ARROW_HEAD_LENGTH).min((start - end).magnitude() / arrows as f32);
+
+    // Paths run from their first point, so the line's start is the last one
+    let mut path = [end, start];
+    let mut caps = vec![];
+    for (cap, i, outwards) in [(line.caps[0], 1, direction), (line.caps[1], 0, -direction)] {
+        let tip = path[i];
+
+        match cap {
+            Cap::Butt => {}
+            Cap::Square => path[i] += outwards * (thickness / 2.0),
+            Cap::Round => caps.push(Segment::dot(line, viewport, tip, outwards)),
+            Cap::Arrow => {
+                path[i] -= outwards * head_length;
+                caps.push(Segment::new(
+                    line,
+                    path[i],
+                    tip,
+                    head_width,
+                    SegmentStyle::ArrowHead,
+                ));
+            }
+        }
+    }
+
+    push_path(line, segments, viewport, &path);
+    segments.extend(caps);
+}
+
+pub(super) fn shape_arrow_segments(
+    line: &Line,
+    segments: &mut Vec<Segment>,
+    transform: &Matrix4<f32>,
+    viewport: &Viewport,
+    magnitude: f32,
+) {
+    let direction = transform
+        .transform_vector(&vector![0.0, 1.0, 0.0])
+        .normalize();
+    let origin = transform.transform_point(&point![0.0, 0.0, 0.0]);
+
+    let start = origin;
+    let end = start + (direction * magnitude);
+
+    let stem_thickness = thickness(line, viewport, origin);
+    let arrow_thickness = stem_thickness * ARROW_HEAD_WIDTH;
+    let arrow_head_length = arrow_thickness * ARROW_HEAD_LENGTH;
+
+    if magnitude <= arrow_head_length {
+        segments.push(Segment::new(
+            line,
+            start,
+            end,
+            arrow_thickness,
+            SegmentStyle::ArrowHead,
+        ));
+    } else {
+        let stem_length = magnitude - arrow_head_length;
+        let stem_end = start + (direction * stem_length);
+
+        push_path(line, segments, viewport, &[start, stem_end]);
+        segments.push(Segment::new(
+            line,
+            stem_end,
+            end,
+            arrow_thickness,
+            SegmentStyle::ArrowHead,
+        ));
+    }
+}
+
+pub(super) fn shape_circle_segments(
+    line: &Line,
+    segments: &mut Vec<Segment>,
+    transform: &Matrix4<f32>,
+    viewport: &Viewport,
+    radius: f32,
+) {
+    let count = circle_segment_count(line, transform, viewport, radius);
+    let points: Vec<_> = circle_points(count, radius)
+        .iter()
+        .map(|p| transform.transform_point(p))
+        .collect();
+
+    // The circle starts and ends on the last point, so the join is where the dashes restart
+    let loop_points: Vec<_> = points[count - 1..].iter().chain(&points).copied().collect();
+    push_path(line, segments, viewport, &loop_points)
+}
+
+fn shape_bezier_segments(
+    line: &Line,
+    segments: &mut Vec<Segment>,
+    transform: &Matrix4<f32>,
+    viewport: &Viewport,
+    points: &[Point3<f32>],
+) {
+    let mut path = vec![];
+    for curve in points.windows(4).step_by(3) {
+        let curve = [curve[0], curve[1], curve[2], curve[3]].map(|p| transform.transform_point(&p));
+        subdivide(
+            line,
+            viewport,
+            &|t| bezier(curve, t),
+            MIN_CURVE_PIECES,
+            &mut path,
+        );
+    }
+
+    push_path(line, segments, viewport, &path)
+}
+
+fn shape_catmull_rom_segments(
+    line: &Line,
+    segments: &mut Vec<Segment>,
+    transform: &Matrix4<f32>,
+    viewport: &Viewport,
+    points: &[Point3<f32>],
+) {
+    let points: Vec<_> = points
+        .iter()
+        .map(|p| transform.transform_point(p))
+        .collect();
+    let (Some(first), Some(last)) = (points.first(), points.last()) else {
+        return;
+    };
+
+    // The ends are repeated, so the curve starts and stops heading at its neighbouring point
+    let padded: Vec<_> = [*first]
+        .iter()
+        .chain(&points)
+        .chain(&[*last])
+        .copied()
+        .collect();
+
+    let mut path = vec![];
+    for curve in padded.windows(4) {
+        let curve = [curve[0], curve[1], curve[2], curve[3]];
+        subdivide(
+            line,
+            viewport,
+            &|t| catmull_rom(curve, t),
+            MIN_CURVE_PIECES,
+            &mut path,
+        );
+    }
+
+    push_path(line, segments, viewport, &path)
+}
+
+fn shape_helix_segments(
+    line: &Line,
+    segments: &mut Vec<Segment>,
+    transform: &Matrix4<f32>,
+    viewport: &Viewport,
+    radius: f32,
+    length: f32,
+    turns: f32,
+) {
+    let helix = |t: f32| {
+        let angle = 2.0 * PI * turns * t;
+        let p = point![
+            angle.sin() * radius,
+            length * (t - 0.5),
+            -angle.cos() * radius
+        ];
+
+        transform.transform_point(&p)
+    };
+    let pieces = (turns.abs() * MIN_HELIX_PIECES_PER_TURN).ceil().max(1.0) as usize;
+
+    let mut path = vec![];
+    subdivide(line, viewport, &helix, pieces, &mut path);
+
+    push_path(line, segments, viewport, &path)
+}
+
+// push_path joins the points with segments, cutting the fill's pattern out of them as it goes
+// so the dashes carry on from one piece to the next
+pub(super) fn push_path(
+    line: &Line,
+    segments: &mut Vec<Segment>,
+    viewport: &Viewport,
+    points: &[Point3<f32>],
+) {
+    let pattern = Pattern::new(&line.fill);
+    // Each segment starts at the later point, like circles do
+    let solid = |a: Point3<f32>, b: Point3<f32>| {
+        Segment::new(
+            line,
+            b,
+            a,
+            thickness(line, viewport, center(&a, &b)),
+            SegmentStyle::Line,
+        )
+    };
+
+    let mut length = 0.0;
+    for pair in points.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        let piece = (b - a).magnitude();
+        let at = |d: f32| a + (b - a) * ((d - length) / piece);
+        let range = (length, length + piece);
+
+        match &pattern {
+            Pattern::Solid => segments.push(solid(a, b)),
+            _ if piece <= 0.0 => {}
+            Pattern::Dashes(lengths) => segments.push(Segment {
+                dashes: Some(Dashes {
+                    lengths: lengths.clone(),
+                    offset: length + line.dash_phase,
+                }),
+                ..solid(a, b)
+            }),
+            Pattern::Dots(spacing) => {
+                for d in dots(*spacing, line.dash_phase, range) {
+                    segments.push(Segment::dot(line, viewport, at(d), b - a));
+                }
+            }
+        }
+
+        length += piece;
+    }
+}
+
+// cut_dashes replaces the segments from `first` on that have dashes with a segment for each dash
+fn cut_dashes(line: &Line, segments: &mut Vec<Segment>, viewport: &Viewport, first: usize) {
+    for segment in segments.split_off(first) {
+        let Some(pattern) = &segment.dashes else {
+            segments.push(segment);
+            continue;
+        };
+
+        let (a, b) = (segment.end, segment.start);
+        let piece = (b - a).magnitude();
+        let at = |d: f32| a + (b - a) * (d / piece);
+        for (from, to) in dashes(&pattern.lengths, pattern.offset, (0.0, piece)) {
+            let (from, to) = (at(from), at(to));
+            segments.push(Segment::new(
+                line,
+                to,
+                from,
+                thickness(line, viewport, center(&from, &to)),
+                SegmentStyle::Line,
+            ));
+        }
+    }
+}
+
+// subdivide adds points along the curve from t = 0 to 1 to path, more of them where it bends
+// The start is only added if path is empty, so curves can be joined end to end
+fn subdivide(
+    line: &Line,
+    viewport: &Viewport,
+    curve: &impl Fn(f32) -> Point3<f32>,
+    pieces: usize,
+    path: &mut Vec<Point3<f32>>,
+) {
+    if path.is_empty() {
+        path.push(curve(0.0));
+    }
+
+    let dash = Pattern::new(&line.fill).shortest();
+    let split = |p0: Point3<f32>, p1: Point3<f32>, middle: Point3<f32>| {
+        let off_curve = (middle - center(&p0, &p1)).magnitude();
+        let pixels = viewport.pixels_per_unit(viewport.clip(middle).w);
+        off_curve * pixels > FLATNESS_PIXELS || (dash > 0.0 && (p1 - p0).magnitude() > dash)
+    };
+
+    for i in 0..pieces {
+        let (t0, t1) = (i as f32 / pieces as f32, (i + 1) as f32 / pieces as f32);
+        subdivide_piece(curve, &split, (t0, t1), (curve(t0), curve(t1)), 0, path);
+    }
+}
+
+fn subdivide_piece(
+    curve: &impl Fn(f32) -> Point3<f32>,
+    split: &impl Fn(Point3<f32>, Point3<f32>, Point3<f32>) -> bool,
+    (t0, t1): (f32, f32),
+    (p0, p1): (Point3<f32>, Point3<f32>),
+    depth: usize,
+    path: &mut Vec<Point3<f32>>,
+) {
+    let t_middle = (t0 + t1) / 2.0;
+    let middle = curve(t_middle);
+
+    if depth < MAX_SUBDIVISION_DEPTH && split(p0, p1, middle) {
+        subdivide_piece(curve, split, (t0, t_middle), (p0, middle), depth + 1, path);
+        subdivide_piece(curve, split, (t_middle, t1), (middle, p1), depth + 1, path);
+    } else {
+        path.push(p1);
+    }
+}
+
+fn bezier([p0, p1, p2, p3]: [Point3<f32>; 4], t: f32) -> Point3<f32> {
+    let s = 1.0 - t;
+
+    Point3::from(
+        p0.coords.scale(s * s * s)
+            + p1.coords.scale(3.0 * s * s * t)
+            + p2.coords.scale(3.0 * s * t * t)
+            + p3.coords.scale(t * t * t),
+    )
+}
+
+// catmull_rom goes from p1 to p2, p0 and p3 set the tangents at either end
+fn catmull_rom([p0, p1, p2, p3]: [Point3<f32>; 4], t: f32) -> Point3<f32> {
+    let (p0, p1, p2, p3) = (p0.coords, p1.coords, p2.coords, p3.coords);
+
+    Point3::from(
+        (p1.scale(2.0)
+            + (p2 - p0).scale(t)
+            + (p0.scale(2.0) - p1.scale(5.0) + p2.scale(4.0) - p3).scale(t * t)
+            + (p3 - p0 + (p1 - p2).scale(3.0)).scale(t * t * t))
+        .scale(0.5),
+    )
+}
+
+// circle_points are evenly spaced around a circle in the XZ plane, starting at -Z and turning
+// towards +X
+fn circle_points(count: usize, radius: f32) -> Vec<Point3<f32>> {
+    let segment_theta = (2.0 * PI) / (count as f32);
+
+    (0..count)
+        .map(|i| {
+            let angle = segment_theta * (i as f32);
+
+            point![angle.sin() * radius, 0.0, -angle.cos() * radius]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use nalgebra::{center, Matrix4, point, Point3};
+
+    use crate::lines::segments::{
+        ARROW_HEAD_LENGTH, ARROW_HEAD_WIDTH, bezier, FLATNESS_PIXELS, MAX_CIRCLE_SEGMENTS,
+        MIN_CIRCLE_SEGMENTS,
+    };
+    use crate::lines::tests::viewport;
+    use crate::lines::{
+        Cap, dashed_line_segments, Fill, Line, line_segments, Segment, SegmentStyle,
+    };
+    use crate::Viewport;
+
+    fn segments(line: &Line) -> Vec<Segment> {
+        segments_from(line, &viewport(20.0))
+    }
+
+    fn segments_from(line: &Line, viewport: &Viewport) -> Vec<Segment> {
+        let mut segments = vec![];
+        line_segments(line, &mut segments, &Matrix4::identity(), viewport);
+
+        segments
+    }
+
+    // Each segment starts where the last one ended
+    fn assert_continuous(segments: &[Segment]) {
+        assert!(!segments.is_empty());
+
+        for pair in segments.windows(2) {
+            assert_eq!(pair[1].end, pair[0].start);
+        }
+    }
+
+    fn length(segment: &Segment) -> f32 {
+        (segment.start - segment.end).magnitude()
+    }
+
+    fn vertices(segments: &[Segment]) -> Vec<Point3<f32>> {
+        [segments[0].end]
+            .into_iter()
+            .chain(segments.iter().map(|s| s.start))
+            .collect()
+    }
+
+    #[test]
+    fn circles_are_closed() {
+        let segments = segments(&Line::new_circle(2.0));
+
+        assert_continuous(&segments);
+        assert_eq!(
+            segments.first().unwrap().end,
+            segments.last().unwrap().start
+        );
+        for s in &segments {
+            assert!((s.start.coords.magnitude() - 2.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn polylines_go_through_every_point() {
+        let points = vec![
+            point![0.0, 0.0, 0.0],
+            point![1.0, 0.0, 0.0],
+            point![1.0, 2.0, 0.0],
+            point![1.0, 2.0, 3.0],
+        ];
+        let segments = segments(&Line::new_polyline(points.clone()));
+
+        assert_continuous(&segments);
+        assert_eq!(vertices(&segments), points);
+    }
+
+    #[test]
+    fn bezier_stays_close_to_the_curve() {
+        let points = vec![
+            point![0.0, 0.0, 0.0],
+            point![0.0, 4.0, 0.0],
+            point![4.0, 4.0, 0.0],
+            point![4.0, 0.0, 0.0],
+            point![4.0, -4.0, 0.0],
+            point![8.0, -4.0, 2.0],
+            point![8.0, 0.0, 2.0],
+        ];
+        let viewport = viewport(20.0);
+        let segments = segments_from(&Line::new_bezier(points.clone()), &viewport);
+
+        assert_continuous(&segments);
+        let vertices = vertices(&segments);
+        for end in [points[0], points[3], points[6]] {
+            assert!(vertices.contains(&end));
+        }
+
+        // Anywhere on the curve is near one of the segments
+        let distance_to_segments = |p: Point3<f32>| {
+            segments
+                .iter()
+                .map(|s| {
+                    let along = s.end - s.start;
+                    let t = ((p - s.start).dot(&along) / along.magnitude_squared()).clamp(0.0, 1.0);
+                    (p - (s.start + along.scale(t))).magnitude()
+                })
+                .fold(f32::INFINITY, f32::min)
+        };
+        for curve in points.windows(4).step_by(3) {
+            let curve = [curve[0], curve[1], curve[2], curve[3]];
+            for i in 0..=100 {
+                let p = bezier(curve, i as f32 / 100.0);
+                let pixels = distance_to_segments(p) * viewport.pixels_per_unit(viewport.clip(p).w);
+                assert!(pixels < FLATNESS_PIXELS * 2.0, "{pixels}");
+            }
+        }
+    }
+
+    #[test]
+    fn nearer_curves_get_more_segments() {
+        let line = Line::new_bezier(vec![
+            point![0.0, 0.0, 0.0],
+            point![0.0, 4.0, 0.0],
+            point![4.0, 4.0, 0.0],
+            point![4.0, 0.0, 0.0],
+        ]);
+
+        let near = segments_from(&line, &viewport(10.0)).len();
+        let far = segments_from(&line, &viewport(1000.0)).len();
+        assert!(near > far, "{near} {far}");
+    }
+
+    #[test]
+    fn circles_get_rounder_up_close() {
+        let line = Line::new_circle(1.0);
+
+        let near = segments_from(&line, &viewport(2.0)).len();
+        let middle = segments_from(&line, &viewport(20.0)).len();
+        let far = segments_from(&line, &viewport(10000.0)).len();
+        assert!(near > middle && middle > far, "{near} {middle} {far}");
+        assert!(near <= MAX_CIRCLE_SEGMENTS);
+        assert_eq!(far, MIN_CIRCLE_SEGMENTS);
+    }
+
+    #[test]
+    fn dashed_segments_keep_their_pattern() {
+        let line = Line::new_circle(1.0)
+            .fill(Fill::Pattern(vec![0.3, 0.1]))
+            .dash_phase(0.2);
+        let mut whole = vec![];
+        dashed_line_segments(&line, &mut whole, &Matrix4::identity(), &viewport(20.0));
+
+        // The circle is in one piece, with each segment carrying on from where the last one was
+        assert_continuous(&whole);
+        let mut offset = 0.2;
+        for s in &whole {
+            let dashes = s.dashes.as_ref().unwrap();
+            assert_eq!(*dashes.lengths, [0.3, 0.1]);
+            assert!((dashes.offset - offset).abs() < 1e-4);
+            offset += length(s);
+        }
+
+        // line_segments cuts them, leaving three quarters of the circle drawn
+        let cut = segments(&line);
+        assert!(cut.iter().all(|s| s.dashes.is_none()));
+        let drawn: f32 = cut.iter().map(length).sum();
+        assert!((drawn - (offset - 0.2) * 0.75).abs() < 0.1, "{drawn} {offset}");
+    }
+
+    #[test]
+    fn dashes_follow_curves() {
+        let dashed = Line::new_circle(1.0).fill(Fill::Dashed(0.05));
+        let segments = segments_from(&dashed, &viewport(1000.0));
+
+        assert!(segments.len() > MIN_CIRCLE_SEGMENTS);
+        for s in &segments {
+            assert!(length(s) <= 0.05);
+            // The ends are on the short pieces the circle was split into
+            assert!((s.start.coords.magnitude() - 1.0).abs() < 1e-3);
+        }
+
+        let dashed = Line::new_bezier(vec![
+            point![0.0, 0.0, 0.0],
+            point![0.0, 1.0, 0.0],
+            point![1.0, 1.0, 0.0],
+            point![1.0, 0.0, 0.0],
+        ])
+        .fill(Fill::Dashed(0.05));
+        for s in segments_from(&dashed, &viewport(1000.0)) {
+            assert!(length(&s) <= 0.05);
+        }
+    }
+
+    #[test]
+    fn pixel_thickness_is_the_same_on_screen() {
+        let line = Line::new(1.0).pixel_thickness(3.0);
+
+        for distance in [5.0, 50.0, 500.0] {
+            let viewport = viewport(distance);
+            let segment = &segments_from(&line, &viewport)[0];
+
+            let pixels = segment.thickness * viewport.pixels_per_unit(distance);
+            assert!((pixels - 3.0).abs() < 1e-3, "{pixels}");
+        }
+
+        let world = &segments(&Line::new(1.0).thickness(0.2))[0];
+        assert_eq!(world.thickness, 0.2);
+    }
+
+    #[test]
+    fn catmull_rom_goes_through_every_point() {
+        let points = vec![
+            point![0.0, 0.0, 0.0],
+            point![1.0, 1.0, 0.0],
+            point![2.0, 0.0, 1.0],
+            point![3.0, 1.0, 1.0],
+        ];
+        let segments = segments(&Line::new_catmull_rom(points.clone()));
+
+        assert_continuous(&segments);
+        let vertices = vertices(&segments);
+        for p in points {
+            assert!(vertices.iter().any(|v| (v - p).magnitude() < 1e-5), "{p}");
+        }
+    }
+
+    #[test]
+    fn helix_winds_around_y() {
+        let (radius, length, turns) = (0.5, 3.0, 2.5);
+        let segments = segments(&Line::new_helix(radius, length, turns));
+
+        assert_continuous(&segments);
+        let vertices = vertices(&segments);
+        for v in &vertices {
+            assert!((v.xz().coords.magnitude() - radius).abs() < 1e-5);
+        }
+
+        let angle = 2.0 * PI * turns;
+        let (first, last) = (vertices[0], *vertices.last().unwrap());
+        assert!((first - point![0.0, -1.5, -0.5]).magnitude() < 1e-5);
+        let end = point![angle.sin() * radius, 1.5, -angle.cos() * radius];
+        assert!((last - end).magnitude() < 1e-4, "{last}");
+    }
+
+    #[test]
+    fn dashes_carry_on_round_corners() {
+        // Two sides of a square, with a dash across the corner
+        let points = vec![
+            point![0.0, 0.0, 0.0],
+            point![1.5, 0.0, 0.0],
+            point![1.5, 1.5, 0.0],
+        ];
+        let line = Line::new_polyline(points).fill(Fill::Pattern(vec![1.0, 0.5]));
+        let segments = segments(&line);
+
+        // From the start: 0 to 1, then 1.5 to 2.5 which is cut at the corner
+        let pieces: Vec<_> = segments.iter().map(|s| (s.end, s.start)).collect();
+        assert_eq!(
+            pieces,
+            [
+                (point![0.0, 0.0, 0.0], point![1.0, 0.0, 0.0]),
+                (point![1.5, 0.0, 0.0], point![1.5, 1.0, 0.0]),
+            ]
+        );
+        assert!(segments.iter().all(|s| s.style == SegmentStyle::Line));
+    }
+
+    #[test]
+    fn dash_phase_marches_the_dashes() {
+        let line = Line::new(4.0).fill(Fill::Dashed(1.0));
+        let starts = |line: &Line| -> Vec<f32> { segments(line).iter().map(|s| s.end.y).collect() };
+
+        // Shape::None runs from -Y to +Y as far as the pattern is concerned
+        assert_eq!(starts(&line), [-2.0, 0.0]);
+        assert_eq!(starts(&line.dash_phase(0.5)), [-2.0, -0.5, 1.5]);
+    }
+
+    #[test]
+    fn dots_are_as_wide_as_the_line() {
+        let line = Line::new_circle(1.0).fill(Fill::Dotted(0.5)).thickness(0.2);
+        let viewport = viewport(20.0);
+        let segments = segments_from(&line, &viewport);
+
+        assert_eq!(segments.len(), (2.0 * PI / 0.5).ceil() as usize);
+        for s in &segments {
+            assert_eq!(s.style, SegmentStyle::Dot);
+            assert!((length(s) - 0.2).abs() < 1e-5);
+
+            // They're on the circle, as near as it's drawn
+            let off_circle = (center(&s.start, &s.end).coords.magnitude() - 1.0).abs();
+            assert!(off_circle * viewport.pixels_per_unit(20.0) <= FLATNESS_PIXELS);
+        }
+    }
+
+    #[test]
+    fn caps_go_on_both_ends() {
+        let caps = |start: Cap, end: Cap| segments(&Line::new(2.0).thickness(0.1).caps(start, end));
+
+        let butt = caps(Cap::Butt, Cap::Butt);
+        assert_eq!(butt.len(), 1);
+        assert_eq!((butt[0].start.y, butt[0].end.y), (1.0, -1.0));
+
+        let square = caps(Cap::Square, Cap::Butt);
+        assert_eq!((square[0].start.y, square[0].end.y), (1.05, -1.0));
+
+        let round = caps(Cap::Butt, Cap::Round);
+        assert_eq!(round.len(), 2);
+        assert_eq!(round[1].style, SegmentStyle::Dot);
+        assert!(
+            (center(&round[1].start, &round[1].end) - point![0.0, -1.0, 0.0]).magnitude() < 1e-6
+        );
+
+        // The heads point out of the line, with their tips where the line used to end
+        let arrows = caps(Cap::Arrow, Cap::Arrow);
+        assert_eq!(arrows.len(), 3);
+        let head_length = 0.1 * ARROW_HEAD_WIDTH * ARROW_HEAD_LENGTH;
+        assert!((length(&arrows[0]) - (2.0 - 2.0 * head_length)).abs() < 1e-5);
+        for (head, tip) in arrows[1..].iter().zip([1.0, -1.0]) {
+            assert_eq!(head.style, SegmentStyle::ArrowHead);
+            assert_eq!(head.end.y, tip);
+            assert!((length(head) - head_length).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn arrow_caps_fit_short_lines() {
+        let arrows = segments(&Line::new(0.2).thickness(0.1).caps(Cap::Arrow, Cap::Arrow));
+
+        for head in &arrows[1..] {
+            assert!((length(head) - 0.1).abs() < 1e-5);
+        }
+    }
+}
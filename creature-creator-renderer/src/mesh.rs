@@ -0,0 +1,390 @@
+use std::array;
+use std::collections::HashMap;
+
+use nalgebra::{Point3, Vector3};
+
+use crate::surface::{gradient, Surface};
+
+// Isosurface extraction turns a Surface into an indexed triangle mesh
+// The grid is walked like marching cubes, but every cube is split into six tetrahedra.
+// Tetrahedra have no ambiguous cases, so the mesh is always closed and consistently wound
+
+// Cube corners are numbered with bit 0 as x, bit 1 as y and bit 2 as z
+// Every tetrahedron walks from corner 0 to corner 7 along a different order of axes,
+// this way neighbouring cubes split their shared faces the same way
+const CUBE_TETRAHEDRA: [[usize; 4]; 6] = [
+    [0, 1, 3, 7],
+    [0, 1, 5, 7],
+    [0, 2, 3, 7],
+    [0, 2, 6, 7],
+    [0, 4, 5, 7],
+    [0, 4, 6, 7],
+];
+
+pub struct Mesh {
+    pub vertices: Vec<Point3<f32>>,
+    pub normals: Vec<Vector3<f32>>,
+    pub colors: Vec<Vector3<f32>>,
+    // Triangles are wound counter-clockwise when looking at the outside of the surface
+    pub triangles: Vec<[u32; 3]>,
+}
+
+impl Mesh {
+    pub fn new() -> Self {
+        Self {
+            vertices: vec![],
+            normals: vec![],
+            colors: vec![],
+            triangles: vec![],
+        }
+    }
+}
+
+impl Default for Mesh {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// extract samples the surface on a grid with cells of `cell_size`, smaller cells give a finer mesh
+pub fn extract(surface: &Surface, cell_size: f32) -> Mesh {
+    assert!(cell_size > 0.0, "cell size must be positive");
+
+    if surface.empty() {
+        return Mesh::new();
+    }
+
+    march(surface, &Grid::new(surface, cell_size))
+}
+
+// march meshes every cube of the grid
+fn march(surface: &Surface, grid: &Grid) -> Mesh {
+    let mut mesher = Mesher {
+        surface,
+        grid,
+        edge_vertices: HashMap::new(),
+        mesh: Mesh::new(),
+    };
+
+    for z in 0..(grid.size[2] - 1) {
+        for y in 0..(grid.size[1] - 1) {
+            for x in 0..(grid.size[0] - 1) {
+                let corners: [usize; 8] =
+                    array::from_fn(|c| grid.index(x + (c & 1), y + ((c >> 1) & 1), z + (c >> 2)));
+
+                for tetrahedron in CUBE_TETRAHEDRA {
+                    mesher.tetrahedron(tetrahedron.map(|c| corners[c]))
+                }
+            }
+        }
+    }
+
+    mesher.mesh
+}
+
+struct Grid {
+    origin: Point3<f32>,
+    cell_size: f32,
+    // Number of points along each axis
+    size: [usize; 3],
+    values: Vec<f32>,
+}
+
+impl Grid {
+    fn new(surface: &Surface, cell_size: f32) -> Self {
+        let (min, max) = surface.bounds();
+
+        // Padding by a cell means the surface never touches the edge of the grid
+        let origin = min - Vector3::repeat(cell_size);
+        let extent = (max - min) + Vector3::repeat(cell_size * 2.0);
+        let size = [0, 1, 2].map(|axis| (extent[axis] / cell_size).ceil() as usize + 1);
+
+        let mut grid = Grid {
+            origin,
+            cell_size,
+            size,
+            values: Vec::with_capacity(size[0] * size[1] * size[2]),
+        };
+
+        for i in 0..(size[0] * size[1] * size[2]) {
+            grid.values.push(surface.sample(grid.position(i)));
+        }
+
+        grid
+    }
+
+    fn index(&self, x: usize, y: usize, z: usize) -> usize {
+        x + (self.size[0] * (y + (self.size[1] * z)))
+    }
+
+    fn position(&self, index: usize) -> Point3<f32> {
+        let x = index % self.size[0];
+        let y = (index / self.size[0]) % self.size[1];
+        let z = index / (self.size[0] * self.size[1]);
+
+        self.origin + Vector3::new(x as f32, y as f32, z as f32).scale(self.cell_size)
+    }
+
+    fn inside(&self, index: usize) -> bool {
+        self.values[index] < 0.0
+    }
+}
+
+struct Mesher<'a> {
+    surface: &'a Surface,
+    grid: &'a Grid,
+
+    // Vertices are shared between every triangle that touches the same grid edge
+    // Edges are keyed by their grid points, lowest index first. A grid point that's exactly on
+    // the surface is keyed as an edge from itself to itself, every edge to it shares its vertex
+    edge_vertices: HashMap<(usize, usize), u32>,
+    mesh: Mesh,
+}
+
+impl<'a> Mesher<'a> {
+    fn tetrahedron(&mut self, points: [usize; 4]) {
+        let (inside, outside): (Vec<usize>, Vec<usize>) =
+            points.iter().partition(|p| self.grid.inside(**p));
+
+        match inside.len() {
+            1 => self.corner(inside[0], [outside[0], outside[1], outside[2]], true),
+            3 => self.corner(outside[0], [inside[0], inside[1], inside[2]], false),
+            2 => self.quad([inside[0], inside[1]], [outside[0], outside[1]]),
+            _ => {}
+        }
+    }
+
+    // corner cuts a single point off the tetrahedron with one triangle
+    fn corner(&mut self, lone: usize, others: [usize; 3], lone_inside: bool) {
+        let [a, b, c, d] = [lone, others[0], others[1], others[2]].map(|i| self.grid.position(i));
+
+        // Winding is decided with the grid points, which can't be degenerate
+        let facing_away_from_lone = (c - b).cross(&(d - b)).dot(&(b - a)) > 0.0;
+
+        let mut triangle = others.map(|other| self.edge_vertex(lone, other));
+        if facing_away_from_lone != lone_inside {
+            triangle.swap(1, 2);
+        }
+
+        self.triangle(triangle)
+    }
+
+    // quad separates two inside points from two outside points with two triangles
+    fn quad(&mut self, inside: [usize; 2], outside: [usize; 2]) {
+        let [a, b] = inside.map(|i| self.grid.position(i));
+        let [c, d] = outside.map(|i| self.grid.position(i));
+
+        // The quad goes ac -> ad -> bd -> bc, this checks it faces from a and b towards c and d
+        let facing_out = (d - c).cross(&(b - a)).dot(&((c - a) + (d - b))) > 0.0;
+
+        let mut quad = [
+            self.edge_vertex(inside[0], outside[0]),
+            self.edge_vertex(inside[0], outside[1]),
+            self.edge_vertex(inside[1], outside[1]),
+            self.edge_vertex(inside[1], outside[0]),
+        ];
+        if !facing_out {
+            quad.reverse();
+        }
+
+        self.triangle([quad[0], quad[1], quad[2]]);
+        self.triangle([quad[0], quad[2], quad[3]]);
+    }
+
+    // triangle skips triangles that were squashed flat by grid points on the surface. Those have
+    // an edge each way between the same two vertices, so leaving them out keeps the mesh closed
+    fn triangle(&mut self, [a, b, c]: [u32; 3]) {
+        if a != b && b != c && c != a {
+            self.mesh.triangles.push([a, b, c])
+        }
+    }
+
+    fn edge_vertex(&mut self, from: usize, to: usize) -> u32 {
+        let (from_value, to_value) = (self.grid.values[from], self.grid.values[to]);
+        let key = if from_value == 0.0 {
+            (from, from)
+        } else if to_value == 0.0 {
+            (to, to)
+        } else {
+            (from.min(to), from.max(to))
+        };
+        if let Some(vertex) = self.edge_vertices.get(&key) {
+            return *vertex;
+        }
+
+        let (from_position, to_position) = (self.grid.position(from), self.grid.position(to));
+
+        // Linearly interpolate where the surface crosses the edge
+        let t = from_value / (from_value - to_value);
+        let position = from_position + (to_position - from_position).scale(t);
+
+        let normal = gradient(self.surface, position)
+            .try_normalize(0.0)
+            .unwrap_or_else(|| {
+                // Fall back to the direction of the edge, pointing outside
+                (to_position - from_position).normalize() * (to_value - from_value).signum()
+            });
+
+        let vertex = self.mesh.vertices.len() as u32;
+        self.mesh.vertices.push(position);
+        self.mesh.normals.push(normal);
+        self.mesh
+            .colors
+            .push(self.surface.sample_material(position).1.color);
+
+        self.edge_vertices.insert(key, vertex);
+        vertex
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use nalgebra::{Matrix4, Translation3};
+
+    use crate::mesh::{extract, march, Grid, Mesh};
+    use crate::shapes::{Material, Shape};
+    use crate::surface::Surface;
+
+    fn sphere_at(surface: &mut Surface, x: f32, radius: f32) {
+        // Surfaces take the inverse of the shape transform
+        surface.push(
+            Translation3::new(-x, 0.0, 0.0).to_homogeneous(),
+            Shape::Sphere(radius),
+            Material::default(),
+            &[],
+        );
+    }
+
+    // Every edge must be used exactly once in each direction
+    fn assert_watertight(mesh: &Mesh) {
+        let mut edges = HashMap::new();
+        for triangle in &mesh.triangles {
+            for i in 0..3 {
+                *edges
+                    .entry((triangle[i], triangle[(i + 1) % 3]))
+                    .or_insert(0) += 1;
+            }
+        }
+
+        for (&(a, b), &count) in &edges {
+            assert_eq!(count, 1, "edge {a} -> {b} is used {count} times");
+            assert_eq!(edges.get(&(b, a)), Some(&1), "edge {a} -> {b} is open");
+        }
+    }
+
+    fn euler_characteristic(mesh: &Mesh) -> i64 {
+        let edges = (mesh.triangles.len() * 3) / 2;
+
+        mesh.vertices.len() as i64 - edges as i64 + mesh.triangles.len() as i64
+    }
+
+    #[test]
+    fn sphere_is_watertight() {
+        let mut surface = Surface::new();
+        sphere_at(&mut surface, 0.0, 2.0);
+
+        let mesh = extract(&surface, 0.25);
+
+        assert!(!mesh.triangles.is_empty());
+        assert_watertight(&mesh);
+        assert_eq!(euler_characteristic(&mesh), 2);
+    }
+
+    #[test]
+    fn sphere_vertices_are_on_surface() {
+        let mut surface = Surface::new();
+        sphere_at(&mut surface, 0.0, 2.0);
+
+        let mesh = extract(&surface, 0.25);
+
+        for (vertex, normal) in mesh.vertices.iter().zip(&mesh.normals) {
+            assert!((vertex.coords.magnitude() - 2.0).abs() < 0.25);
+            assert!(normal.dot(&vertex.coords.normalize()) > 0.95);
+        }
+    }
+
+    #[test]
+    fn triangles_face_outwards() {
+        let mut surface = Surface::new();
+        sphere_at(&mut surface, 0.0, 2.0);
+
+        let mesh = extract(&surface, 0.25);
+
+        for [a, b, c] in &mesh.triangles {
+            let [a, b, c] = [*a, *b, *c].map(|i| mesh.vertices[i as usize]);
+            let face_normal = (b - a).cross(&(c - a));
+
+            assert!(face_normal.dot(&a.coords) >= 0.0);
+        }
+    }
+
+    #[test]
+    fn blend_is_watertight() {
+        let mut surface = Surface::new();
+        sphere_at(&mut surface, -1.0, 1.5);
+        sphere_at(&mut surface, 1.0, 1.5);
+
+        let mesh = extract(&surface, 0.2);
+
+        assert_watertight(&mesh);
+        // Blended together the two spheres are one closed surface
+        assert_eq!(euler_characteristic(&mesh), 2);
+    }
+
+    #[test]
+    fn separate_shapes_are_separate_meshes() {
+        let mut surface = Surface::new();
+        sphere_at(&mut surface, -4.0, 1.0);
+        sphere_at(&mut surface, 4.0, 1.0);
+
+        let mesh = extract(&surface, 0.2);
+
+        assert_watertight(&mesh);
+        assert_eq!(euler_characteristic(&mesh), 4);
+    }
+
+    #[test]
+    fn smaller_cells_give_more_triangles() {
+        let mut surface = Surface::new();
+        surface.push(
+            Matrix4::identity(),
+            Shape::Cyliner(1.0, 2.0),
+            Material::default(),
+            &[],
+        );
+
+        let coarse = extract(&surface, 0.5);
+        let fine = extract(&surface, 0.25);
+
+        assert_watertight(&coarse);
+        assert_watertight(&fine);
+        assert!(fine.triangles.len() > coarse.triangles.len());
+    }
+
+    #[test]
+    fn grid_points_on_the_surface_are_shared() {
+        let mut surface = Surface::new();
+        sphere_at(&mut surface, 0.0, 3.0);
+
+        // A sphere of radius 3 on whole cells goes exactly through grid points like (3, 0, 0)
+        // and (2, 2, 1), so the values there are zero rather than just either side of it
+        let mut grid = Grid::new(&surface, 1.0);
+        let center = -grid.origin.coords.map(f32::round);
+        for i in 0..grid.values.len() {
+            let offset = (grid.position(i) - grid.origin).map(f32::round) - center;
+            grid.values[i] = offset.magnitude_squared() - 9.0;
+        }
+        assert!(grid.values.contains(&0.0));
+
+        let mesh = march(&surface, &grid);
+
+        assert_watertight(&mesh);
+        assert_eq!(euler_characteristic(&mesh), 2);
+        for [a, b, c] in &mesh.triangles {
+            let [a, b, c] = [*a, *b, *c].map(|i| mesh.vertices[i as usize]);
+            assert!((b - a).cross(&(c - a)).magnitude() > 1e-6);
+        }
+    }
+}
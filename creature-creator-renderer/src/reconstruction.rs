@@ -0,0 +1,360 @@
+use std::collections::HashMap;
+
+use nalgebra::{point, Point2, Point3, Vector2, Vector3};
+
+use crate::export::points::Point;
+use crate::mesh::Mesh;
+
+// Surface reconstruction by local tangent plane Delaunay triangulation.
+// Every point projects its neighbours onto its tangent plane and keeps the Delaunay triangles
+// around itself, its "umbrella". Triangles that enough umbrellas agree on make up the mesh.
+// Citation:
+// M. Gopi, S. Krishnan, C.T. Silva. Surface Reconstruction based on Lower Dimensional Localized
+// Delaunay Triangulation. Computer Graphics Forum 19(3), 2000
+
+// NEIGHBOUR_RADIUS is in multiples of each point's radius, it's grown until there are enough neighbours
+const NEIGHBOUR_RADIUS: f32 = 3.0;
+const MIN_NEIGHBOURS: usize = 6;
+const MAX_NEIGHBOUR_SEARCHES: usize = 4;
+// Neighbours facing away from a point are on the other side of a thin part of the surface
+const MIN_NORMAL_AGREEMENT: f32 = 0.0;
+// Triangles need to be in the umbrellas of this many of their corners
+const MIN_VOTES: u32 = 2;
+
+// reconstruct triangulates points directly, every point becomes a vertex of the mesh
+// `within` gives the indices of the points within a radius of a position, like a KD tree would
+pub fn reconstruct<F>(points: &[Point], within: F) -> Mesh
+where
+    F: Fn(Point3<f32>, f32) -> Vec<usize>,
+{
+    let mut votes: HashMap<[u32; 3], (u32, [u32; 3])> = HashMap::new();
+    for i in 0..points.len() {
+        for triangle in umbrella(points, &within, i) {
+            let mut key = triangle;
+            key.sort();
+
+            votes.entry(key).or_insert((0, triangle)).0 += 1;
+        }
+    }
+
+    let mut mesh = Mesh::new();
+    for point in points {
+        mesh.vertices.push(point.position);
+        mesh.normals.push(point.normal);
+        mesh.colors.push(point.color);
+    }
+
+    // Hash maps aren't ordered, sorting keeps the output the same between runs
+    let mut candidates: Vec<(u32, [u32; 3])> = votes
+        .into_values()
+        .filter(|(count, _)| *count >= MIN_VOTES)
+        .collect();
+    candidates.sort_by(|(a_count, a), (b_count, b)| b_count.cmp(a_count).then(a.cmp(b)));
+
+    // Umbrellas can disagree where neighbours are nearly cocircular, so triangles the most
+    // umbrellas agree on go first and anything that would give an edge a third triangle is skipped
+    let mut edge_triangles: HashMap<(u32, u32), u32> = HashMap::new();
+    for (_, triangle) in candidates {
+        let edges = [0, 1, 2].map(|k| {
+            let (a, b) = (triangle[k], triangle[(k + 1) % 3]);
+            (a.min(b), a.max(b))
+        });
+
+        if edges
+            .iter()
+            .any(|e| edge_triangles.get(e).copied().unwrap_or(0) >= 2)
+        {
+            continue;
+        }
+
+        for edge in edges {
+            *edge_triangles.entry(edge).or_insert(0) += 1;
+        }
+        mesh.triangles.push(triangle);
+    }
+
+    fill_small_holes(&mut mesh);
+    mesh
+}
+
+// fill_small_holes adds the triangles that no umbrella had enough votes for. Where the surface
+// is shaped like a saddle neighbouring umbrellas can each pick a different triangle for the same
+// spot, leaving a hole with three edges
+fn fill_small_holes(mesh: &mut Mesh) {
+    let mut edges: HashMap<u32, Vec<u32>> = HashMap::new();
+    for triangle in &mesh.triangles {
+        for k in 0..3 {
+            edges
+                .entry(triangle[k])
+                .or_default()
+                .push(triangle[(k + 1) % 3]);
+        }
+    }
+    let has_edge = |a: u32, b: u32| edges.get(&a).is_some_and(|ends| ends.contains(&b));
+
+    // An edge without its reverse is on the edge of a hole, going around it the other way
+    let mut open: HashMap<u32, u32> = HashMap::new();
+    for (&a, ends) in &edges {
+        for &b in ends {
+            if !has_edge(b, a) {
+                open.insert(b, a);
+            }
+        }
+    }
+
+    let mut holes: Vec<[u32; 3]> = open
+        .iter()
+        .filter_map(|(&a, &b)| {
+            let c = *open.get(&b)?;
+            // Each hole is found from every corner, it's only kept from its smallest one
+            (open.get(&c) == Some(&a) && a < b && a < c).then_some([a, b, c])
+        })
+        .collect();
+    holes.sort();
+
+    mesh.triangles.extend(holes);
+}
+
+// umbrella returns the Delaunay triangles around point i, in its tangent plane
+fn umbrella<F>(points: &[Point], within: &F, i: usize) -> Vec<[u32; 3]>
+where
+    F: Fn(Point3<f32>, f32) -> Vec<usize>,
+{
+    let point = points[i];
+    let plane = TangentPlane::new(point.position, point.normal);
+
+    let mut radius = NEIGHBOUR_RADIUS * point.radius;
+    let mut neighbours = vec![];
+    for _ in 0..MAX_NEIGHBOUR_SEARCHES {
+        neighbours = within(point.position, radius)
+            .into_iter()
+            .filter(|j| *j != i && points[*j].normal.dot(&point.normal) > MIN_NORMAL_AGREEMENT)
+            .collect();
+
+        if neighbours.len() >= MIN_NEIGHBOURS {
+            break;
+        }
+        radius *= 2.0;
+    }
+
+    // The Voronoi cell of the point is found by clipping a square with every neighbour's bisector
+    // Its corners between two bisectors are the circumcenters of the Delaunay triangles
+    let mut cell: Vec<(Point2<f32>, Option<usize>)> =
+        [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+            .map(|(x, y)| (point![x * radius, y * radius], None))
+            .to_vec();
+
+    for j in neighbours {
+        let offset = plane.project(points[j].position).coords;
+
+        // Points on top of each other don't have a bisector
+        if offset.magnitude_squared() > 0.0 {
+            cell = clip(&cell, offset, j);
+        }
+    }
+
+    let mut triangles = vec![];
+    for k in 0..cell.len() {
+        let (a, b) = (cell[k].1, cell[(k + 1) % cell.len()].1);
+
+        if let (Some(a), Some(b)) = (a, b) {
+            if a != b {
+                triangles.push(orient(points, [i, a, b]));
+            }
+        }
+    }
+
+    triangles
+}
+
+// TangentPlane flattens points onto the plane through a point at right angles to its normal
+struct TangentPlane {
+    origin: Point3<f32>,
+    u: Vector3<f32>,
+    v: Vector3<f32>,
+}
+
+impl TangentPlane {
+    fn new(origin: Point3<f32>, normal: Vector3<f32>) -> Self {
+        // Any axis the normal is furthest from makes a good start for the plane's axes
+        let mut axis = Vector3::zeros();
+        axis[normal.iamin()] = 1.0;
+
+        let u = normal.cross(&axis).normalize();
+        let v = u.cross(&normal).normalize();
+        Self { origin, u, v }
+    }
+
+    fn project(&self, p: Point3<f32>) -> Point2<f32> {
+        let d = p - self.origin;
+        point![d.dot(&self.u), d.dot(&self.v)]
+    }
+}
+
+// clip keeps the part of the cell closer to the origin than `offset`
+// Each corner is stored with the neighbour whose bisector the edge leaving it lies on,
+// edges of the starting square have no neighbour
+fn clip(
+    cell: &[(Point2<f32>, Option<usize>)],
+    offset: Vector2<f32>,
+    neighbour: usize,
+) -> Vec<(Point2<f32>, Option<usize>)> {
+    let distance = |p: Point2<f32>| p.coords.dot(&offset) - (offset.magnitude_squared() / 2.0);
+
+    let mut clipped = Vec::with_capacity(cell.len() + 1);
+    for k in 0..cell.len() {
+        let (a, edge) = cell[k];
+        let (b, _) = cell[(k + 1) % cell.len()];
+        let (a_distance, b_distance) = (distance(a), distance(b));
+
+        let a_inside = a_distance <= 0.0;
+        if a_inside {
+            clipped.push((a, edge));
+        }
+
+        if a_inside != (b_distance <= 0.0) {
+            let crossing = a + (b - a).scale(a_distance / (a_distance - b_distance));
+
+            // Leaving the cell, the new edge follows the bisector until it re-enters
+            let crossing_edge = if a_inside { Some(neighbour) } else { edge };
+            clipped.push((crossing, crossing_edge));
+        }
+    }
+
+    clipped
+}
+
+// orient winds the triangle counter-clockwise when looking at the outside of the surface
+fn orient(points: &[Point], triangle: [usize; 3]) -> [u32; 3] {
+    let [a, b, c] = triangle.map(|i| points[i]);
+
+    let face_normal = (b.position - a.position).cross(&(c.position - a.position));
+    let normal = a.normal + b.normal + c.normal;
+
+    let [a, b, c] = triangle.map(|i| i as u32);
+    if face_normal.dot(&normal) < 0.0 {
+        [a, c, b]
+    } else {
+        [a, b, c]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::f32::consts::PI;
+
+    use nalgebra::{vector, Point3, Translation3, Vector3};
+
+    use crate::export::points::Point;
+    use crate::mesh::Mesh;
+    use crate::reconstruction::reconstruct;
+    use crate::shapes::{Material, Shape};
+    use crate::surface::{gradient, normal, Surface};
+
+    fn sphere_at(surface: &mut Surface, x: f32, radius: f32) {
+        // Surfaces take the inverse of the shape transform
+        surface.push(
+            Translation3::new(-x, 0.0, 0.0).to_homogeneous(),
+            Shape::Sphere(radius),
+            Material::default(),
+            &[],
+        );
+    }
+
+    // sampled spreads points evenly over an ellipsoid around the origin, then moves each one
+    // onto the surface, like the particles settle onto it. The ellipsoid should be about the
+    // shape of the surface, so the points stay evenly spread
+    fn sampled(surface: &Surface, size: Vector3<f32>, count: usize, spacing: f32) -> Vec<Point> {
+        let golden_angle = PI * (3.0 - 5.0f32.sqrt());
+
+        (0..count)
+            .map(|i| {
+                let y = 1.0 - ((2 * i + 1) as f32 / count as f32);
+                let (sin, cos) = (golden_angle * i as f32).sin_cos();
+                let r = (1.0 - (y * y)).sqrt();
+
+                let mut position = Point3::from(size.component_mul(&vector![r * cos, y, r * sin]));
+                for _ in 0..32 {
+                    let g = gradient(surface, position);
+                    position -= g * (surface.sample(position) / g.dot(&g));
+                }
+
+                Point {
+                    position,
+                    normal: normal(surface, position).expect("samples aren't on a crease"),
+                    radius: spacing,
+                    color: Vector3::zeros(),
+                }
+            })
+            .collect()
+    }
+
+    // mesh reconstructs the points, looking for neighbours one by one instead of with a KD tree
+    fn mesh(points: &[Point]) -> Mesh {
+        reconstruct(points, |origin, radius| {
+            (0..points.len())
+                .filter(|j| (points[*j].position - origin).magnitude() <= radius)
+                .collect()
+        })
+    }
+
+    // Every edge is in two triangles, once in each direction
+    fn assert_closed(mesh: &Mesh) {
+        let mut edges = HashMap::new();
+        for triangle in &mesh.triangles {
+            for i in 0..3 {
+                *edges
+                    .entry((triangle[i], triangle[(i + 1) % 3]))
+                    .or_insert(0) += 1;
+            }
+        }
+
+        for (&(a, b), &count) in &edges {
+            assert_eq!(count, 1, "edge {a} -> {b} is used {count} times");
+            assert_eq!(edges.get(&(b, a)), Some(&1), "edge {a} -> {b} is open");
+        }
+    }
+
+    // Triangles are wound counter-clockwise looking at them from the side their normals face
+    fn assert_outward(mesh: &Mesh) {
+        for triangle in &mesh.triangles {
+            let [a, b, c] = triangle.map(|i| mesh.vertices[i as usize]);
+            let face_normal = (b - a).cross(&(c - a));
+            let normal: Vector3<f32> = triangle.iter().map(|i| mesh.normals[*i as usize]).sum();
+
+            assert!(face_normal.dot(&normal) > 0.0, "{triangle:?} faces inwards");
+        }
+    }
+
+    #[test]
+    fn sampled_spheres_are_closed() {
+        let mut surface = Surface::new();
+        sphere_at(&mut surface, 0.0, 2.0);
+
+        let points = sampled(&surface, vector![2.0, 2.0, 2.0], 400, 0.35);
+        let mesh = mesh(&points);
+
+        assert_eq!(mesh.vertices.len(), points.len());
+        assert_closed(&mesh);
+        assert_outward(&mesh);
+        // A closed surface with no holes through it
+        let edges = (mesh.triangles.len() * 3) / 2;
+        assert_eq!(
+            points.len() as i64 - edges as i64 + mesh.triangles.len() as i64,
+            2
+        );
+    }
+
+    #[test]
+    fn sampled_blends_are_closed() {
+        let mut surface = Surface::new();
+        sphere_at(&mut surface, -1.0, 1.5);
+        sphere_at(&mut surface, 1.0, 1.5);
+
+        let mesh = mesh(&sampled(&surface, vector![2.5, 1.5, 1.5], 600, 0.3));
+
+        assert_closed(&mesh);
+        assert_outward(&mesh);
+    }
+}
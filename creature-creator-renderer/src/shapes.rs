@@ -6,3 +6,106 @@ pub enum Shape {
     Sphere(f32),
     Cyliner(f32, f32),
 }
+
+impl Shape {
+    // bounding_radius is the radius of a sphere around the origin that contains the shape
+    // Displacement is ignored
+    pub fn bounding_radius(&self) -> f32 {
+        match *self {
+            Shape::Ellipsoid(s) => s.max(),
+            Shape::Sphere(r) => r,
+            Shape::Cyliner(r, h) => ((r * r) + (h * h)).sqrt(),
+        }
+    }
+}
+
+// Material controls how the surface of a shape is colored
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Material {
+    pub color: Vector3<f32>,
+    // 0.0 is shiny, 1.0 is completely matte
+    pub roughness: f32,
+    // How far this material bleeds into touching shapes, 0.0 gives a hard edge
+    pub blend: f32,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            color: Vector3::new(1.0, 1.0, 1.0),
+            roughness: 1.0,
+            blend: 0.5,
+        }
+    }
+}
+
+impl Material {
+    pub fn new(color: Vector3<f32>) -> Self {
+        Self {
+            color,
+            ..Default::default()
+        }
+    }
+
+    pub fn roughness(mut self, roughness: f32) -> Self {
+        self.roughness = roughness;
+        self
+    }
+
+    pub fn blend(mut self, blend: f32) -> Self {
+        self.blend = blend;
+        self
+    }
+}
+
+#[derive(Copy, Clone)]
+pub enum Noise {
+    // Perlin style gradient noise, roughly in [-1, 1]
+    Gradient,
+    // Simplex noise, roughly in [-1, 1]
+    Simplex,
+    // Fractal brownian motion, octaves of gradient noise summed together
+    Fbm { octaves: u32 },
+    // Like fbm, but each octave is folded to make sharp creases, in [0, 1]
+    Ridged { octaves: u32 },
+    // Distance to the nearest cell point, good for scales, roughly in [0, 1]
+    Voronoi,
+}
+
+// A Displacement pushes the surface of a shape in and out using noise
+// Positive values push the surface outwards
+#[derive(Copy, Clone)]
+pub struct Displacement {
+    pub noise: Noise,
+    // How far the surface moves where the noise is 1, in the shape's local units. It doesn't
+    // depend on the shape's size, big and small shapes get the same size bumps
+    pub amplitude: f32,
+    pub frequency: f32,
+    pub seed: u32,
+}
+
+impl Displacement {
+    pub fn new(noise: Noise) -> Self {
+        Self {
+            noise,
+            amplitude: 0.1,
+            frequency: 1.0,
+            seed: 0,
+        }
+    }
+
+    pub fn amplitude(mut self, amplitude: f32) -> Self {
+        self.amplitude = amplitude;
+        self
+    }
+
+    pub fn frequency(mut self, frequency: f32) -> Self {
+        self.frequency = frequency;
+        self
+    }
+
+    pub fn seed(mut self, seed: u32) -> Self {
+        self.seed = seed;
+        self
+    }
+}
@@ -0,0 +1,430 @@
+use nalgebra::vector;
+
+use crate::animation::{Mask, Pose};
+use crate::export::Joint;
+use crate::ik::Chain;
+use crate::lines::Line;
+use crate::shapes::{Material, Shape};
+use crate::{NodeId, NodeMut, NodeTransform, RenderGraph};
+
+pub use rig::RigError;
+
+mod rig;
+
+// BoneId is a bone's index in its skeleton, parents always come before their children
+pub type BoneId = usize;
+
+// Bone is a joint and the length of limb that hangs off it along +Y
+pub struct Bone {
+    pub name: String,
+    pub parent: Option<BoneId>,
+    pub length: f32,
+    // Degrees around the bone's own Y, it turns the skin and any child bones with it
+    pub roll: f32,
+    // Where the bone sits at the tip of its parent, or in the skeleton's node for root bones
+    pub rest: NodeTransform,
+    // The rest pose moved by animation, this is what gets applied to the graph
+    pub pose: NodeTransform,
+    skins: Vec<(Shape, Material)>,
+
+    // Set once the skeleton is built into a graph
+    joint_id: Option<NodeId>,
+    tip_id: Option<NodeId>,
+}
+
+impl Bone {
+    pub fn new(name: impl Into<String>, length: f32) -> Self {
+        Self {
+            name: name.into(),
+            parent: None,
+            length,
+            roll: 0.0,
+            rest: NodeTransform::identity(),
+            pose: NodeTransform::identity(),
+            skins: vec![],
+            joint_id: None,
+            tip_id: None,
+        }
+    }
+
+    pub fn roll(mut self, degrees: f32) -> Self {
+        self.roll = degrees;
+        self
+    }
+
+    // rest sets the rest pose, the current pose starts there too
+    pub fn rest(mut self, rest: NodeTransform) -> Self {
+        self.rest = rest;
+        self.pose = rest;
+        self
+    }
+
+    // joint_id is the node that gets rotated to move the bone
+    pub fn joint_id(&self) -> Option<NodeId> {
+        self.joint_id
+    }
+
+    // tip_id is the node that child bones attach to
+    pub fn tip_id(&self) -> Option<NodeId> {
+        self.tip_id
+    }
+}
+
+// Skeleton is a hierarchy of named bones, the render graph nodes are generated from it
+#[derive(Default)]
+pub struct Skeleton {
+    bones: Vec<Bone>,
+}
+
+impl Skeleton {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // from_rig reads a skeleton from its text description, see rig.rs for the format
+    pub fn from_rig(rig: &str) -> Result<Self, RigError> {
+        rig::parse(rig)
+    }
+
+    // add_bone puts a bone under `parent`, which has to already be in the skeleton
+    pub fn add_bone(&mut self, parent: Option<BoneId>, mut bone: Bone) -> BoneId {
+        if let Some(parent) = parent {
+            assert!(parent < self.bones.len(), "parent bone should exist");
+        }
+        assert!(
+            self.find(&bone.name).is_none(),
+            "bone names should be unique"
+        );
+
+        bone.parent = parent;
+        self.bones.push(bone);
+        self.bones.len() - 1
+    }
+
+    pub fn find(&self, name: &str) -> Option<BoneId> {
+        self.bones.iter().position(|b| b.name == name)
+    }
+
+    pub fn bone(&self, id: BoneId) -> &Bone {
+        &self.bones[id]
+    }
+
+    pub fn bone_mut(&mut self, id: BoneId) -> &mut Bone {
+        &mut self.bones[id]
+    }
+
+    pub fn len(&self) -> usize {
+        self.bones.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bones.is_empty()
+    }
+
+    // iter goes through the bones in hierarchy order, every parent before its children
+    pub fn iter(&self) -> impl Iterator<Item = (BoneId, &Bone)> {
+        self.bones.iter().enumerate()
+    }
+
+    pub fn children(&self, id: BoneId) -> impl Iterator<Item = BoneId> + '_ {
+        self.iter()
+            .filter(move |(_, b)| b.parent == Some(id))
+            .map(|(i, _)| i)
+    }
+
+    // attach adds a shape to the bone's skin, it's scaled with the bone so a unit shape covers it
+    pub fn attach(&mut self, id: BoneId, shape: Shape, material: Material) -> &mut Self {
+        self.bones[id].skins.push((shape, material));
+        self
+    }
+
+    // reset_pose puts every bone back in its rest pose
+    pub fn reset_pose(&mut self) {
+        for bone in &mut self.bones {
+            bone.pose = bone.rest;
+        }
+    }
+
+    // build generates the nodes for every bone under `node_id`, with debug lines and the skins
+    pub fn build(&mut self, graph: &mut RenderGraph, node_id: NodeId) {
+        for i in 0..self.bones.len() {
+            let parent_id = match self.bones[i].parent {
+                Some(parent) => self.bones[parent].tip_id.expect("parents are built first"),
+                None => node_id,
+            };
+            self.build_bone(i, graph.node_mut(parent_id));
+        }
+    }
+
+    // build_bone adds one bone's nodes under its parent's tip
+    fn build_bone(&mut self, id: BoneId, mut parent_node: NodeMut) {
+        let bone = &mut self.bones[id];
+        let length = bone.length;
+
+        let mut joint_node = parent_node.push_empty();
+        *joint_node.transform() = bone.pose;
+        bone.joint_id = Some(joint_node.node_id());
+
+        let mut roll_node = joint_node.push_empty();
+        roll_node.with_transform(|t| t.rotation.y = bone.roll);
+
+        let mut tip_node = roll_node.push_empty();
+        tip_node.with_transform(|t| t.position.y = length);
+        bone.tip_id = Some(tip_node.node_id());
+
+        // Scaling the bone node means the skin and debug lines follow the bone's length
+        let mut bone_node = roll_node.push_empty();
+        bone_node.with_transform(|t| {
+            t.position.y = length / 2.0;
+            t.scale = vector![length / 2.0, length, length / 2.0];
+        });
+
+        // Debug lines for the bones
+        bone_node
+            .push_line(Line::new_circle(0.5).color(vector![1.0, 0.0, 0.0]))
+            .with_transform(|t| t.position.y += 0.5);
+        bone_node
+            .push_line(Line::new_circle(0.5).color(vector![0.0, 1.0, 0.0]))
+            .with_transform(|t| t.position.y -= 0.5);
+        bone_node.push_line(Line::new(1.0));
+
+        let mut skin_node = bone_node.push_empty();
+        skin_node.with_transform(|t| {
+            // Y is scaled slightly so the skin from two bones connects
+            // This scaling isn't applied on the bone node because we don't want
+            // to affect debug markers
+            t.scale.y += 0.2
+        });
+        for (shape, material) in &bone.skins {
+            skin_node.push_shape(*shape).set_material(*material);
+        }
+    }
+
+    // rest_pose is the built joints in their rest pose, for animation clips to start from
+    pub fn rest_pose(&self) -> Pose {
+        let mut pose = Pose::new();
+        for bone in &self.bones {
+            if let Some(joint_id) = bone.joint_id {
+                pose.set(joint_id, bone.rest);
+            }
+        }
+
+        pose
+    }
+
+    // mask is the joints of a bone and everything below it, for blending part of the skeleton
+    pub fn mask(&self, id: BoneId) -> Mask {
+        let mut subtree = vec![id];
+        for (i, bone) in self.iter().skip(id + 1) {
+            if bone.parent.is_some_and(|p| subtree.contains(&p)) {
+                subtree.push(i);
+            }
+        }
+
+        Mask::new(subtree.iter().filter_map(|b| self.bones[*b].joint_id))
+    }
+
+    // owner is the bone a node was built under, like a skin shape that was picked. Child bones
+    // hang off their parent's tip, so the first joint above the node is its bone's
+    pub fn owner(&self, graph: &RenderGraph, node_id: NodeId) -> Option<BoneId> {
+        let mut node_id = Some(node_id);
+        while let Some(id) = node_id {
+            if let Some(bone) = self.bones.iter().position(|b| b.joint_id == Some(id)) {
+                return Some(bone);
+            }
+            node_id = graph.parent(id);
+        }
+
+        None
+    }
+
+    // chain is the joints from `from` down to `to` for the IK solvers, ending at `to`'s tip
+    pub fn chain(&self, from: BoneId, to: BoneId) -> Chain {
+        let mut bones = vec![to];
+        while bones[bones.len() - 1] != from {
+            let parent = self.bones[bones[bones.len() - 1]].parent;
+            bones.push(parent.expect("chains should go down from `from` to `to`"));
+        }
+
+        let joint = |b: &BoneId| self.bones[*b].joint_id.expect("skeleton is built");
+        Chain::new(
+            bones.iter().rev().map(joint).collect(),
+            self.bones[to].tip_id.expect("skeleton is built"),
+        )
+    }
+
+    // apply copies the current pose onto the built joint nodes
+    pub fn apply(&self, graph: &mut RenderGraph) {
+        for bone in &self.bones {
+            if let Some(joint_id) = bone.joint_id {
+                *graph.node_mut(joint_id).transform() = bone.pose;
+            }
+        }
+    }
+
+    // joints are the built bones' joint nodes, named for exporting as a skin
+    pub fn joints(&self) -> Vec<Joint> {
+        self.bones
+            .iter()
+            .filter_map(|b| {
+                Some(Joint {
+                    name: b.name.clone(),
+                    node_id: b.joint_id?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{point, Point3, vector};
+
+    use crate::lines::Line;
+    use crate::shapes::{Material, Shape};
+    use crate::skeleton::{Bone, Skeleton};
+    use crate::{Kind, NodeId, RenderGraph};
+
+    const ARM: &str = "
+        arm      -    10  0   3 0 0  0 0 0
+        forearm  arm  10  0   0 0 0  0 0 0
+        thumb    arm  2   90  0 0 0  0 0 -90
+    ";
+
+    fn built() -> (Skeleton, RenderGraph) {
+        let mut skeleton = Skeleton::from_rig(ARM).unwrap();
+        let mut graph = RenderGraph::new();
+        let root_id = graph.root_mut().node_id();
+        skeleton.build(&mut graph, root_id);
+
+        (skeleton, graph)
+    }
+
+    // world_origin is where the node's origin ends up, only nodes with a kind are walked so it
+    // gets a marker
+    fn world_origin(graph: &mut RenderGraph, id: NodeId) -> Point3<f32> {
+        let marker_id = graph.node_mut(id).push_line(Line::new(0.0)).node_id();
+
+        let mut origin = None;
+        graph.walk_instances(|node_id, transform, _| {
+            if node_id == marker_id {
+                origin = Some(transform.transform_point(&Point3::origin()));
+            }
+        });
+
+        origin.unwrap()
+    }
+
+    #[test]
+    fn bones_iterate_parents_first() {
+        let skeleton = Skeleton::from_rig(ARM).unwrap();
+
+        let names: Vec<_> = skeleton.iter().map(|(_, b)| b.name.as_str()).collect();
+        assert_eq!(names, ["arm", "forearm", "thumb"]);
+        for (id, bone) in skeleton.iter() {
+            assert!(bone.parent.is_none_or(|p| p < id));
+        }
+        let arm = skeleton.find("arm").unwrap();
+        assert_eq!(skeleton.children(arm).collect::<Vec<_>>(), [1, 2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "parent bone should exist")]
+    fn parents_come_first() {
+        Skeleton::new().add_bone(Some(0), Bone::new("orphan", 1.0));
+    }
+
+    #[test]
+    fn built_bones_end_at_their_tips() {
+        let (skeleton, mut graph) = built();
+        let tip = |graph: &mut RenderGraph, name| {
+            let id = skeleton
+                .bone(skeleton.find(name).unwrap())
+                .tip_id()
+                .unwrap();
+            world_origin(graph, id)
+        };
+
+        assert!((tip(&mut graph, "arm") - point![3.0, 10.0, 0.0]).magnitude() < 1e-4);
+        assert!((tip(&mut graph, "forearm") - point![3.0, 20.0, 0.0]).magnitude() < 1e-4);
+        // Rotated to point along +X, rolling it doesn't move the tip
+        assert!((tip(&mut graph, "thumb") - point![5.0, 10.0, 0.0]).magnitude() < 1e-4);
+    }
+
+    #[test]
+    fn poses_are_applied_to_the_joints() {
+        let (mut skeleton, mut graph) = built();
+        let forearm = skeleton.find("forearm").unwrap();
+        let tip_id = skeleton.bone(forearm).tip_id().unwrap();
+
+        skeleton.bone_mut(forearm).pose.rotation = vector![0.0, 0.0, 90.0];
+        skeleton.apply(&mut graph);
+        assert!((world_origin(&mut graph, tip_id) - point![-7.0, 10.0, 0.0]).magnitude() < 1e-4);
+        // The rest pose is kept to go back to
+        assert_eq!(skeleton.bone(forearm).rest.rotation, vector![0.0, 0.0, 0.0]);
+
+        skeleton.reset_pose();
+        skeleton.apply(&mut graph);
+        assert!((world_origin(&mut graph, tip_id) - point![3.0, 20.0, 0.0]).magnitude() < 1e-4);
+    }
+
+    #[test]
+    fn masks_cover_a_bone_and_everything_below_it() {
+        let (skeleton, _) = built();
+        let joint = |b| skeleton.bone(b).joint_id().unwrap();
+
+        let arm = skeleton.mask(0);
+        assert!((0..3).all(|b| arm.contains(joint(b))));
+        let forearm = skeleton.mask(1);
+        assert!(forearm.contains(joint(1)));
+        assert!(!forearm.contains(joint(0)) && !forearm.contains(joint(2)));
+    }
+
+    #[test]
+    fn skins_are_attached_to_their_bones() {
+        let mut skeleton = Skeleton::from_rig(ARM).unwrap();
+        let arm = skeleton.find("arm").unwrap();
+        skeleton
+            .attach(arm, Shape::Sphere(0.5), Material::default())
+            .attach(arm, Shape::Sphere(0.25), Material::default());
+
+        let mut graph = RenderGraph::new();
+        let root_id = graph.root_mut().node_id();
+        skeleton.build(&mut graph, root_id);
+
+        let mut shapes = 0;
+        graph.walk(|_, kind| {
+            if let Kind::Shape(..) = kind {
+                shapes += 1
+            }
+        });
+        assert_eq!(shapes, 2);
+
+        let joints = skeleton.joints();
+        assert_eq!(joints.len(), 3);
+        assert_eq!(joints[1].name, "forearm");
+        assert_eq!(Some(joints[1].node_id), skeleton.bone(1).joint_id());
+    }
+
+    #[test]
+    fn picked_skins_belong_to_their_bone() {
+        let mut skeleton = Skeleton::from_rig(ARM).unwrap();
+        // The thumb has no skin, so nothing picked belongs to it
+        for bone in 0..2 {
+            skeleton.attach(bone, Shape::Sphere(0.5), Material::default());
+        }
+        let mut graph = RenderGraph::new();
+        let root_id = graph.root_mut().node_id();
+        skeleton.build(&mut graph, root_id);
+
+        // Looking along Z at the middle of each bone
+        let owner = |origin| {
+            let picked = graph.pick(origin, vector![0.0, 0.0, -1.0])?;
+            skeleton.owner(&graph, picked)
+        };
+        assert_eq!(owner(point![3.0, 5.0, 50.0]), Some(0));
+        assert_eq!(owner(point![3.0, 15.0, 50.0]), Some(1));
+        assert_eq!(owner(point![-20.0, 5.0, 50.0]), None);
+        assert_eq!(skeleton.owner(&graph, root_id), None);
+    }
+}
@@ -0,0 +1,130 @@
+use std::error::Error;
+use std::fmt;
+
+use nalgebra::{point, vector};
+
+use crate::skeleton::{Bone, Skeleton};
+use crate::NodeTransform;
+
+// A rig has a bone per line, with whitespace between the columns and `#` starting a comment
+//
+//   # name    parent  length  roll  position  rotation
+//   arm       -       10      0     3 0 0     0 0 0
+//   forearm   arm     10      0     0 0 0     0 0 0
+//
+// Parents are named before their children, `-` means the bone hangs off the skeleton's node
+// Roll and rotation are in degrees, rotation is the rest pose's rotation like NodeTransform's
+const COLUMNS: usize = 10;
+
+// RigError is why a rig couldn't be read, and the line it's on counting from 1
+#[derive(Debug, PartialEq)]
+pub struct RigError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for RigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for RigError {}
+
+pub(super) fn parse(rig: &str) -> Result<Skeleton, RigError> {
+    let mut skeleton = Skeleton::new();
+
+    for (i, line) in rig.lines().enumerate() {
+        let error = |message: String| RigError {
+            line: i + 1,
+            message,
+        };
+
+        let line = line.split('#').next().unwrap_or_default();
+        let columns: Vec<&str> = line.split_whitespace().collect();
+        if columns.is_empty() {
+            continue;
+        }
+        if columns.len() != COLUMNS {
+            return Err(error(format!(
+                "expected {COLUMNS} columns, found {}",
+                columns.len()
+            )));
+        }
+
+        let name = columns[0];
+        if skeleton.find(name).is_some() {
+            return Err(error(format!("bone `{name}` is already defined")));
+        }
+        let parent = match columns[1] {
+            "-" => None,
+            parent => Some(
+                skeleton
+                    .find(parent)
+                    .ok_or_else(|| error(format!("parent `{parent}` isn't defined yet")))?,
+            ),
+        };
+
+        let mut numbers = [0.0; COLUMNS - 2];
+        for (n, column) in numbers.iter_mut().zip(&columns[2..]) {
+            *n = column
+                .parse()
+                .map_err(|_| error(format!("`{column}` isn't a number")))?;
+        }
+        let [length, roll, x, y, z, rx, ry, rz] = numbers;
+
+        let mut rest = NodeTransform::identity();
+        rest.position = point![x, y, z];
+        rest.rotation = vector![rx, ry, rz];
+        skeleton.add_bone(parent, Bone::new(name, length).roll(roll).rest(rest));
+    }
+
+    Ok(skeleton)
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{point, vector};
+
+    use crate::skeleton::{RigError, Skeleton};
+
+    #[test]
+    fn rigs_are_read_in_order() {
+        let skeleton = Skeleton::from_rig(
+            "# name  parent  length  roll  position  rotation
+            hip      -       2       0     0 5 0     0 0 0
+            thigh    hip     4       15    1 0 0     0 0 180  # pointing down
+
+            shin     thigh   3.5     0     0 0 0     0 0 0",
+        )
+        .unwrap();
+
+        assert_eq!(skeleton.len(), 3);
+        let thigh = skeleton.bone(1);
+        assert_eq!(thigh.name, "thigh");
+        assert_eq!(thigh.parent, Some(0));
+        assert_eq!(thigh.roll, 15.0);
+        assert_eq!(thigh.rest.position, point![1.0, 0.0, 0.0]);
+        assert_eq!(thigh.rest.rotation, vector![0.0, 0.0, 180.0]);
+        assert_eq!(skeleton.bone(2).length, 3.5);
+    }
+
+    #[test]
+    fn errors_point_at_the_line() {
+        let error = |rig| Skeleton::from_rig(rig).err().unwrap();
+
+        assert_eq!(
+            error("a - 1 0 0 0 0 0 0 0\nb c 1 0 0 0 0 0 0 0"),
+            RigError {
+                line: 2,
+                message: "parent `c` isn't defined yet".to_string()
+            }
+        );
+        assert_eq!(error("\na - 1 0 0 0 0 0 0").line, 2);
+        assert_eq!(error("a - one 0 0 0 0 0 0 0").line, 1);
+        assert_eq!(
+            error("a - 1 0 0 0 0 0 0 0\na - 1 0 0 0 0 0 0 0").to_string(),
+            "line 2: bone `a` is already defined"
+        );
+    }
+}
@@ -0,0 +1,540 @@
+use nalgebra::{Matrix3, Matrix4, point, Point3, vector, Vector3};
+
+use crate::graph::{Kind, RenderGraph};
+use crate::shapes::{Displacement, Material, Shape};
+use crate::surface::noise::displacement;
+use crate::surface::primitives::{cylinder, ellipsoid, ellipsoid_slope, sphere};
+
+mod noise;
+pub mod primitives;
+
+// How far shapes reach out to blend with each other
+const SMOOTH_MIN_K: f32 = 0.5;
+// Second derivatives need a bigger step than the gradient, or f32 rounding swamps them
+const CURVATURE_H: f32 = 0.01;
+// Gradients shorter than this have no reliable direction, like at the middle of a symmetric blend
+const MIN_NORMAL_GRADIENT: f32 = 0.01;
+
+pub struct Surface {
+    shapes: Vec<(Matrix4<f32>, Shape, Material, Vec<Displacement>)>,
+}
+
+impl Default for Surface {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Surface {
+    pub fn new() -> Self {
+        Self { shapes: vec![] }
+    }
+
+    // from_graph collects every shape in the graph, symmetry copies included
+    pub fn from_graph(graph: &RenderGraph) -> Self {
+        let mut surface = Surface::new();
+
+        graph.walk(|transform, kind| {
+            if let Kind::Shape(shape, material, displacements) = kind {
+                surface.push(
+                    transform.try_inverse().expect("transform can be inverted"),
+                    *shape,
+                    *material,
+                    displacements,
+                )
+            }
+        });
+
+        surface
+    }
+
+    pub fn push(
+        &mut self,
+        transform: Matrix4<f32>,
+        shape: Shape,
+        material: Material,
+        displacements: &[Displacement],
+    ) {
+        self.shapes
+            .push((transform, shape, material, displacements.to_vec()))
+    }
+
+    pub fn empty(&self) -> bool {
+        self.shapes.is_empty()
+    }
+
+    // bounds returns the min and max corners of a box that contains the whole surface
+    // It's conservative, blending and displacement can only grow the surface so much. Noise can
+    // reach about twice its amplitude, and amplitudes are in the shape's units
+    pub fn bounds(&self) -> (Point3<f32>, Point3<f32>) {
+        let mut min = point![f32::MAX, f32::MAX, f32::MAX];
+        let mut max = point![f32::MIN, f32::MIN, f32::MIN];
+
+        for (inverse, shape, _, displacements) in &self.shapes {
+            let transform = inverse
+                .try_inverse()
+                .expect("shape transform can be inverted");
+
+            let displaced = displacements
+                .iter()
+                .map(|d| 2.0 * d.amplitude.abs())
+                .sum::<f32>();
+            let radius =
+                (shape.bounding_radius() * (1.0 + SMOOTH_MIN_K)) + SMOOTH_MIN_K + displaced;
+
+            let center = transform.transform_point(&Point3::origin());
+            for axis in 0..3 {
+                // How far a transformed sphere reaches along each axis
+                let extent = radius * transform.fixed_view::<1, 3>(axis, 0).magnitude();
+
+                min[axis] = min[axis].min(center[axis] - extent);
+                max[axis] = max[axis].max(center[axis] + extent);
+            }
+        }
+
+        (min, max)
+    }
+
+    fn eval_shape(&self, index: usize, at: Point3<f32>) -> f32 {
+        let (t, s, _, displacements) = &self.shapes[index];
+
+        let tat = t.transform_point(&at);
+
+        let value = match *s {
+            Shape::Ellipsoid(p) => ellipsoid(p)(tat),
+            Shape::Sphere(r) => sphere(r)(tat),
+            Shape::Cyliner(r, h) => cylinder(r, h)(tat),
+        };
+
+        if displacements.is_empty() {
+            return value;
+        }
+
+        // Noise is sampled in the shape's local space so it sticks to the shape as it moves
+        // It's scaled by how fast the value grows, so the surface moves by the noise's value
+        // whatever the shape's size
+        let slope = match *s {
+            Shape::Ellipsoid(p) => ellipsoid_slope(p)(tat),
+            Shape::Sphere(r) => ellipsoid_slope(Vector3::new(r, r, r))(tat),
+            // The cylinder's value is already the distance to it
+            Shape::Cyliner(..) => 1.0,
+        };
+        displacements
+            .iter()
+            .fold(value, |value, d| value - (displacement(d)(tat) * slope))
+    }
+
+    pub fn sample(&self, at: Point3<f32>) -> f32 {
+        match self.shapes.len() {
+            0 => {
+                panic!("No shapes! Nothing to sample.")
+            }
+            1 => self.eval_shape(0, at),
+            2 => smooth_min(self.eval_shape(0, at), self.eval_shape(1, at), SMOOTH_MIN_K),
+            _ => {
+                let mut min_1 = f32::MAX;
+                let mut min_2 = f32::MAX;
+
+                for i in 0..self.shapes.len() {
+                    let t = self.eval_shape(i, at);
+
+                    if t < min_1 {
+                        min_2 = min_1;
+                        min_1 = t;
+                    }
+                }
+
+                smooth_min(min_1, min_2, SMOOTH_MIN_K)
+            }
+        }
+    }
+
+    // inverse_transforms returns the world to local transform of every shape, in the order they were pushed
+    pub fn inverse_transforms(&self) -> Vec<Matrix4<f32>> {
+        self.shapes.iter().map(|(inverse, ..)| *inverse).collect()
+    }
+
+    // motion returns the transform that carries each shape from where it was to where it is now,
+    // `previous` comes from inverse_transforms. Shapes are matched in the order they were pushed,
+    // so there's no motion if the number of shapes has changed
+    pub fn motion(&self, previous: &[Matrix4<f32>]) -> Option<Vec<Matrix4<f32>>> {
+        if previous.len() != self.shapes.len() {
+            return None;
+        }
+
+        let motion = self
+            .shapes
+            .iter()
+            .zip(previous)
+            .map(|((inverse, ..), previous_inverse)| {
+                inverse
+                    .try_inverse()
+                    .expect("shape transform can be inverted")
+                    * previous_inverse
+            })
+            .collect();
+
+        Some(motion)
+    }
+
+    // nearest_shape returns the index of the shape with the lowest value at a point,
+    // shapes are indexed in the order they were pushed
+    pub fn nearest_shape(&self, at: Point3<f32>) -> Option<usize> {
+        (0..self.shapes.len())
+            .map(|i| (i, self.eval_shape(i, at)))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(i, _)| i)
+    }
+
+    // sample_material returns the surface value and the blended material at a point
+    // Every shape within its material's blend distance of the nearest shape contributes
+    pub fn sample_material(&self, at: Point3<f32>) -> (f32, Material) {
+        let values: Vec<f32> = (0..self.shapes.len())
+            .map(|i| self.eval_shape(i, at))
+            .collect();
+        let nearest = values.iter().copied().fold(f32::MAX, f32::min);
+
+        let mut total_weight = 0.0;
+        let mut color = vector![0.0, 0.0, 0.0];
+        let mut roughness = 0.0;
+        for (value, (_, _, material, _)) in values.iter().zip(&self.shapes) {
+            let weight = material_weight(value - nearest, material.blend);
+
+            total_weight += weight;
+            color += material.color.scale(weight);
+            roughness += material.roughness * weight;
+        }
+
+        let blended = Material {
+            color: color / total_weight,
+            roughness: roughness / total_weight,
+            blend: 0.0,
+        };
+
+        (self.sample(at), blended)
+    }
+}
+
+// material_weight fades from 1.0 for the nearest shape to 0.0 at the blend distance
+fn material_weight(distance_from_nearest: f32, blend: f32) -> f32 {
+    if blend <= 0.0 {
+        return if distance_from_nearest <= 0.0 {
+            1.0
+        } else {
+            0.0
+        };
+    }
+
+    let t = (1.0 - (distance_from_nearest / blend)).clamp(0.0, 1.0);
+
+    t * t * (3.0 - (2.0 * t))
+}
+
+fn smooth_min(a: f32, b: f32, k: f32) -> f32 {
+    let h = (k - (a - b).abs()).max(0.0);
+
+    a.min(b) - (h * h * 0.25 / k)
+}
+
+pub fn seed(surface: &Surface) -> Point3<f32> {
+    let mut seed_point = point![rand::random(), rand::random(), rand::random()];
+
+    for _ in 0..100 {
+        let grad = gradient(surface, seed_point);
+
+        let gdg = grad.dot(&grad);
+        if gdg.is_nan() {
+            panic!("NANANANANANA")
+        }
+
+        seed_point -= grad.scale(surface.sample(seed_point) / gdg);
+
+        if on_surface(surface, seed_point) {
+            return seed_point;
+        }
+    }
+
+    if !on_surface(surface, seed_point) {
+        panic!("could not find a seed point near {seed_point}")
+    }
+
+    seed_point
+}
+
+pub fn gradient(surface: &Surface, p: Point3<f32>) -> Vector3<f32> {
+    let h = 0.0001;
+
+    let sp = surface.sample(p);
+
+    let dx = (surface.sample(point![p.x + h, p.y, p.z]) - sp) / h;
+    let dy = (surface.sample(point![p.x, p.y + h, p.z]) - sp) / h;
+    let dz = (surface.sample(point![p.x, p.y, p.z + h]) - sp) / h;
+
+    vector![dx, dy, dz]
+}
+
+// normal is the outward unit normal at p, or None where the gradient vanishes
+pub fn normal(surface: &Surface, p: Point3<f32>) -> Option<Vector3<f32>> {
+    let gradient = gradient(surface, p);
+    if !gradient.iter().all(|v| v.is_finite()) {
+        return None;
+    }
+
+    gradient.try_normalize(MIN_NORMAL_GRADIENT)
+}
+
+// curvature estimates how sharply the surface bends at p, it's 1 / r on a sphere of radius r
+// It's the root mean square of the principal curvatures, so saddles don't cancel out
+pub fn curvature(surface: &Surface, p: Point3<f32>) -> f32 {
+    let h = CURVATURE_H;
+    let offset = |i: usize| Vector3::ith(i, h);
+
+    let sp = surface.sample(p);
+    let mut gradient = Vector3::zeros();
+    let mut hessian = Matrix3::zeros();
+
+    for i in 0..3 {
+        let (forward, back) = (surface.sample(p + offset(i)), surface.sample(p - offset(i)));
+
+        gradient[i] = (forward - back) / (2.0 * h);
+        hessian[(i, i)] = (forward - (2.0 * sp) + back) / (h * h);
+
+        for j in (i + 1)..3 {
+            let mixed = (surface.sample(p + offset(i) + offset(j))
+                - surface.sample(p + offset(i) - offset(j))
+                - surface.sample(p - offset(i) + offset(j))
+                + surface.sample(p - offset(i) - offset(j)))
+                / (4.0 * h * h);
+
+            hessian[(i, j)] = mixed;
+            hessian[(j, i)] = mixed;
+        }
+    }
+
+    let magnitude = gradient.magnitude();
+    if magnitude == 0.0 {
+        return 0.0;
+    }
+
+    // The shape operator is the hessian projected onto the tangent plane,
+    // its non-zero eigenvalues are the principal curvatures
+    let normal = gradient / magnitude;
+    let tangent = Matrix3::identity() - (normal * normal.transpose());
+    let shape_operator = (tangent * hessian * tangent) / magnitude;
+
+    (shape_operator.norm_squared() / 2.0).sqrt()
+}
+
+pub fn on_surface(surface: &Surface, point: Point3<f32>) -> bool {
+    surface.sample(point).abs() <= f32::EPSILON * 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use nalgebra::{Matrix4, point, Point3, Rotation3, Translation3, vector};
+
+    use crate::shapes::{Displacement, Material, Noise, Shape};
+    use crate::surface::noise::displacement;
+    use crate::surface::{curvature, gradient, normal, Surface};
+    use crate::RenderGraph;
+
+    fn two_colored_spheres() -> Surface {
+        let mut surface = Surface::new();
+
+        surface.push(
+            Translation3::new(1.0, 0.0, 0.0).to_homogeneous(),
+            Shape::Sphere(1.0),
+            Material::new(vector![1.0, 0.0, 0.0]),
+            &[],
+        );
+        surface.push(
+            Translation3::new(-1.0, 0.0, 0.0).to_homogeneous(),
+            Shape::Sphere(1.0),
+            Material::new(vector![0.0, 0.0, 1.0]).roughness(0.0),
+            &[],
+        );
+
+        surface
+    }
+
+    #[test]
+    fn displacement_moves_the_surface() {
+        let noise = Displacement::new(Noise::Gradient).amplitude(0.2).seed(3);
+        let offset = displacement(&noise);
+
+        // Amplitude is a distance, so the surface moves as far on a big sphere as on a small one
+        for radius in [1.0, 3.0] {
+            let mut graph = RenderGraph::new();
+            graph
+                .root_mut()
+                .push_shape(Shape::Sphere(radius))
+                .displace(noise);
+            let surface = Surface::from_graph(&graph);
+
+            let mut furthest = 0.0_f32;
+            let mut gradient_changed = false;
+            for i in 0..100 {
+                let direction = Rotation3::new(vector![i as f32 * 0.37, i as f32 * 0.61, 0.0])
+                    * vector![1.0, 0.0, 0.0];
+                let on_sphere = Point3::from(direction * radius);
+
+                // Find where the displaced surface crosses this direction
+                let (mut inside, mut outside) = (radius - 0.5, radius + 0.5);
+                for _ in 0..30 {
+                    let middle = (inside + outside) / 2.0;
+                    if surface.sample(Point3::from(direction * middle)) < 0.0 {
+                        inside = middle
+                    } else {
+                        outside = middle
+                    }
+                }
+
+                let moved = inside - radius;
+                let expected = offset(on_sphere);
+                assert!(
+                    (moved - expected).abs() < 0.02,
+                    "radius {radius} moved {moved}, expected {expected}"
+                );
+                furthest = furthest.max(moved.abs());
+
+                let plain = direction * (2.0 / radius);
+                gradient_changed |= (gradient(&surface, on_sphere) - plain).magnitude() > 0.05;
+            }
+
+            assert!(furthest > 0.05, "{furthest}");
+            assert!(gradient_changed);
+        }
+    }
+
+    #[test]
+    fn curvature_is_inverse_radius() {
+        for radius in [0.5, 1.0, 4.0] {
+            let mut surface = Surface::new();
+            surface.push(
+                Matrix4::identity(),
+                Shape::Sphere(radius),
+                Material::default(),
+                &[],
+            );
+
+            let k = curvature(&surface, point![0.0, radius, 0.0]);
+            assert!(
+                (k - (1.0 / radius)).abs() < 0.05 / radius,
+                "{k} on radius {radius}"
+            );
+        }
+    }
+
+    #[test]
+    fn curvature_is_higher_on_smaller_shapes() {
+        // Surfaces take the inverse transform, so the small sphere sits on the side of the large one
+        let mut surface = Surface::new();
+        surface.push(
+            Matrix4::identity(),
+            Shape::Sphere(3.0),
+            Material::default(),
+            &[],
+        );
+        surface.push(
+            Translation3::new(-3.0, 0.0, 0.0).to_homogeneous(),
+            Shape::Sphere(0.5),
+            Material::default(),
+            &[],
+        );
+
+        let large = curvature(&surface, point![-3.0, 0.0, 0.0]);
+        let small = curvature(&surface, point![3.5, 0.0, 0.0]);
+
+        assert!(small > large * 2.0);
+    }
+
+    #[test]
+    fn motion_carries_points_with_shapes() {
+        let mut previous = Surface::new();
+        previous.push(
+            Matrix4::identity(),
+            Shape::Sphere(1.0),
+            Material::default(),
+            &[],
+        );
+
+        let moved = Translation3::new(2.0, 0.0, 0.0).to_homogeneous()
+            * Rotation3::new(vector![0.0, 0.0, PI / 2.0]).to_homogeneous();
+        let mut current = Surface::new();
+        current.push(
+            moved.try_inverse().unwrap(),
+            Shape::Sphere(1.0),
+            Material::default(),
+            &[],
+        );
+
+        let motion = current.motion(&previous.inverse_transforms()).unwrap();
+        let carried = motion[0].transform_point(&point![1.0, 0.0, 0.0]);
+
+        assert!((carried - point![2.0, 1.0, 0.0]).magnitude() < 0.0001);
+        assert!(current.sample(carried).abs() < 0.0001);
+        assert!(current.motion(&[]).is_none());
+    }
+
+    #[test]
+    fn normals_point_outward() {
+        let surface = two_colored_spheres();
+
+        let n = normal(&surface, point![2.0, 0.0, 0.0]).unwrap();
+        assert!((n - vector![1.0, 0.0, 0.0]).magnitude() < 0.01, "{n}");
+
+        // The neck between the spheres curves inward, its normals still face away from it
+        let n = normal(&surface, point![0.0, 0.5, 0.0]).unwrap();
+        assert!(n.y > 0.9, "{n}");
+    }
+
+    #[test]
+    fn normal_is_none_where_gradient_vanishes() {
+        // The middle of two identical spheres is symmetric in every direction
+        assert_eq!(normal(&two_colored_spheres(), point![0.0, 0.0, 0.0]), None);
+    }
+
+    #[test]
+    fn material_is_pure_away_from_blend() {
+        let surface = two_colored_spheres();
+
+        let (_, material) = surface.sample_material(point![-3.0, 0.0, 0.0]);
+
+        assert_eq!(material.color, vector![1.0, 0.0, 0.0]);
+        assert_eq!(material.roughness, 1.0);
+    }
+
+    #[test]
+    fn material_blends_where_shapes_meet() {
+        let surface = two_colored_spheres();
+
+        let (value, material) = surface.sample_material(point![0.0, 0.0, 0.0]);
+
+        assert_eq!(value, surface.sample(point![0.0, 0.0, 0.0]));
+        assert!((material.color - vector![0.5, 0.0, 0.5]).magnitude() <= 0.0001);
+        assert!((material.roughness - 0.5).abs() <= 0.0001);
+    }
+
+    #[test]
+    fn hard_edge_material_does_not_blend() {
+        let mut surface = Surface::new();
+
+        surface.push(
+            Matrix4::identity(),
+            Shape::Sphere(1.0),
+            Material::new(vector![1.0, 1.0, 0.0]).blend(0.0),
+            &[],
+        );
+        surface.push(
+            Translation3::new(-0.1, 0.0, 0.0).to_homogeneous(),
+            Shape::Sphere(1.0),
+            Material::new(vector![0.0, 1.0, 1.0]).blend(0.0),
+            &[],
+        );
+
+        let (_, material) = surface.sample_material(point![-0.5, 0.0, 0.0]);
+        assert_eq!(material.color, vector![1.0, 1.0, 0.0]);
+    }
+}
@@ -0,0 +1,294 @@
+use nalgebra::{point, Point3, vector, Vector3};
+
+use crate::shapes::{Displacement, Noise};
+
+// Procedural noise used to displace primitives
+// All noise is deterministic for a given seed, so the surface doesn't change between frames
+
+const FBM_LACUNARITY: f32 = 2.0;
+const FBM_GAIN: f32 = 0.5;
+
+// The 12 edge midpoints of a cube, from Ken Perlin's improved noise
+const GRADIENTS: [Vector3<f32>; 12] = [
+    Vector3::new(1.0, 1.0, 0.0),
+    Vector3::new(-1.0, 1.0, 0.0),
+    Vector3::new(1.0, -1.0, 0.0),
+    Vector3::new(-1.0, -1.0, 0.0),
+    Vector3::new(1.0, 0.0, 1.0),
+    Vector3::new(-1.0, 0.0, 1.0),
+    Vector3::new(1.0, 0.0, -1.0),
+    Vector3::new(-1.0, 0.0, -1.0),
+    Vector3::new(0.0, 1.0, 1.0),
+    Vector3::new(0.0, -1.0, 1.0),
+    Vector3::new(0.0, 1.0, -1.0),
+    Vector3::new(0.0, -1.0, -1.0),
+];
+
+pub fn displacement(d: &Displacement) -> impl Fn(Point3<f32>) -> f32 {
+    let d = *d;
+
+    move |p| {
+        let p = p * d.frequency;
+
+        let n = match d.noise {
+            Noise::Gradient => gradient_noise(p, d.seed),
+            Noise::Simplex => simplex_noise(p, d.seed),
+            Noise::Fbm { octaves } => fbm(p, d.seed, octaves),
+            Noise::Ridged { octaves } => ridged(p, d.seed, octaves),
+            Noise::Voronoi => voronoi(p, d.seed),
+        };
+
+        n * d.amplitude
+    }
+}
+
+fn hash(x: i32, y: i32, z: i32, seed: u32) -> u32 {
+    let mut h = seed.wrapping_mul(0x27d4_eb2d);
+    h ^= (x as u32).wrapping_mul(0x8da6_b343);
+    h = h.rotate_left(13);
+    h ^= (y as u32).wrapping_mul(0xd816_3841);
+    h = h.rotate_left(13);
+    h ^= (z as u32).wrapping_mul(0xcb1a_b31f);
+
+    // Final avalanche so neighbouring cells don't correlate
+    h = (h ^ (h >> 16)).wrapping_mul(0x7feb_352d);
+    h = (h ^ (h >> 15)).wrapping_mul(0x846c_a68b);
+    h ^ (h >> 16)
+}
+
+fn cell_gradient(x: i32, y: i32, z: i32, seed: u32) -> Vector3<f32> {
+    GRADIENTS[(hash(x, y, z, seed) % 12) as usize]
+}
+
+// Quintic fade, so the noise has a continuous second derivative
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+pub fn gradient_noise(p: Point3<f32>, seed: u32) -> f32 {
+    let cell = p.map(f32::floor);
+    let f = p - cell;
+    let (x, y, z) = (cell.x as i32, cell.y as i32, cell.z as i32);
+
+    let corner = |dx: i32, dy: i32, dz: i32| {
+        cell_gradient(x + dx, y + dy, z + dz, seed)
+            .dot(&(f - vector![dx as f32, dy as f32, dz as f32]))
+    };
+
+    let u = f.map(fade);
+
+    let x00 = lerp(corner(0, 0, 0), corner(1, 0, 0), u.x);
+    let x10 = lerp(corner(0, 1, 0), corner(1, 1, 0), u.x);
+    let x01 = lerp(corner(0, 0, 1), corner(1, 0, 1), u.x);
+    let x11 = lerp(corner(0, 1, 1), corner(1, 1, 1), u.x);
+
+    lerp(lerp(x00, x10, u.y), lerp(x01, x11, u.y), u.z)
+}
+
+// Stefan Gustavson. Simplex noise demystified.
+// https://weber.itn.liu.se/~stegu/simplexnoise/simplexnoise.pdf
+pub fn simplex_noise(p: Point3<f32>, seed: u32) -> f32 {
+    const F3: f32 = 1.0 / 3.0;
+    const G3: f32 = 1.0 / 6.0;
+
+    // Skew into simplex cell space
+    let s = (p.x + p.y + p.z) * F3;
+    let cell = point![(p.x + s).floor(), (p.y + s).floor(), (p.z + s).floor()];
+    let t = (cell.x + cell.y + cell.z) * G3;
+    let x0 = p - (cell - vector![t, t, t]);
+
+    // Figure out which of the six simplices we're in
+    let (o1, o2) = if x0.x >= x0.y {
+        if x0.y >= x0.z {
+            ([1, 0, 0], [1, 1, 0])
+        } else if x0.x >= x0.z {
+            ([1, 0, 0], [1, 0, 1])
+        } else {
+            ([0, 0, 1], [1, 0, 1])
+        }
+    } else if x0.y < x0.z {
+        ([0, 0, 1], [0, 1, 1])
+    } else if x0.x < x0.z {
+        ([0, 1, 0], [0, 1, 1])
+    } else {
+        ([0, 1, 0], [1, 1, 0])
+    };
+
+    let (x, y, z) = (cell.x as i32, cell.y as i32, cell.z as i32);
+    let offset = |o: [i32; 3], k: f32| {
+        x0 - vector![o[0] as f32, o[1] as f32, o[2] as f32] + vector![k * G3, k * G3, k * G3]
+    };
+
+    let corners = [
+        ([0, 0, 0], x0),
+        (o1, offset(o1, 1.0)),
+        (o2, offset(o2, 2.0)),
+        ([1, 1, 1], offset([1, 1, 1], 3.0)),
+    ];
+
+    let total: f32 = corners
+        .iter()
+        .map(|(o, d)| {
+            let t = 0.6 - d.magnitude_squared();
+            if t < 0.0 {
+                return 0.0;
+            }
+
+            let g = cell_gradient(x + o[0], y + o[1], z + o[2], seed);
+            t.powi(4) * g.dot(d)
+        })
+        .sum();
+
+    32.0 * total
+}
+
+pub fn fbm(p: Point3<f32>, seed: u32, octaves: u32) -> f32 {
+    let mut total = 0.0;
+    let mut amplitude = 1.0;
+    let mut max_amplitude = 0.0;
+    let mut frequency = 1.0;
+
+    for octave in 0..octaves.max(1) {
+        total += gradient_noise(p * frequency, seed.wrapping_add(octave)) * amplitude;
+        max_amplitude += amplitude;
+
+        amplitude *= FBM_GAIN;
+        frequency *= FBM_LACUNARITY;
+    }
+
+    total / max_amplitude
+}
+
+pub fn ridged(p: Point3<f32>, seed: u32, octaves: u32) -> f32 {
+    let mut total = 0.0;
+    let mut amplitude = 1.0;
+    let mut max_amplitude = 0.0;
+    let mut frequency = 1.0;
+
+    for octave in 0..octaves.max(1) {
+        let n = 1.0 - gradient_noise(p * frequency, seed.wrapping_add(octave)).abs();
+        total += n * n * amplitude;
+        max_amplitude += amplitude;
+
+        amplitude *= FBM_GAIN;
+        frequency *= FBM_LACUNARITY;
+    }
+
+    total / max_amplitude
+}
+
+pub fn voronoi(p: Point3<f32>, seed: u32) -> f32 {
+    let cell = p.map(f32::floor);
+    let (x, y, z) = (cell.x as i32, cell.y as i32, cell.z as i32);
+
+    let mut nearest = f32::MAX;
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            for dz in -1..=1 {
+                let h = hash(x + dx, y + dy, z + dz, seed);
+
+                // Each cell gets one feature point, jittered by the hash
+                let jitter = vector![
+                    (h & 0x3ff) as f32 / 1023.0,
+                    ((h >> 10) & 0x3ff) as f32 / 1023.0,
+                    ((h >> 20) & 0x3ff) as f32 / 1023.0
+                ];
+                let feature = cell + vector![dx as f32, dy as f32, dz as f32] + jitter;
+
+                nearest = nearest.min((feature - p).magnitude_squared());
+            }
+        }
+    }
+
+    nearest.sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::point;
+
+    use crate::surface::noise::{fbm, gradient_noise, ridged, simplex_noise, voronoi};
+
+    fn sample_points() -> impl Iterator<Item = nalgebra::Point3<f32>> {
+        (0..1000).map(|i| {
+            let i = i as f32;
+            point![i * 0.137 - 50.0, i * 0.071 - 20.0, i * 0.173 - 80.0]
+        })
+    }
+
+    #[test]
+    fn noise_is_deterministic() {
+        // Values from a known good run, the surface would change between runs if these did
+        let expected = [
+            (
+                point![0.3, 0.7, 1.1],
+                [-0.22818121, -0.102813885, -0.1186478, 0.5840785, 0.6504059],
+            ),
+            (
+                point![-2.4, 5.6, 0.25],
+                [0.17930396, 0.22155118, 0.16168875, 0.7041091, 0.55869985],
+            ),
+            (
+                point![10.5, -3.3, 7.9],
+                [0.0021307915, 0.68590516, 0.04951202, 0.81978166, 0.63076466],
+            ),
+        ];
+
+        for (p, values) in expected {
+            let actual = [
+                gradient_noise(p, 7),
+                simplex_noise(p, 7),
+                fbm(p, 7, 4),
+                ridged(p, 7, 4),
+                voronoi(p, 7),
+            ];
+
+            for (actual, expected) in actual.iter().zip(values) {
+                assert!((actual - expected).abs() < 0.00001, "{actual} at {p}");
+            }
+        }
+    }
+
+    #[test]
+    fn seed_changes_noise() {
+        let differs = sample_points().any(|p| gradient_noise(p, 1) != gradient_noise(p, 2));
+
+        assert!(differs)
+    }
+
+    #[test]
+    fn gradient_noise_is_zero_on_lattice() {
+        assert_eq!(gradient_noise(point![3.0, -2.0, 5.0], 0), 0.0)
+    }
+
+    #[test]
+    fn noise_is_bounded() {
+        for p in sample_points() {
+            assert!(gradient_noise(p, 3).abs() <= 1.5);
+            assert!(simplex_noise(p, 3).abs() <= 1.5);
+            assert!(fbm(p, 3, 4).abs() <= 1.5);
+
+            let r = ridged(p, 3, 4);
+            assert!((0.0..=1.0).contains(&r));
+
+            let v = voronoi(p, 3);
+            assert!((0.0..=3.0_f32.sqrt()).contains(&v));
+        }
+    }
+
+    #[test]
+    fn noise_is_continuous() {
+        let h = 0.0001;
+        for p in sample_points() {
+            let step = point![p.x + h, p.y, p.z];
+
+            assert!((gradient_noise(p, 0) - gradient_noise(step, 0)).abs() < 0.01);
+            assert!((simplex_noise(p, 0) - simplex_noise(step, 0)).abs() < 0.01);
+            assert!((voronoi(p, 0) - voronoi(step, 0)).abs() < 0.01);
+        }
+    }
+}
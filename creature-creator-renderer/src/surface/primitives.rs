@@ -23,6 +23,11 @@ pub fn sphere(r: f32) -> impl Fn(Point3<f32>) -> f32 {
     ellipsoid(Vector3::new(r, r, r))
 }
 
+// ellipsoid_slope is how fast the ellipsoid's value grows with distance at p, the size of its gradient
+pub fn ellipsoid_slope(s: Vector3<f32>) -> impl Fn(Point3<f32>) -> f32 {
+    move |p| (2.0 * p.coords.component_div(&s.component_mul(&s))).magnitude()
+}
+
 pub fn cylinder(r: f32, h: f32) -> impl Fn(Point3<f32>) -> f32 {
     move |p| {
         let d = vector![p.xz().coords.magnitude() - 2.0 * r + r, p.y.abs() - h];
@@ -0,0 +1,134 @@
+use std::f32::consts::PI;
+
+use nalgebra::{Matrix4, Quaternion, Unit, UnitQuaternion, Vector3};
+
+// A Symmetry node repeats its children, every copy shares the same nodes
+#[derive(Copy, Clone)]
+pub enum Symmetry {
+    // Mirror the children across the plane through the node's origin with this normal
+    Mirror { normal: Vector3<f32> },
+    // Repeat the children `count` times, evenly rotated around an axis through the node's origin
+    Radial { axis: Vector3<f32>, count: u32 },
+}
+
+impl Symmetry {
+    pub fn new_mirror(normal: Vector3<f32>) -> Self {
+        let symmetry = Symmetry::Mirror { normal };
+        symmetry.assert_valid();
+        symmetry
+    }
+
+    pub fn new_radial(axis: Vector3<f32>, count: u32) -> Self {
+        let symmetry = Symmetry::Radial { axis, count };
+        symmetry.assert_valid();
+        symmetry
+    }
+
+    // assert_valid panics on symmetries that would give NaN transforms or no copies at all
+    pub(crate) fn assert_valid(&self) {
+        match *self {
+            Symmetry::Mirror { normal } => {
+                assert!(normal.magnitude() > 0.0, "mirror normal can't be zero")
+            }
+            Symmetry::Radial { axis, count } => {
+                assert!(axis.magnitude() > 0.0, "radial axis can't be zero");
+                assert!(count >= 1, "radial symmetry needs at least one copy");
+            }
+        }
+    }
+
+    // copies returns the transform of each copy, relative to the symmetry node
+    // The first copy is always the identity, which is the source subtree itself
+    pub fn copies(&self) -> Vec<Matrix4<f32>> {
+        self.copy_rotations()
+            .iter()
+            .map(|(rotation, scale)| rotation.to_homogeneous() * Matrix4::new_scaling(*scale))
+            .collect()
+    }
+
+    // copy_rotations is like copies, but splits each copy into a rotation and a uniform scale
+    // This makes it easy to export copies to formats that only support TRS transforms
+    pub fn copy_rotations(&self) -> Vec<(UnitQuaternion<f32>, f32)> {
+        match *self {
+            Symmetry::Mirror { normal } => {
+                // A reflection is a half turn around the normal, then flipping everything
+                let n = normal.normalize();
+                let half_turn = UnitQuaternion::new_normalize(Quaternion::new(0.0, n.x, n.y, n.z));
+
+                vec![(UnitQuaternion::identity(), 1.0), (half_turn, -1.0)]
+            }
+            Symmetry::Radial { axis, count } => {
+                let axis = Unit::new_normalize(axis);
+                let step = (2.0 * PI) / (count as f32);
+
+                (0..count)
+                    .map(|i| {
+                        (
+                            UnitQuaternion::from_axis_angle(&axis, step * (i as f32)),
+                            1.0,
+                        )
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{point, vector};
+
+    use crate::symmetry::Symmetry;
+    use crate::RenderGraph;
+
+    #[test]
+    fn mirror_flips_across_plane() {
+        let copies = Symmetry::new_mirror(vector![2.0, 0.0, 0.0]).copies();
+
+        assert_eq!(copies.len(), 2);
+        assert_eq!(
+            copies[1].transform_point(&point![1.0, 2.0, 3.0]),
+            point![-1.0, 2.0, 3.0]
+        );
+        assert!(copies[1].fixed_view::<3, 3>(0, 0).determinant() < 0.0);
+    }
+
+    #[test]
+    fn radial_copies_are_evenly_spaced() {
+        let copies = Symmetry::new_radial(vector![0.0, 1.0, 0.0], 4).copies();
+
+        assert_eq!(copies.len(), 4);
+
+        let p = copies[1].transform_point(&point![1.0, 0.0, 0.0]);
+        assert!((p - point![0.0, 0.0, -1.0]).magnitude() <= 0.0001);
+    }
+
+    #[test]
+    #[should_panic(expected = "mirror normal can't be zero")]
+    fn mirror_rejects_zero_normal() {
+        Symmetry::new_mirror(vector![0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "radial axis can't be zero")]
+    fn radial_rejects_zero_axis() {
+        Symmetry::new_radial(vector![0.0, 0.0, 0.0], 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "radial symmetry needs at least one copy")]
+    fn radial_rejects_no_copies() {
+        Symmetry::new_radial(vector![0.0, 1.0, 0.0], 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "radial symmetry needs at least one copy")]
+    fn graph_rejects_invalid_symmetry() {
+        RenderGraph::new()
+            .root_mut()
+            .push_symmetry(Symmetry::Radial {
+                axis: vector![0.0, 1.0, 0.0],
+                count: 0,
+            });
+    }
+}
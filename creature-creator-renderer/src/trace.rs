@@ -0,0 +1,362 @@
+use std::io;
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use nalgebra::{Matrix4, point, Point3, vector, Vector3, Vector4};
+
+use crate::Camera;
+use crate::surface::{normal, Surface};
+
+// Sphere tracing renders the surface directly by marching rays through it, without sampling it
+// into particles first. It's slow, but it's the ground truth the particle render is compared to
+// Citation:
+// John C. Hart. Sphere tracing: a geometric method for the antialiased ray tracing of implicit
+// surfaces. The Visual Computer 12(10), 1996
+
+const TILE_SIZE: usize = 32;
+const MAX_STEPS: usize = 512;
+// Surface values aren't exact distances, they overestimate away from shapes and where they blend,
+// so steps are shortened and capped to a fraction of the surface's bounds
+const STEP_SCALE: f32 = 0.8;
+const MIN_STEPS_ACROSS: f32 = 128.0;
+// Steps that still end up inside the surface are refined back to its edge
+const BISECTION_STEPS: usize = 24;
+const HIT_DISTANCE: f32 = 0.0005;
+
+// The lighting is the same as sphere_shader.metal
+pub(crate) const BACKGROUND_COLOR: Vector3<f32> = vector![0.960, 0.991, 0.960];
+const AMBIENT: f32 = 0.3;
+const DIFFUSE_K: f32 = 0.5;
+const LIGHTS: [([f32; 3], Vector3<f32>, f32); 3] = [
+    ([-50.0, -50.0, -50.0], vector![0.839, 0.007, 0.497], 0.50),
+    ([0.0, -50.0, -50.0], vector![0.607, 0.309, 0.588], 0.75),
+    ([-50.0, -50.0, 0.0], vector![0.0, 0.219, 0.658], 1.00),
+];
+
+// TraceImage holds a value per pixel for each output, in rows from the top left
+pub struct TraceImage {
+    pub width: usize,
+    pub height: usize,
+    // Distance from the camera to the surface, infinite where the ray misses
+    pub depth: Vec<f32>,
+    // Outward surface normals, zero where the ray misses
+    pub normals: Vec<Vector3<f32>>,
+    // Shaded colors, the background where the ray misses
+    pub colors: Vec<Vector3<f32>>,
+}
+
+#[derive(Copy, Clone)]
+struct Pixel {
+    depth: f32,
+    normal: Vector3<f32>,
+    color: Vector3<f32>,
+}
+
+const MISS: Pixel = Pixel {
+    depth: f32::INFINITY,
+    normal: vector![0.0, 0.0, 0.0],
+    color: BACKGROUND_COLOR,
+};
+
+impl TraceImage {
+    // write_ppm writes the colors as a binary PPM, which most image viewers can open
+    pub fn write_ppm<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "P6")?;
+        writeln!(writer, "{} {}", self.width, self.height)?;
+        writeln!(writer, "255")?;
+
+        for color in &self.colors {
+            writer.write_all(
+                &color
+                    .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+                    .data
+                    .0[0],
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+// trace renders the surface as the camera sees it, split into tiles across every available core
+pub fn trace(surface: &Surface, camera: &Camera, width: usize, height: usize) -> TraceImage {
+    let mut image = TraceImage {
+        width,
+        height,
+        depth: vec![MISS.depth; width * height],
+        normals: vec![MISS.normal; width * height],
+        colors: vec![MISS.color; width * height],
+    };
+    if surface.empty() || width == 0 || height == 0 {
+        return image;
+    }
+
+    let tiles: Vec<(usize, usize)> = (0..height)
+        .step_by(TILE_SIZE)
+        .flat_map(|y| (0..width).step_by(TILE_SIZE).map(move |x| (x, y)))
+        .collect();
+    let tracer = Tracer::new(surface, camera, width, height);
+
+    // Threads take the next tile until there are none left, so slow tiles don't hold others up
+    let next_tile = AtomicUsize::new(0);
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let traced: Vec<Vec<(usize, Pixel)>> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.min(tiles.len()))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut pixels = vec![];
+                    while let Some(&(x0, y0)) = tiles.get(next_tile.fetch_add(1, Ordering::Relaxed))
+                    {
+                        for y in y0..(y0 + TILE_SIZE).min(height) {
+                            for x in x0..(x0 + TILE_SIZE).min(width) {
+                                pixels.push((y * width + x, tracer.pixel(x, y)));
+                            }
+                        }
+                    }
+                    pixels
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .map(|w| w.join().expect("trace thread finishes"))
+            .collect()
+    });
+
+    for (i, pixel) in traced.into_iter().flatten() {
+        image.depth[i] = pixel.depth;
+        image.normals[i] = pixel.normal;
+        image.colors[i] = pixel.color;
+    }
+
+    image
+}
+
+struct Tracer<'a> {
+    surface: &'a Surface,
+    eye: Point3<f32>,
+    // Clip space back to world space
+    unproject: Matrix4<f32>,
+    bounds: (Point3<f32>, Point3<f32>),
+    max_step: f32,
+    width: usize,
+    height: usize,
+}
+
+impl<'a> Tracer<'a> {
+    fn new(surface: &'a Surface, camera: &Camera, width: usize, height: usize) -> Self {
+        let view_projection = camera.view_projection(width as f32 / height as f32);
+
+        let bounds = surface.bounds();
+
+        Tracer {
+            surface,
+            eye: camera.eye(),
+            unproject: view_projection
+                .try_inverse()
+                .expect("camera projection can be inverted"),
+            bounds,
+            max_step: (bounds.1 - bounds.0).magnitude() / MIN_STEPS_ACROSS,
+            width,
+            height,
+        }
+    }
+
+    // ray goes through the middle of a pixel, from the near plane towards the far plane
+    fn ray(&self, x: usize, y: usize) -> (Point3<f32>, Vector3<f32>) {
+        let ndc_x = ((x as f32 + 0.5) / self.width as f32) * 2.0 - 1.0;
+        let ndc_y = 1.0 - ((y as f32 + 0.5) / self.height as f32) * 2.0;
+
+        let unproject = |z: f32| {
+            let p = self.unproject * Vector4::new(ndc_x, ndc_y, z, 1.0);
+            point![p.x / p.w, p.y / p.w, p.z / p.w]
+        };
+        let (near, far) = (unproject(-1.0), unproject(1.0));
+
+        (near, (far - near).normalize())
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> Pixel {
+        let (origin, direction) = self.ray(x, y);
+
+        let Some((mut t, t_far)) = intersect_box(origin, direction, self.bounds) else {
+            return MISS;
+        };
+
+        let at = |t: f32| origin + direction.scale(t);
+
+        let mut previous = t;
+        for _ in 0..MAX_STEPS {
+            let value = self.surface.sample(at(t));
+
+            if value.abs() < HIT_DISTANCE {
+                return self.shade(at(t));
+            }
+            if value < 0.0 {
+                return self.shade(at(self.bisect(at, previous, t)));
+            }
+
+            previous = t;
+            t += (value * STEP_SCALE).clamp(HIT_DISTANCE, self.max_step);
+            if t > t_far {
+                break;
+            }
+        }
+
+        MISS
+    }
+
+    // bisect narrows down where the surface is between an outside and an inside point on a ray
+    fn bisect(&self, at: impl Fn(f32) -> Point3<f32>, mut outside: f32, mut inside: f32) -> f32 {
+        for _ in 0..BISECTION_STEPS {
+            let middle = (outside + inside) / 2.0;
+            if self.surface.sample(at(middle)) < 0.0 {
+                inside = middle;
+            } else {
+                outside = middle;
+            }
+        }
+
+        (outside + inside) / 2.0
+    }
+
+    fn shade(&self, p: Point3<f32>) -> Pixel {
+        let normal = normal(self.surface, p).unwrap_or_else(Vector3::zeros);
+        let (_, material) = self.surface.sample_material(p);
+
+        let mut diffuse = BACKGROUND_COLOR.scale(AMBIENT);
+        let mut specular = Vector3::zeros();
+        for (light, light_color, intensity) in LIGHTS {
+            let to_light = (p - Point3::from(light)).normalize();
+
+            diffuse += light_color.scale(intensity * DIFFUSE_K * normal.dot(&to_light));
+
+            let to_camera = (self.eye - p).normalize();
+            let halfway = (to_light + to_camera).normalize();
+            let shininess = 128.0 + (2.0 - 128.0) * material.roughness;
+            let highlight = normal.dot(&halfway).max(0.0).powf(shininess);
+            specular += light_color.scale(intensity * (1.0 - material.roughness) * highlight);
+        }
+
+        Pixel {
+            depth: (p - self.eye).magnitude(),
+            normal,
+            color: material.color.component_mul(&diffuse) + specular,
+        }
+    }
+}
+
+// intersect_box returns how far along the ray it enters and leaves the box, if it hits it at all
+fn intersect_box(
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+    (min, max): (Point3<f32>, Point3<f32>),
+) -> Option<(f32, f32)> {
+    let mut t_near = 0.0_f32;
+    let mut t_far = f32::INFINITY;
+
+    for axis in 0..3 {
+        let inverse = 1.0 / direction[axis];
+        let a = (min[axis] - origin[axis]) * inverse;
+        let b = (max[axis] - origin[axis]) * inverse;
+
+        // A ray parallel to the slab is either always in it, giving infinities, or never is
+        if a.is_nan() || b.is_nan() {
+            if origin[axis] < min[axis] || origin[axis] > max[axis] {
+                return None;
+            }
+            continue;
+        }
+
+        t_near = t_near.max(a.min(b));
+        t_far = t_far.min(a.max(b));
+    }
+
+    (t_near <= t_far).then_some((t_near, t_far))
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{Matrix4, point, vector};
+
+    use crate::Camera;
+    use crate::shapes::{Material, Shape};
+    use crate::surface::Surface;
+    use crate::trace::{BACKGROUND_COLOR, trace};
+
+    fn sphere() -> Surface {
+        let mut surface = Surface::new();
+        surface.push(
+            Matrix4::identity(),
+            Shape::Sphere(1.0),
+            Material::new(vector![1.0, 1.0, 1.0]),
+            &[],
+        );
+
+        surface
+    }
+
+    fn camera() -> Camera {
+        Camera::new(point![0.0, 0.0, 10.0], point![0.0, 0.0, 0.0], 60.0)
+    }
+
+    #[test]
+    fn center_hits_sphere() {
+        let image = trace(&sphere(), &camera(), 64, 64);
+
+        // The middle four pixels are just off the axis
+        let center = 32 * 64 + 32;
+        assert!(
+            (image.depth[center] - 9.0).abs() < 0.01,
+            "{}",
+            image.depth[center]
+        );
+        assert!(image.normals[center].z > 0.99, "{}", image.normals[center]);
+        assert_ne!(image.colors[center], BACKGROUND_COLOR);
+    }
+
+    #[test]
+    fn corners_miss() {
+        let image = trace(&sphere(), &camera(), 64, 64);
+
+        for i in [0, 63, 63 * 64, 64 * 64 - 1] {
+            assert_eq!(image.depth[i], f32::INFINITY);
+            assert_eq!(image.colors[i], BACKGROUND_COLOR);
+        }
+    }
+
+    #[test]
+    fn every_tile_is_traced() {
+        // Sizes that aren't a multiple of the tile size leave partial tiles at the edges
+        let (width, height) = (75, 41);
+        let image = trace(&sphere(), &camera(), width, height);
+
+        let hits = image.depth.iter().filter(|d| d.is_finite()).count();
+        assert!(hits > 0);
+
+        // The sphere is round, so it covers the same number of pixels on both sides of the middle
+        let left = (0..height)
+            .flat_map(|y| (0..width / 2).map(move |x| y * width + x))
+            .filter(|i| image.depth[*i].is_finite())
+            .count();
+        let right = (0..height)
+            .flat_map(|y| (width - width / 2..width).map(move |x| y * width + x))
+            .filter(|i| image.depth[*i].is_finite())
+            .count();
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn ppm_has_every_pixel() {
+        let image = trace(&sphere(), &camera(), 20, 10);
+
+        let mut written = vec![];
+        image.write_ppm(&mut written).unwrap();
+
+        let header = b"P6\n20 10\n255\n";
+        assert!(written.starts_with(header));
+        assert_eq!(written.len(), header.len() + 20 * 10 * 3);
+    }
+}
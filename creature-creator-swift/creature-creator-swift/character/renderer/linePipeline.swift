@@ -32,18 +32,14 @@ func lineSegment(
     from: simd_float3,
     to: simd_float3,
     color: simd_float3,
-    shape: UInt32, // 0 = rectangle, 1 = triangle
-    thickness: Float,
-    dash_size: Float,
-    dash_offset: Float
+    shape: UInt32, // 0 = rectangle, 1 = triangle, 2 = dot
+    thickness: Float
 ) -> LineSegment {
     LineSegment(
         from.x, from.y, from.z,
         to.x, to.y, to.z,
         color.x, color.y, color.z,
         thickness,
-        dash_size,
-        shape,
-        dash_offset
+        shape
     )
 }
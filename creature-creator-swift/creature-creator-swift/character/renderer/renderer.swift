@@ -71,9 +71,7 @@ struct RendererView: NSViewRepresentable {
                     to: simd_float3(0, 0, 5),
                     color: simd_float3(),
                     shape: 0,
-                    thickness: 1.0,
-                    dash_size: 0.0,
-                    dash_offset: 0.0
+                    thickness: 1.0
                 )
             ]
             
@@ -1,126 +1,33 @@
-use std::f32::consts::PI;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
 use std::time::Instant;
 
-use nalgebra::{point, vector, Vector3};
-use winit::dpi::{LogicalSize, PhysicalSize};
+use nalgebra::{point, Point2};
+use winit::dpi::{LogicalSize, PhysicalPosition, PhysicalSize};
 use winit::event_loop::EventLoopWindowTarget;
 use winit::raw_window_handle::HasWindowHandle;
 use winit::window::{Window, WindowBuilder};
 
 use creature_creator_metal_renderer::MetalRenderer;
+use creature_creator_renderer::export::obj;
+use creature_creator_renderer::export::points::PointFormat;
 use creature_creator_renderer::lines::Line;
-use creature_creator_renderer::shapes::Shape;
-use creature_creator_renderer::{Camera, NodeId, NodeMut, RenderGraph, Renderer};
+use creature_creator_renderer::{Camera, RenderGraph, Renderer};
 
-use crate::bones::Bone;
+use crate::character::Character;
 
-struct Character {
-    root_id: NodeId,
-
-    arm: Bone,
-    forearm: Bone,
-}
-
-impl Character {
-    fn new(render_graph: &mut RenderGraph, root_id: NodeId) -> Self {
-        let mut root_node = render_graph.node_mut(root_id);
-
-        root_node.with_transform(|t| {
-            t.position = point![0.0, 0.0, 0.0];
-            t.rotation = vector![0.0, 45.0, 0.0];
-        });
-        let root_id = root_node.node_id();
-
-        let arm = Bone::new(root_node.push_empty(), 10.0, |mut s| {
-            s.push_shape(Shape::Sphere(0.5));
-        });
-        let forearm = Bone::new(render_graph.node_mut(arm.next_joint_id), 10.0, |mut s| {
-            s.push_shape(Shape::Sphere(0.5));
-        });
-
-        Self {
-            root_id,
-            arm,
-            forearm,
-        }
-    }
-
-    fn update_animation(&self, render_graph: &mut RenderGraph, seconds: f32) {
-        let wiggle = oscillation(seconds, 0.75, 0.0, 1.0);
-
-        let mut elbow_node = render_graph.node_mut(self.forearm.joint_id);
-
-        elbow_node.with_transform(|t| {
-            t.rotation = Vector3::lerp(&vector![0.0, 0.0, 0.0], &vector![0.0, 0.0, 90.0], wiggle)
-        })
-    }
-}
-
-fn oscillation(seconds: f32, period: f32, min: f32, max: f32) -> f32 {
-    assert!(min < max);
-
-    let o = ((((seconds + (period / 4.0)) * (PI / period) * 2.0).sin() / 2.0) + 0.5) * (max - min)
-        + min;
-
-    assert!(o >= min);
-    assert!(o <= max);
-
-    return o;
-}
-
-fn grid(mut root: NodeMut, size: f32, step: f32) {
-    let start = -(size / 2.0);
-
-    let mut grid_line_position = start;
-    while grid_line_position <= -start {
-        let mut x_line = root.push_line(Line::new(size));
-        x_line.with_transform(|t| {
-            t.position = point![grid_line_position, 0.0, 0.0];
-            t.rotation = vector![90.0, 0.0, 0.0];
-        });
-
-        let mut y_line = root.push_line(Line::new(size));
-        y_line.with_transform(|t| {
-            t.position = point![0.0, 0.0, grid_line_position];
-            t.rotation = vector![0.0, 0.0, 90.0];
-        });
-
-        grid_line_position += step
-    }
-}
-
-fn cardinal_arrows(mut root: NodeMut, magnitude: f32) {
-    root.push_line(
-        Line::new_arrow(magnitude)
-            .thickness(0.2)
-            .color(vector![1.0, 0.0, 0.0]),
-    )
-    .with_transform(|t| {
-        t.rotation = vector![0.0, 0.0, -90.0];
-    });
-
-    root.push_line(
-        Line::new_arrow(magnitude)
-            .thickness(0.2)
-            .color(vector![0.0, 1.0, 0.0]),
-    );
-
-    root.push_line(
-        Line::new_arrow(magnitude)
-            .thickness(0.2)
-            .color(vector![0.0, 0.0, 1.0]),
-    )
-    .with_transform(|t| {
-        t.rotation = vector![90.0, 0.0, 0.0];
-    })
-}
+// Enough particles to keep the creature smooth, without slowing the sampling down too much
+const MAX_PARTICLES: usize = 100_000;
 
 pub struct App {
     #[allow(dead_code)] // Window is never used after initialization but it can't be dropped
     window: Window,
 
-    start: Instant,
+    last_update: Instant,
     character: Character,
+    // Where the mouse is in the window, in pixels
+    cursor: Point2<f32>,
 
     renderer: MetalRenderer,
     render_graph: RenderGraph,
@@ -137,6 +44,7 @@ impl App {
         let mut renderer = MetalRenderer::new(
             &window.window_handle().unwrap(),
             Camera::new(point![40.0, 40.0, 40.0], point![0.0, 0.0, 0.0], 60.0),
+            MAX_PARTICLES,
         );
         renderer.rescaled(window.scale_factor());
         let size = window.inner_size();
@@ -146,8 +54,8 @@ impl App {
         let mut root_node = render_graph.root_mut();
 
         let mut ui_node = root_node.push_empty();
-        grid(ui_node.push_empty(), 100.0, 5.0);
-        cardinal_arrows(ui_node.push_empty(), 5.0);
+        ui_node.push_line(Line::new_grid(100.0, 5.0).pixel_thickness(2.0));
+        ui_node.push_line(Line::new_axes(5.0).thickness(0.2));
 
         let character_node_id = root_node.push_empty().node_id();
 
@@ -155,8 +63,9 @@ impl App {
 
         App {
             window,
-            start: Instant::now(),
+            last_update: Instant::now(),
             character,
+            cursor: Point2::origin(),
             renderer,
             render_graph,
         }
@@ -170,11 +79,114 @@ impl App {
         self.renderer.resized((new_size.width, new_size.height));
     }
 
+    pub fn cursor_moved(&mut self, position: PhysicalPosition<f64>) {
+        self.cursor = point![position.x as f32, position.y as f32];
+    }
+
+    // click prints the bone under the cursor, picking either side of the mirror gives the same one
+    pub fn click(&self) {
+        let picked = self.renderer.pick(&self.render_graph, self.cursor);
+        match picked.and_then(|id| self.character.bone_name(&self.render_graph, id)) {
+            Some(name) => println!("Picked {name}"),
+            None => println!("Nothing picked"),
+        }
+    }
+
+    // export_particles writes the particles in the format the path's extension is for
+    pub fn export_particles(&self, path: &str) {
+        let Some(format) = PointFormat::from_path(Path::new(path)) else {
+            eprintln!("Couldn't export particles: {path} isn't a .ply or .xyz file");
+            return;
+        };
+
+        let result = File::create(path).and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            self.renderer.export_points(format, &mut writer)?;
+            writer.flush()
+        });
+
+        match result {
+            Ok(()) => println!("Exported particles to {path}"),
+            Err(e) => eprintln!("Couldn't export particles: {e}"),
+        }
+    }
+
+    pub fn export_diagram(&self) {
+        let path = "diagram.svg";
+
+        let result = File::create(path).and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            self.renderer.export_diagram(&self.render_graph, &mut writer)?;
+            writer.flush()
+        });
+
+        match result {
+            Ok(()) => println!("Exported diagram to {path}"),
+            Err(e) => eprintln!("Couldn't export diagram: {e}"),
+        }
+    }
+
+    pub fn export_particle_mesh(&self) {
+        let path = "particles.obj";
+
+        let mesh = self.renderer.particle_mesh();
+        let result = File::create(path).and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            obj::write(&mesh, &mut writer)?;
+            writer.flush()
+        });
+
+        match result {
+            Ok(()) => println!("Exported particle mesh to {path}"),
+            Err(e) => eprintln!("Couldn't export particle mesh: {e}"),
+        }
+    }
+
+    pub fn print_sampling_stats(&self) {
+        let stats = self.renderer.sampling_stats();
+        println!(
+            "Particles {} living, {} born, {} died, churn {:.3}",
+            stats.living,
+            stats.born,
+            stats.died,
+            stats.churn()
+        );
+        println!(
+            "Repulsion energy mean {:.4}, variance {:.4}, desired {:.4}, converged: {}",
+            stats.mean_energy,
+            stats.energy_variance,
+            stats.desired_energy,
+            stats.converged()
+        );
+
+        // Normals are only reported when something is wrong with them
+        let normals = self.renderer.particle_normal_report();
+        if !normals.ok() || stats.repaired_normals > 0 {
+            println!(
+                "Normals checked {}, invalid {}, inward {}, inconsistent {}, repaired {}",
+                normals.checked,
+                normals.invalid,
+                normals.inward,
+                normals.inconsistent,
+                stats.repaired_normals
+            );
+        }
+    }
+
+    pub fn toggle_wave(&mut self) {
+        self.character.toggle_wave()
+    }
+
+    pub fn toggle_reach(&mut self) {
+        self.character.toggle_reach()
+    }
+
     fn update(&mut self) {
-        let seconds = self.start.elapsed().as_secs_f32();
+        let now = Instant::now();
+        let dt = (now - self.last_update).as_secs_f32();
+        self.last_update = now;
 
-        self.character
-            .update_animation(&mut self.render_graph, seconds);
+        self.character.update_animation(&mut self.render_graph, dt);
     }
 
     pub fn draw(&mut self) {
@@ -0,0 +1,187 @@
+use nalgebra::{point, Point3, vector};
+
+use creature_creator_renderer::animation::{
+    Channel, Clip, Interpolation, LoopMode, Pose, Property, StateId, StateMachine, Transition,
+};
+use creature_creator_renderer::export::Joint;
+use creature_creator_renderer::ik::{Chain, Limit, two_bone};
+use creature_creator_renderer::labels::{Anchor, Label};
+use creature_creator_renderer::lines::Line;
+use creature_creator_renderer::shapes::{Material, Shape};
+use creature_creator_renderer::skeleton::Skeleton;
+use creature_creator_renderer::symmetry::Symmetry;
+use creature_creator_renderer::{NodeId, RenderGraph};
+
+const ARM_RIG: &str = include_str!("../rigs/arm.rig");
+
+// Only the window animates the character, exporting and tracing just build it
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub struct Character {
+    root_id: NodeId,
+
+    skeleton: Skeleton,
+    rest: Pose,
+    seconds: f32,
+
+    animation: StateMachine,
+    wave: StateId,
+    still: StateId,
+    // Played on top of whatever the state machine is doing
+    sway: Clip,
+
+    // While reaching, the hand follows the target and IK works out the arm
+    reaching: bool,
+    arm_chain: Chain,
+    mirror_id: NodeId,
+    target_id: NodeId,
+}
+
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+impl Character {
+    pub fn new(render_graph: &mut RenderGraph, root_id: NodeId) -> Self {
+        let mut root_node = render_graph.node_mut(root_id);
+
+        root_node.with_transform(|t| {
+            t.position = point![0.0, 0.0, 0.0];
+            t.rotation = vector![0.0, 45.0, 0.0];
+        });
+        let root_id = root_node.node_id();
+
+        // Everything under the mirror node is copied onto the other side of the character
+        let mirror_id = root_node
+            .push_symmetry(Symmetry::new_mirror(vector![1.0, 0.0, 0.0]))
+            .node_id();
+
+        let mut skeleton = Skeleton::from_rig(ARM_RIG).expect("arm rig should be valid");
+        let arm = skeleton.find("arm").expect("arm rig has an arm");
+        let forearm = skeleton.find("forearm").expect("arm rig has a forearm");
+        skeleton
+            .attach(
+                arm,
+                Shape::Sphere(0.5),
+                Material::new(vector![0.839, 0.007, 0.497]),
+            )
+            .attach(
+                forearm,
+                Shape::Sphere(0.5),
+                Material::new(vector![0.0, 0.219, 0.658]).roughness(0.3),
+            );
+        skeleton.build(render_graph, mirror_id);
+
+        // Bone names, off to the side so they don't cover the skin
+        for (_, bone) in skeleton.iter() {
+            let joint_id = bone.joint_id().expect("skeleton is built");
+            render_graph.node_mut(joint_id).push_label(
+                Label::new(bone.name.as_str())
+                    .anchor(Anchor::Above)
+                    .leader(vector![0.0, 1.0, 2.0]),
+            );
+        }
+
+        let joint_id = |bone| skeleton.bone(bone).joint_id().expect("skeleton is built");
+
+        // The elbow bends and straightens, easing in and out of each end
+        let mut animation = StateMachine::new(
+            Clip::new("wave", 0.75).looping(LoopMode::Repeat).channel(
+                Channel::new(joint_id(forearm), Property::Rotation)
+                    .key(0.0, vector![0.0, 0.0, 0.0], Interpolation::EASE_IN_OUT)
+                    .key(0.375, vector![0.0, 0.0, 90.0], Interpolation::EASE_IN_OUT)
+                    .key(0.75, vector![0.0, 0.0, 0.0], Interpolation::EASE_IN_OUT),
+            ),
+        );
+        // Nothing is animated, so the arm goes back to its rest pose
+        let still = animation.add_state(Clip::new("still", 0.0));
+        let wave = animation.current();
+        animation.add_transition(Transition::new(wave, still, 0.3));
+        animation.add_transition(Transition::new(still, wave, 0.3));
+
+        // The whole arm swings slowly back and forth
+        let sway = Clip::new("sway", 3.0).looping(LoopMode::PingPong).channel(
+            Channel::new(joint_id(arm), Property::Rotation)
+                .key(0.0, vector![-10.0, 0.0, 0.0], Interpolation::EASE_IN_OUT)
+                .key(3.0, vector![10.0, 0.0, 0.0], Interpolation::EASE_IN_OUT),
+        );
+
+        // The elbow is a hinge, it bends around Z like the wave does and not backwards
+        let arm_chain = skeleton.chain(arm, forearm).limit(
+            1,
+            Limit::new(vector![0.0, 0.0, 0.0], vector![0.0, 0.0, 150.0]),
+        );
+        let target_id = render_graph
+            .node_mut(mirror_id)
+            .push_line(Line::new_sphere(0.5).color(vector![1.0, 0.5, 0.0]))
+            .set_hidden(true)
+            .node_id();
+
+        Self {
+            root_id,
+            rest: skeleton.rest_pose(),
+            skeleton,
+            seconds: 0.0,
+            animation,
+            wave,
+            still,
+            sway,
+            reaching: false,
+            arm_chain,
+            mirror_id,
+            target_id,
+        }
+    }
+
+    // joints are the nodes exported as a skeleton, the mirrored copies get their own joints
+    pub fn joints(&self) -> Vec<Joint> {
+        self.skeleton.joints()
+    }
+
+    // toggle_wave fades between waving and holding the arm still
+    pub fn toggle_wave(&mut self) {
+        if self.animation.current() == self.wave {
+            self.animation.go_to(self.still);
+        } else {
+            self.animation.go_to(self.wave);
+        }
+    }
+
+    // bone_name is the name of the bone a node, like a picked skin shape, belongs to
+    pub fn bone_name(&self, render_graph: &RenderGraph, node_id: NodeId) -> Option<&str> {
+        let bone = self.skeleton.owner(render_graph, node_id)?;
+        Some(&self.skeleton.bone(bone).name)
+    }
+
+    // toggle_reach switches between animating the arm and having it reach for a moving target
+    pub fn toggle_reach(&mut self) {
+        self.reaching = !self.reaching
+    }
+
+    pub fn update_animation(&mut self, render_graph: &mut RenderGraph, dt: f32) {
+        self.seconds += dt;
+        self.animation.update(dt);
+
+        let mut pose = self.rest.clone();
+        self.animation.sample(&mut pose);
+
+        let mut sway = self.rest.clone();
+        self.sway.sample(self.seconds, &mut sway);
+        pose.add(&sway, &self.rest, 1.0, None);
+
+        pose.apply(render_graph);
+
+        let angle = self.seconds;
+        let mut target_node = render_graph.node_mut(self.target_id);
+        target_node.set_hidden(!self.reaching);
+        target_node.with_transform(|t| {
+            // Going round in the plane the elbow bends in
+            t.position = point![-3.0 + 6.0 * angle.cos(), 8.0 + 6.0 * angle.sin(), 0.0];
+        });
+        if self.reaching {
+            let target = render_graph
+                .world_transform(self.target_id)
+                .transform_point(&Point3::origin());
+            let pole = render_graph
+                .world_transform(self.mirror_id)
+                .transform_point(&point![23.0, 10.0, 0.0]);
+            two_bone(render_graph, &self.arm_chain, target, pole);
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::process;
+
+use creature_creator_renderer::export::{export, Format};
+use creature_creator_renderer::RenderGraph;
+
+use crate::character::Character;
+
+const DEFAULT_CELL_SIZE: f32 = 0.1;
+
+fn usage() -> ! {
+    eprintln!("usage: creature-creator export <path.obj|path.stl|path.glb> [cell size]");
+    process::exit(2)
+}
+
+// run exports the character without opening a window, the format comes from the file extension
+pub fn run(args: &[String]) {
+    let (path, cell_size) = match args {
+        [path] => (Path::new(path), DEFAULT_CELL_SIZE),
+        [path, cell_size] => (
+            Path::new(path),
+            cell_size.parse().unwrap_or_else(|_| usage()),
+        ),
+        _ => usage(),
+    };
+    if cell_size <= 0.0 {
+        usage()
+    }
+    let format = Format::from_path(path).unwrap_or_else(|| usage());
+
+    let mut render_graph = RenderGraph::new();
+    let character_id = render_graph.root_mut().push_empty().node_id();
+    let character = Character::new(&mut render_graph, character_id);
+
+    let result = File::create(path).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        export(
+            &render_graph,
+            &character.joints(),
+            format,
+            cell_size,
+            &mut writer,
+        )?;
+        writer.flush()
+    });
+
+    match result {
+        Ok(()) => println!("Exported to {}", path.display()),
+        Err(e) => {
+            eprintln!("Couldn't export to {}: {e}", path.display());
+            process::exit(1)
+        }
+    }
+}
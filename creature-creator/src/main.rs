@@ -1,17 +1,48 @@
+use std::env;
+#[cfg(not(target_os = "macos"))]
+use std::process;
+
+#[cfg(target_os = "macos")]
 use winit::{
     event::{Event, WindowEvent},
     event_loop::EventLoop,
 };
-use winit::event::StartCause;
+#[cfg(target_os = "macos")]
+use winit::event::{ElementState, MouseButton, StartCause};
+#[cfg(target_os = "macos")]
 use winit::event_loop::ControlFlow;
+#[cfg(target_os = "macos")]
 use winit::keyboard::{Key, NamedKey};
 
+#[cfg(target_os = "macos")]
 use crate::app::App;
 
+// The window draws with Metal, so it's only on macOS. Exporting and tracing run anywhere
+#[cfg(target_os = "macos")]
 mod app;
-mod bones;
+mod character;
+mod export;
+mod trace;
 
 fn main() {
+    // `creature-creator export <path> [cell size]` writes the creature to a file instead
+    // `creature-creator trace <path> [width height]` renders it to an image on the cpu
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("export") => export::run(&args[1..]),
+        Some("trace") => trace::run(&args[1..]),
+        _ => run_window(),
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn run_window() {
+    eprintln!("the window needs Metal, use `creature-creator export` or `creature-creator trace`");
+    process::exit(2)
+}
+
+#[cfg(target_os = "macos")]
+fn run_window() {
     let event_loop = EventLoop::new().unwrap();
     let mut app = None;
 
@@ -28,10 +59,41 @@ fn main() {
                     app.as_mut().unwrap().scale_factor_changed(scale_factor);
                 }
                 WindowEvent::Resized(size) => app.as_mut().unwrap().resized(size),
+                WindowEvent::CursorMoved { position, .. } => {
+                    app.as_mut().unwrap().cursor_moved(position)
+                }
+                // Clicking prints the bone under the cursor
+                WindowEvent::MouseInput {
+                    state: ElementState::Pressed,
+                    button: MouseButton::Left,
+                    ..
+                } => app.as_ref().unwrap().click(),
                 WindowEvent::KeyboardInput { event, .. } => {
                     if event.logical_key == Key::Named(NamedKey::Escape) {
                         event_loop.exit()
                     }
+
+                    // P saves the surface particles, for looking at in a point cloud viewer
+                    // X saves just their positions, for tools that don't read PLY
+                    // M saves them triangulated into a mesh
+                    // D saves the lines as an SVG diagram, hidden where the particles cover them
+                    // S prints the sampler's statistics for the last update
+                    // W starts and stops the character waving
+                    // R has the character reach for a target instead
+                    if let Key::Character(c) = &event.logical_key {
+                        if event.state == ElementState::Pressed {
+                            match c.as_str() {
+                                "p" => app.as_ref().unwrap().export_particles("particles.ply"),
+                                "x" => app.as_ref().unwrap().export_particles("particles.xyz"),
+                                "m" => app.as_ref().unwrap().export_particle_mesh(),
+                                "d" => app.as_ref().unwrap().export_diagram(),
+                                "s" => app.as_ref().unwrap().print_sampling_stats(),
+                                "w" => app.as_mut().unwrap().toggle_wave(),
+                                "r" => app.as_mut().unwrap().toggle_reach(),
+                                _ => (),
+                            }
+                        }
+                    }
                 }
                 _ => (),
             },
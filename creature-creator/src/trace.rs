@@ -0,0 +1,52 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::process;
+
+use nalgebra::point;
+
+use creature_creator_renderer::surface::Surface;
+use creature_creator_renderer::trace::trace;
+use creature_creator_renderer::{Camera, RenderGraph};
+
+use crate::character::Character;
+
+const DEFAULT_SIZE: (usize, usize) = (800, 600);
+
+fn usage() -> ! {
+    eprintln!("usage: creature-creator trace <path.ppm> [width height]");
+    process::exit(2)
+}
+
+// run sphere traces the character from the same camera as the window, without needing a gpu
+pub fn run(args: &[String]) {
+    let parse = |s: &String| s.parse().unwrap_or_else(|_| usage());
+    let (path, (width, height)) = match args {
+        [path] => (path, DEFAULT_SIZE),
+        [path, width, height] => (path, (parse(width), parse(height))),
+        _ => usage(),
+    };
+    if width == 0 || height == 0 {
+        usage()
+    }
+
+    let mut render_graph = RenderGraph::new();
+    let character_id = render_graph.root_mut().push_empty().node_id();
+    Character::new(&mut render_graph, character_id);
+
+    let camera = Camera::new(point![40.0, 40.0, 40.0], point![0.0, 0.0, 0.0], 60.0);
+    let image = trace(&Surface::from_graph(&render_graph), &camera, width, height);
+
+    let result = File::create(path).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        image.write_ppm(&mut writer)?;
+        writer.flush()
+    });
+
+    match result {
+        Ok(()) => println!("Traced to {path}"),
+        Err(e) => {
+            eprintln!("Couldn't trace to {path}: {e}");
+            process::exit(1)
+        }
+    }
+}